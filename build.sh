@@ -2,4 +2,4 @@
 
 set -e
 
-cargo build --examples --release --all-features
+cargo build --examples --release --features panic-handler,trap-handler,sdcard
@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(asm)]
 
+#[cfg(not(feature = "panic-handler"))]
 use panic_halt as _;
 
 use core::fmt::Write;
@@ -23,6 +24,8 @@ use gd32vf103xx_hal::prelude::*;
 use gd32vf103xx_hal::timer;
 // board support
 use longan_nano_playground::adc::{self, Adc, Temperature, Vrefint};
+use longan_nano_playground::lcd::config::LcdConfig;
+use longan_nano_playground::ui::{self, layout, Chart, Grid, Readout, Scale, Stack};
 use longan_nano_playground::{lcd, lcd_pins, sprintln};
 use longan_nano_playground::{stdout, ByteMutWriter};
 
@@ -96,7 +99,8 @@ fn main() -> ! {
 
     // LCD
     let lcd_pins = lcd_pins!(gpioa, gpiob);
-    let mut lcd = lcd::configure(dp.SPI0, lcd_pins, &mut afio, &mut rcu);
+    let mut lcd =
+        lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu).unwrap();
     let (width, height) = (lcd.size().width as i32, lcd.size().height as i32);
 
     // LED
@@ -134,6 +138,14 @@ fn main() -> ! {
         let _ = block!(timer.wait());
     }
 
+    // temperature and Vref on top, temperature history below
+    cls!();
+    let mut rows = Stack::vertical(layout::screen(width as u32, height as u32)).gap(2);
+    let top = Grid::new(rows.take(12), 2, 1).gap(4);
+    let mut temp_readout = Readout::new(top.cell(0, 0), Font6x12, "C").decimals(1);
+    let mut vref_readout = Readout::new(top.cell(1, 0), Font6x12, "mV");
+    let mut chart = Chart::new(rows.rest(), 1).scale(Scale::Auto).grid(20, 4);
+
     loop {
         sprintln!("Hello World from UART!");
 
@@ -156,20 +168,20 @@ fn main() -> ! {
         adc.enable_software_trigger();
         */
 
-        buf.clear();
-
-        writeln!(buf, "temp: {:.2}C", temperature).unwrap();
-        writeln!(buf, "Vref: {:.4}V", vref_value).unwrap();
-        writeln!(buf, "data0: {}", adc.read_idata0()).unwrap();
-        writeln!(buf, "data1: {}", adc.read_idata1()).unwrap();
-        writeln!(buf, "data2: {}", adc.read_idata2()).unwrap();
-        writeln!(buf, "data3: {}", adc.read_idata3()).unwrap();
+        sprintln!("temp: {:.2}C", temperature);
+        sprintln!("Vref: {:.4}V", vref_value);
 
         //write!(buf, "a0: 0x{:04x}", a0_val).unwrap();
-        Text::new(buf.as_str(), Point::new(0, 0))
-            .into_styled(style)
-            .draw(&mut lcd)
-            .unwrap();
+        let decicelsius = adc::to_decicelsius(raw_temp);
+        let millivolts = adc::to_millivolts(adc.read_idata1());
+        temp_readout.set(decicelsius);
+        vref_readout.set(millivolts);
+        chart.push(&[decicelsius]);
+        ui::update(
+            &mut lcd,
+            &mut [&mut temp_readout, &mut vref_readout, &mut chart],
+        )
+        .unwrap();
 
         delay.delay_ms(500);
     }
@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(asm)]
 
+#[cfg(not(feature = "panic-handler"))]
 use panic_halt as _;
 
 use core::fmt::Write;
@@ -17,11 +18,11 @@ use embedded_hal::digital::v2::OutputPin;
 use gd32vf103xx_hal::pac;
 use gd32vf103xx_hal::prelude::*;
 // use gd32vf103xx_hal::timer;
+use longan_nano_playground::lcd::config::LcdConfig;
+use longan_nano_playground::lcd::framebuffer::Rect;
 use longan_nano_playground::{lcd, lcd_pins};
 use riscv_rt::entry;
 
-use embedded_graphics::image::{Image, ImageRaw};
-
 use gd32vf103xx_hal::delay;
 
 // spi
@@ -47,7 +48,8 @@ fn main() -> ! {
     let gpiob = dp.GPIOB.split(&mut rcu);
 
     let lcd_pins = lcd_pins!(gpioa, gpiob);
-    let mut lcd = lcd::configure(dp.SPI0, lcd_pins, &mut afio, &mut rcu);
+    let mut lcd =
+        lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu).unwrap();
     let (width, height) = (lcd.size().width as i32, lcd.size().height as i32);
 
     macro_rules! cls {
@@ -138,6 +140,8 @@ fn main() -> ! {
     buf.clear();
 
     let mut img_buf = [0u8; 106 * 80 * 2];
+    // 106x80 frames at (26, 0)
+    let frame = Rect::new(26, 0, 26 + 106 - 1, 79);
     // first volume
     'outer: loop {
         let mut vol = cntlr.get_volume(sdmmc::VolumeIdx(0)).unwrap();
@@ -194,9 +198,7 @@ fn main() -> ! {
             }
             let _ = fp.seek_from_current(106 * 80 * 2); // skip 1 frame
 
-            let raw_image: ImageRaw<Rgb565> = ImageRaw::new(&img_buf[..], 106, 80);
-            let image: Image<_, Rgb565> = Image::new(&raw_image, Point::new(26, 0));
-            image.draw(&mut lcd).unwrap();
+            lcd::blit(&mut lcd, frame, &img_buf).unwrap();
             delay.delay_ms(18_u16); // 1_000 / 24
         }
 
@@ -211,9 +213,7 @@ fn main() -> ! {
             .unwrap();
         delay.delay_ms(2_000_u16);
         cls!();
-        let raw_image: ImageRaw<Rgb565> = ImageRaw::new(&img_buf[..], 106, 80);
-        let image: Image<_, Rgb565> = Image::new(&raw_image, Point::new(26, 0));
-        image.draw(&mut lcd).unwrap();
+        lcd::blit(&mut lcd, frame, &img_buf).unwrap();
         delay.delay_ms(2_000_u16);
     }
 }
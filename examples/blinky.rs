@@ -4,6 +4,7 @@
 #![no_main]
 #![feature(asm)]
 
+#[cfg(not(feature = "panic-handler"))]
 use panic_halt as _;
 
 use gd32vf103xx_hal::delay;
@@ -5,6 +5,7 @@
 #![no_main]
 #![feature(asm)]
 
+#[cfg(not(feature = "panic-handler"))]
 use panic_halt as _;
 
 use core::fmt::Write;
@@ -15,6 +16,7 @@ use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
 use embedded_graphics::primitives::Rectangle;
 use embedded_graphics::{primitive_style, text_style};
+use longan_nano_playground::lcd::config::LcdConfig;
 use longan_nano_playground::{lcd, lcd_pins};
 // gd32vf103_pac
 use gd32vf103xx_hal::pac;
@@ -60,7 +62,8 @@ fn main() -> ! {
 
     // # LCD
     let lcd_pins = lcd_pins!(gpioa, gpiob);
-    let mut lcd = lcd::configure(dp.SPI0, lcd_pins, &mut afio, &mut rcu);
+    let mut lcd =
+        lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu).unwrap();
     let (width, height) = (lcd.size().width as i32, lcd.size().height as i32);
     macro_rules! cls {
         () => {
@@ -174,6 +177,7 @@ fn EXTI_LINE9_5() {
     }
 }
 
+#[cfg(not(feature = "trap-handler"))]
 #[allow(non_snake_case)]
 #[no_mangle]
 fn DefaultHandler() {
@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(asm)]
 
+#[cfg(not(feature = "panic-handler"))]
 use panic_halt as _;
 
 use core::fmt::Write;
@@ -16,6 +17,7 @@ use embedded_graphics::{primitive_style, text_style};
 use gd32vf103xx_hal::delay;
 use gd32vf103xx_hal::pac;
 use gd32vf103xx_hal::prelude::*;
+use longan_nano_playground::lcd::config::LcdConfig;
 use longan_nano_playground::ByteMutWriter;
 use longan_nano_playground::{lcd, lcd_pins};
 use riscv_rt::entry;
@@ -37,7 +39,8 @@ fn main() -> ! {
     let gpiob = dp.GPIOB.split(&mut rcu);
 
     let lcd_pins = lcd_pins!(gpioa, gpiob);
-    let mut lcd = lcd::configure(dp.SPI0, lcd_pins, &mut afio, &mut rcu);
+    let mut lcd =
+        lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu).unwrap();
     let (width, height) = (lcd.size().width as i32, lcd.size().height as i32);
 
     macro_rules! cls {
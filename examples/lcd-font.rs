@@ -2,11 +2,9 @@
 #![no_main]
 #![feature(asm)]
 
+#[cfg(not(feature = "panic-handler"))]
 use panic_halt as _;
 
-use core::fmt::Write;
-use longan_nano_playground::ByteMutWriter;
-
 use embedded_graphics::fonts::{Font8x16, Text};
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
@@ -16,7 +14,9 @@ use embedded_graphics::{primitive_style, text_style};
 use gd32vf103xx_hal::pac;
 use gd32vf103xx_hal::prelude::*;
 use gd32vf103xx_hal::timer;
-use longan_nano_playground::{lcd, lcd_pins};
+use longan_nano_playground::font::{self, Align};
+use longan_nano_playground::lcd::config::LcdConfig;
+use longan_nano_playground::{bitmap_font, lcd, lcd_pins};
 use riscv_rt::entry;
 #[macro_use(block)]
 extern crate nb;
@@ -32,54 +32,9 @@ ProFont24Point	The 24 point size with a character size of 16x30 pixels.
 */
 // use profont::ProFont10Point;
 
-use embedded_graphics::fonts::Font;
-use embedded_graphics::geometry::Size;
-
-#[derive(Clone, Copy)]
-pub struct ChnFont;
-
-impl Font for ChnFont {
-    const FONT_IMAGE: &'static [u8] = include_bytes!("../font.raw");
-    const FONT_IMAGE_WIDTH: u32 = 16;
-    const CHARACTER_SIZE: Size = Size::new(16, 16);
-    const VARIABLE_WIDTH: bool = true;
-
-    fn char_offset(c: char) -> u32 {
-        unreachable!()
-    }
-
-    fn char_width(c: char) -> u32 {
-        if (c as u16) < 128 {
-            8
-        } else {
-            16
-        }
-    }
-
-    fn character_pixel(c: char, x: u32, y: u32) -> bool {
-        if (c as u16) < 128 {
-            let map = &Self::FONT_IMAGE[16 * (c as usize)..16 + (c as usize) * 16];
-            return (map[y as usize] & (1 << (7 - x))) != 0;
-        }
-
-        const start: usize = 128 * 16;
-        const step: usize = 2 * 16;
-        let map = match c {
-            '卧' => &Self::FONT_IMAGE[start..start + 1 * step],
-            '槽' => &Self::FONT_IMAGE[start + 1 * step..start + 2 * step],
-            '艹' => &Self::FONT_IMAGE[start + 2 * step..start + 3 * step],
-            _ => {
-                return false;
-            }
-        };
-
-        if x >= 8 {
-            (map[y as usize * 2 + 1] & (1 << (15 - x))) != 0
-        } else {
-            (map[y as usize * 2] & (1 << (7 - x))) != 0
-        }
-    }
-}
+// ASCII and a few CJK characters of Unibit, generated with tools/font-compiler:
+// font-compiler unibit.bdf --range U+20-U+7E --chars "°气温湿度照压卧槽艹牛逼数据" -o font.bin
+bitmap_font!(ChnFont = include_bytes!("../font.bin"));
 
 // rtc
 use gd32vf103xx_hal::rtc::Rtc;
@@ -101,7 +56,8 @@ fn main() -> ! {
     let gpiob = dp.GPIOB.split(&mut rcu);
 
     let lcd_pins = lcd_pins!(gpioa, gpiob);
-    let mut lcd = lcd::configure(dp.SPI0, lcd_pins, &mut afio, &mut rcu);
+    let mut lcd =
+        lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu).unwrap();
     let (width, height) = (lcd.size().width as i32, lcd.size().height as i32);
 
     // Clear screen
@@ -118,15 +74,34 @@ fn main() -> ! {
 
     // let max_duty = pwm.try_get_max_duty().unwrap();
 
-    // 160 / 8 = 20 chars, per line
-    let mut buf = [0u8; 20];
-    let mut buf = ByteMutWriter::new(&mut buf[..]);
-
-    // Create a text at position (20, 30) and draw it using style defined above
-    Text::new("卧槽艹~ABCD卧卧槽", Point::new(0, 0))
-        .into_styled(style)
-        .draw(&mut lcd)
-        .unwrap();
+    // one glyph lookup per character, `Text` would do one per pixel
+    ChnFont::draw_text(
+        &mut lcd,
+        "卧槽艹~ABCD卧卧槽",
+        Point::new(0, 0),
+        Rgb565::WHITE,
+        Some(Rgb565::BLACK),
+    )
+    .unwrap();
+
+    // wrapped to the rest of the screen, with the widths of the font
+    let area = Rectangle::new(Point::new(0, 16), Point::new(width - 1, height - 1));
+    let overflow = font::draw_layout(
+        &mut lcd,
+        &ChnFont::font(),
+        "温度 23.5°C 湿度 61% 气压 1013hPa, 数据照压牛逼! The quick brown fox jumps over the lazy dog",
+        area,
+        Align::Center,
+        Rgb565::GREEN,
+        Some(Rgb565::BLACK),
+    )
+    .unwrap();
+    if !overflow.is_empty() {
+        Text::new("...", Point::new(width - 24, height - 16))
+            .into_styled(style)
+            .draw(&mut lcd)
+            .unwrap();
+    }
 
     loop {}
 }
@@ -0,0 +1,126 @@
+#![no_std]
+#![no_main]
+#![feature(asm)]
+
+#[cfg(not(feature = "panic-handler"))]
+use panic_halt as _;
+
+use core::fmt::Write;
+use longan_nano_playground::ByteMutWriter;
+
+use embedded_graphics::fonts::{Font6x8, Text};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::{primitive_style, text_style};
+use embedded_hal::digital::v2::OutputPin;
+// gd32vf103_pac
+use gd32vf103xx_hal::pac;
+use gd32vf103xx_hal::prelude::*;
+use longan_nano_playground::font::stream::{SdFile, StreamFont};
+use longan_nano_playground::lcd::config::LcdConfig;
+use longan_nano_playground::{lcd, lcd_pins};
+use riscv_rt::entry;
+
+use gd32vf103xx_hal::delay;
+
+// spi
+use gd32vf103xx_hal::spi::{Spi, MODE_0};
+
+// sdcard
+use embedded_sdmmc as sdmmc;
+
+// A whole 16px Unifont, too large for flash, compiled onto the SD card with
+// tools/font-compiler:
+// font-compiler unifont.bdf --range U+20-U+FFFF -o UNIFONT.BIN
+//
+// 13 distinct glyphs, fewer than `CACHE_SLOTS`, so all of them stay cached
+const TEXT: &str = "温度 23.5°C\n湿度 61%";
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    // Configure clocks
+    let mut rcu = dp
+        .RCU
+        .configure()
+        .ext_hf_clock(8.mhz())
+        .sysclk(108.mhz())
+        .freeze();
+    let mut afio = dp.AFIO.constrain(&mut rcu);
+
+    let gpioa = dp.GPIOA.split(&mut rcu);
+    let gpiob = dp.GPIOB.split(&mut rcu);
+
+    let lcd_pins = lcd_pins!(gpioa, gpiob);
+    let mut lcd =
+        lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu).unwrap();
+    let (width, height) = (lcd.size().width as i32, lcd.size().height as i32);
+
+    Rectangle::new(Point::new(0, 0), Point::new(width - 1, height - 1))
+        .into_styled(primitive_style!(fill_color = Rgb565::BLACK))
+        .draw(&mut lcd)
+        .unwrap();
+
+    let mut delay = delay::McycleDelay::new(&rcu.clocks);
+
+    // SPI1_SCK(PB13), SPI1_MISO(PB14) and SPI1_MOSI(PB15) GPIO pin configuration
+    let spi = Spi::spi1(
+        dp.SPI1,
+        (
+            gpiob.pb13.into_alternate_push_pull(),
+            gpiob.pb14.into_floating_input(),
+            gpiob.pb15.into_alternate_push_pull(),
+        ),
+        MODE_0,
+        20.mhz(),
+        &mut rcu,
+    );
+
+    let mut cs = gpiob.pb12.into_push_pull_output();
+    cs.set_low().unwrap();
+
+    let mut cntlr = sdmmc::Controller::new(sdmmc::SdMmcSpi::new(spi, cs), DummyTimeSource);
+    cntlr.device().init().unwrap();
+    let mut vol = cntlr.get_volume(sdmmc::VolumeIdx(0)).unwrap();
+    let dir = cntlr.open_root_dir(&vol).unwrap();
+
+    // Must use DOS 8.3 name
+    let file = SdFile::open(&mut cntlr, &mut vol, &dir, "UNIFONT.BIN").unwrap();
+    let mut font = StreamFont::new(file).unwrap();
+
+    let mut buf = [0u8; 40];
+    let mut buf = ByteMutWriter::new(&mut buf[..]);
+
+    loop {
+        // the first pass reads every glyph, later ones only hit the cache
+        let end = font
+            .draw_text(&mut lcd, TEXT, Point::new(0, 0), Rgb565::WHITE, Some(Rgb565::BLACK))
+            .unwrap();
+
+        // in a built-in font, so the cache only holds TEXT
+        let (hits, misses) = font.stats();
+        buf.clear();
+        write!(buf, "hits {} misses {}", hits, misses).unwrap();
+        Text::new(buf.as_str(), Point::new(0, end.y + font.height() as i32))
+            .into_styled(text_style!(
+                font = Font6x8,
+                text_color = Rgb565::GREEN,
+                background_color = Rgb565::BLACK
+            ))
+            .draw(&mut lcd)
+            .unwrap();
+
+        delay.delay_ms(1_000_u16);
+    }
+}
+
+/// Zero time as fake time source.
+pub struct DummyTimeSource;
+
+impl sdmmc::TimeSource for DummyTimeSource {
+    fn get_timestamp(&self) -> sdmmc::Timestamp {
+        sdmmc::Timestamp::from_fat(0, 0)
+    }
+}
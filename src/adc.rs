@@ -14,8 +14,6 @@ use crate::hal::delay::McycleDelay;
 use crate::hal::rcu::Rcu;
 use crate::pac::{ADC0, ADC1, RCU};
 
-use crate::sprintln;
-
 macro_rules! adc_pins {
     ($ADC:ident, $($input:ty => $chan:expr),+ $(,)*) => {
         $(
@@ -625,7 +623,7 @@ impl Adc<ADC0, Disabled> {
         self.configure();
 
         self.rb.ctl1.modify(|_, w| w.adcon().set_bit());
-        sprintln!("adc0 enabled");
+        log::debug!("adc0 enabled");
         Adc {
             rb: self.rb,
             config: self.config,
@@ -693,6 +691,26 @@ impl Adc<ADC0, Enabled> {
         self.rb.rdata.read().rdata().bits()
     }
 
+    /// Converts one channel by software trigger, e.g. 16 for the temperature
+    /// sensor. Replaces the regular sequence with this channel.
+    pub fn read_channel(&mut self, channel: u8) -> u16 {
+        unsafe {
+            // one conversion: RL = 0, RSQ0 = channel
+            self.rb.rsq0.modify(|r, w| w.bits(r.bits() & !(0xf << 20)));
+            self.rb
+                .rsq2
+                .modify(|r, w| w.bits((r.bits() & !0x1f) | (channel as u32 & 0x1f)));
+            self.rb
+                .ctl1
+                .modify(|_, w| w.etsrc().bits(config::RegularExternalTrigger::None as u8));
+        }
+        self.rb.ctl1.modify(|_, w| w.eterc().set_bit());
+        self.rb.stat.modify(|_, w| w.eoc().clear_bit());
+        self.rb.ctl1.modify(|_, w| w.swrcst().set_bit());
+        while self.rb.stat.read().eoc().bit_is_clear() {}
+        self.read_rdata()
+    }
+
     /// Read data from inserted channel 0
     pub fn read_idata0(&self) -> u16 {
         self.rb.idata0.read().idatan().bits()
@@ -711,6 +729,17 @@ impl Adc<ADC0, Enabled> {
     }
 }
 
+/// Millivolts of a 12-bit right aligned reading, with VDDA at 3.3V
+pub fn to_millivolts(raw: u16) -> i32 {
+    raw as i32 * 3300 / 4096
+}
+
+/// Temperature sensor reading in 0.1°C: (V25 - Vsense) / Avg_Slope + 25,
+/// with V25 = 1.45V and Avg_Slope = 4.1mV/°C
+pub fn to_decicelsius(raw: u16) -> i32 {
+    (1450 - to_millivolts(raw)) * 100 / 41 + 250
+}
+
 /// Internal temperature sensor
 pub struct Temperature<ED> {
     _marker: PhantomData<ED>,
@@ -0,0 +1,509 @@
+//! AT command client for ESP8266/ESP32 modules
+//!
+//! Talks to the Espressif AT firmware over any serial port, usually a
+//! `serial::Tx/Rx` pair of USART1 or USART2:
+//!
+//! ```ignore
+//! let (tx, rx) = serial.split();
+//! let mut esp = EspAt::new(tx, rx);
+//! esp.init()?;
+//! esp.send_command("AT+CWMODE=1")?;
+//!
+//! let mut buf = [0u8; 128];
+//! let resp = esp.query(format_args!("AT+CWHOSTNAME?"), &mut buf)?;
+//! for line in resp.lines() {
+//!     // +CWHOSTNAME:LWIP
+//! }
+//! ```
+//!
+//! Unsolicited lines like `WIFI CONNECTED` are queued, and can be taken with
+//! `EspAt::next_urc`. Payload of `+IPD` is kept in a receive buffer per link,
+//! see the `net` module for the socket API.
+
+use core::fmt::{self, Write};
+
+use embedded_hal::serial;
+use nb::block;
+
+mod net;
+mod parser;
+mod wifi;
+
+#[cfg(any(test, feature = "esp-at-sim"))]
+pub mod sim;
+
+pub use self::net::{Link, LinkState, TcpSocket, UdpSocket, LINK_BUFFER_CAPACITY, MAX_LINKS};
+pub use self::parser::{Event, Fields, Parser, Urc, LINE_CAPACITY};
+pub use self::wifi::{AccessPoint, Encryption, IpConfig, Ssid, WifiMode};
+
+use self::parser::trim;
+
+/// Number of queued unsolicited result codes, older ones are dropped
+const URC_CAPACITY: usize = 8;
+
+/// Bytes of the last command kept to recognize its echo
+const ECHO_CAPACITY: usize = 64;
+
+/// Default number of empty polls of the serial port before giving up
+const DEFAULT_TIMEOUT: u32 = 2_000_000;
+
+/// AT client errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Serial port read/write error
+    Serial,
+    /// No final result code in time
+    Timeout,
+    /// Command answered with `ERROR`
+    CommandFailed,
+    /// Command answered with `FAIL`
+    Fail,
+    /// `SEND FAIL`
+    SendFailed,
+    /// Response does not fit in the given buffer
+    Overflow,
+    /// `AT+CWJAP` failed: 1 timeout, 2 wrong password, 3 no such AP,
+    /// 4 connection failed
+    JoinFailed(u8),
+    /// Response is not what the command should return
+    Parse,
+    /// All links are in use
+    NoFreeLink,
+    /// Link is closed by the remote, or never connected
+    Closed,
+    /// Datagram does not fit in one `AT+CIPSEND`
+    TooLong,
+    /// TCP data did not fit in the link buffer and was lost
+    Overrun,
+}
+
+/// Response lines of a command, without the final result code
+pub struct Response<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Response<'a> {
+    /// Iterates over response lines
+    pub fn lines(&self) -> impl Iterator<Item = &'a [u8]> {
+        let buf = self.buf;
+        buf.split(|&c| c == b'\n').filter(|l| !l.is_empty())
+    }
+
+    /// First line starting with `prefix`, with the prefix removed
+    pub fn find(&self, prefix: &[u8]) -> Option<&'a [u8]> {
+        self.lines()
+            .find(|l| l.starts_with(prefix))
+            .map(|l| &l[prefix.len()..])
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+/// Collects response lines into a caller buffer
+struct Collector<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    overflow: bool,
+}
+
+impl<'a> Collector<'a> {
+    fn push_line(&mut self, line: &[u8]) {
+        if self.len + line.len() + 1 > self.buf.len() {
+            self.overflow = true;
+            return;
+        }
+        self.buf[self.len..self.len + line.len()].copy_from_slice(line);
+        self.len += line.len();
+        self.buf[self.len] = b'\n';
+        self.len += 1;
+    }
+}
+
+/// The command just sent, to tell its echo from response lines
+struct Echo {
+    buf: [u8; ECHO_CAPACITY],
+    /// Length of the whole command, line ending included
+    len: usize,
+    /// Not echoed back yet
+    pending: bool,
+}
+
+impl Echo {
+    const fn new() -> Self {
+        Echo {
+            buf: [0; ECHO_CAPACITY],
+            len: 0,
+            pending: false,
+        }
+    }
+
+    fn start(&mut self) {
+        self.len = 0;
+        self.pending = true;
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if self.len < ECHO_CAPACITY {
+                self.buf[self.len] = b;
+            }
+            self.len += 1;
+        }
+    }
+
+    /// Whether `line` is the echo of the command, longer commands are
+    /// compared on their first `ECHO_CAPACITY` bytes
+    fn matches(&self, line: &[u8]) -> bool {
+        if !self.pending {
+            return false;
+        }
+        if self.len <= ECHO_CAPACITY {
+            line == trim(&self.buf[..self.len])
+        } else {
+            line.starts_with(&self.buf)
+        }
+    }
+}
+
+/// ESP AT firmware client
+pub struct EspAt<TX, RX> {
+    tx: TX,
+    rx: RX,
+    parser: Parser,
+    echo: Echo,
+    urcs: [Option<Urc>; URC_CAPACITY],
+    urc_head: usize,
+    urc_len: usize,
+    links: [Link; MAX_LINKS],
+    /// Link receiving `+IPD` payload, `None` while dropping it
+    ipd_link: Option<u8>,
+    /// `AT+CIPMUX=1` in effect
+    mux: bool,
+    timeout: u32,
+}
+
+impl<TX, RX> EspAt<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    pub fn new(tx: TX, rx: RX) -> Self {
+        EspAt {
+            tx,
+            rx,
+            parser: Parser::new(),
+            echo: Echo::new(),
+            urcs: [None; URC_CAPACITY],
+            urc_head: 0,
+            urc_len: 0,
+            links: [Link::EMPTY; MAX_LINKS],
+            ipd_link: None,
+            mux: false,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Number of empty serial polls before a command times out
+    pub fn set_timeout(&mut self, polls: u32) {
+        self.timeout = polls;
+    }
+
+    /// Gives back the serial port
+    pub fn release(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+
+    /// Checks the module is alive and turns off command echo
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.parser.reset();
+        self.send_command("AT")?;
+        self.send_command("ATE0")
+    }
+
+    /// Sends a command, ignoring response lines
+    pub fn send_command(&mut self, cmd: &str) -> Result<(), Error> {
+        self.write_command(format_args!("{}", cmd))?;
+        self.wait_result(&mut |_| {})
+    }
+
+    /// Sends a formatted command, ignoring response lines
+    pub fn send_command_fmt(&mut self, cmd: fmt::Arguments) -> Result<(), Error> {
+        self.write_command(cmd)?;
+        self.wait_result(&mut |_| {})
+    }
+
+    /// Sends a formatted command, handing each response line to `on_line`
+    pub fn command_lines<F>(&mut self, cmd: fmt::Arguments, mut on_line: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]),
+    {
+        self.write_command(cmd)?;
+        self.wait_result(&mut on_line)
+    }
+
+    /// Sends a formatted command, collecting response lines into `buf`
+    pub fn query<'b>(
+        &mut self,
+        cmd: fmt::Arguments,
+        buf: &'b mut [u8],
+    ) -> Result<Response<'b>, Error> {
+        self.write_command(cmd)?;
+        let mut collector = Collector {
+            buf,
+            len: 0,
+            overflow: false,
+        };
+        self.wait_result(&mut |line| collector.push_line(line))?;
+        if collector.overflow {
+            return Err(Error::Overflow);
+        }
+        let Collector { buf, len, .. } = collector;
+        Ok(Response { buf: &buf[..len] })
+    }
+
+    /// Processes everything already received, without blocking
+    pub fn poll(&mut self) -> Result<(), Error> {
+        loop {
+            match self.rx.read() {
+                Ok(b) => {
+                    let _ = self.handle_byte(b, &mut |_| {});
+                }
+                Err(nb::Error::WouldBlock) => return Ok(()),
+                Err(nb::Error::Other(_)) => return Err(Error::Serial),
+            }
+        }
+    }
+
+    /// Takes the oldest unsolicited result code
+    pub fn next_urc(&mut self) -> Option<Urc> {
+        if self.urc_len == 0 {
+            return None;
+        }
+        let urc = self.urcs[self.urc_head].take();
+        self.urc_head = (self.urc_head + 1) % URC_CAPACITY;
+        self.urc_len -= 1;
+        urc
+    }
+
+    /// State of a link
+    pub fn link(&self, link: u8) -> Option<&Link> {
+        self.links.get(link as usize)
+    }
+
+    fn write_command(&mut self, cmd: fmt::Arguments) -> Result<(), Error> {
+        // drop anything left from previous commands, keeping URCs
+        self.poll()?;
+        self.echo.start();
+        let mut writer = TxWriter {
+            tx: &mut self.tx,
+            echo: &mut self.echo,
+            failed: false,
+        };
+        let _ = writer.write_fmt(cmd);
+        let _ = writer.write_str("\r\n");
+        if writer.failed {
+            return Err(Error::Serial);
+        }
+        Ok(())
+    }
+
+    /// Writes raw bytes, e.g. data after the `AT+CIPSEND` prompt
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        for &b in data {
+            block!(self.tx.write(b)).map_err(|_| Error::Serial)?;
+        }
+        block!(self.tx.flush()).map_err(|_| Error::Serial)
+    }
+
+    /// Blocks until the final result code of the pending command
+    fn wait_result(&mut self, on_line: &mut dyn FnMut(&[u8])) -> Result<(), Error> {
+        loop {
+            let b = self.read_byte()?;
+            if let Some(result) = self.handle_byte(b, on_line) {
+                return result;
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut polls = self.timeout;
+        loop {
+            match self.rx.read() {
+                Ok(b) => return Ok(b),
+                Err(nb::Error::WouldBlock) => {
+                    if polls == 0 {
+                        return Err(Error::Timeout);
+                    }
+                    polls -= 1;
+                }
+                Err(nb::Error::Other(_)) => return Err(Error::Serial),
+            }
+        }
+    }
+
+    /// Returns the command result when `b` completes a final result code
+    fn handle_byte(
+        &mut self,
+        b: u8,
+        on_line: &mut dyn FnMut(&[u8]),
+    ) -> Option<Result<(), Error>> {
+        let event = self.parser.push(b)?;
+        match event {
+            Event::Ok | Event::Error | Event::Fail => self.echo.pending = false,
+            _ => {}
+        }
+        match event {
+            Event::Ok => Some(Ok(())),
+            Event::Error => Some(Err(Error::CommandFailed)),
+            Event::Fail => Some(Err(Error::Fail)),
+            Event::Line(line) => {
+                // command echo, when ATE0 is not in effect yet
+                if self.echo.matches(line) {
+                    self.echo.pending = false;
+                } else {
+                    on_line(line);
+                }
+                None
+            }
+            Event::Urc(urc) => {
+                self.push_urc(urc);
+                None
+            }
+            Event::Data(b) => {
+                self.push_data(b);
+                None
+            }
+            Event::SendOk | Event::SendFail | Event::Prompt => None,
+        }
+    }
+
+    fn push_urc(&mut self, urc: Urc) {
+        self.update_links(urc);
+        if self.urc_len == URC_CAPACITY {
+            // full, drop the oldest
+            self.urc_head = (self.urc_head + 1) % URC_CAPACITY;
+            self.urc_len -= 1;
+        }
+        let idx = (self.urc_head + self.urc_len) % URC_CAPACITY;
+        self.urcs[idx] = Some(urc);
+        self.urc_len += 1;
+    }
+}
+
+/// `fmt::Write` adapter over a serial transmitter, keeping what is written
+/// as the echo to expect
+struct TxWriter<'a, TX> {
+    tx: &'a mut TX,
+    echo: &'a mut Echo,
+    failed: bool,
+}
+
+impl<TX: serial::Write<u8>> fmt::Write for TxWriter<'_, TX> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.echo.push(s.as_bytes());
+        for &b in s.as_bytes() {
+            if block!(self.tx.write(b)).is_err() {
+                self.failed = true;
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::sim::{Modem, Rule, SimRx, SimTx};
+    use super::*;
+
+    const GMR: &[u8] = b"AT version:2.1.0.0(883f7f2 - Jul 24 2020 11:50:07)\r\n\
+SDK version:v4.0.1-193-ge7ac221b4\r\n\
+compile time(195d0bf):Jul 28 2020 02:47:21\r\n\
+\r\nOK\r\n";
+
+    fn lines(resp: &Response) -> Vec<String> {
+        resp.lines()
+            .map(|l| String::from_utf8_lossy(l).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_lines_starting_with_at() {
+        let rules = [Rule::new("AT+GMR", GMR)];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = EspAt::new(SimTx(&modem), SimRx(&modem));
+        esp.set_timeout(10);
+
+        // the modem still echoes commands
+        let mut buf = [0u8; 256];
+        let resp = esp.query(format_args!("AT+GMR"), &mut buf).unwrap();
+        assert_eq!(
+            lines(&resp),
+            [
+                "AT version:2.1.0.0(883f7f2 - Jul 24 2020 11:50:07)",
+                "SDK version:v4.0.1-193-ge7ac221b4",
+                "compile time(195d0bf):Jul 28 2020 02:47:21"
+            ]
+        );
+
+        esp.init().unwrap();
+        let resp = esp.query(format_args!("AT+GMR"), &mut buf).unwrap();
+        assert_eq!(lines(&resp).len(), 3);
+        assert!(lines(&resp)[0].starts_with("AT version:"));
+    }
+
+    #[test]
+    fn drops_long_echoes() {
+        let long = "AT+CWJAP=\"a very long network name, with commas\",\"and a longer password\"";
+        let rules = [Rule::new("AT+CWJAP=", b"\r\n+CWJAP:1\r\n\r\nFAIL\r\n")];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = EspAt::new(SimTx(&modem), SimRx(&modem));
+        esp.set_timeout(10);
+
+        let mut seen = Vec::new();
+        let ret = esp.command_lines(format_args!("{}", long), |l| seen.push(l.to_vec()));
+        assert_eq!(ret, Err(Error::Fail));
+        assert_eq!(seen, [b"+CWJAP:1".to_vec()]);
+    }
+
+    #[test]
+    fn results_and_urcs() {
+        let rules = [
+            Rule::new("AT+BAD", b"\r\nWIFI DISCONNECT\r\nERROR\r\n"),
+            Rule::new("AT+GMR", GMR),
+        ];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = EspAt::new(SimTx(&modem), SimRx(&modem));
+        esp.set_timeout(10);
+
+        assert_eq!(esp.send_command("AT"), Ok(()));
+        assert_eq!(esp.send_command("AT+BAD"), Err(Error::CommandFailed));
+        assert_eq!(esp.next_urc(), Some(Urc::WifiDisconnect));
+        assert_eq!(esp.next_urc(), None);
+        // nothing answers
+        assert_eq!(esp.send_command("AT+UNKNOWN"), Err(Error::CommandFailed));
+
+        let mut small = [0u8; 8];
+        assert!(matches!(
+            esp.query(format_args!("AT+GMR"), &mut small),
+            Err(Error::Overflow)
+        ));
+    }
+
+    #[test]
+    fn times_out() {
+        let rules = [Rule::new("AT+SLOW", b"\r\nbusy p...\r\n")];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = EspAt::new(SimTx(&modem), SimRx(&modem));
+        esp.set_timeout(10);
+        assert_eq!(esp.send_command("AT+SLOW"), Err(Error::Timeout));
+    }
+}
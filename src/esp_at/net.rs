@@ -0,0 +1,417 @@
+//! TCP/UDP sockets over the AT link
+//!
+//! Implements `embedded-nal` `TcpClientStack` and `UdpClientStack`, so protocol
+//! crates built on those traits run on top of an ESP module:
+//!
+//! ```ignore
+//! use embedded_nal::{nb::block, TcpClientStack};
+//!
+//! esp.enable_mux()?;
+//! let mut sock = esp.socket()?;
+//! let remote = "192.168.1.198:2000".parse().unwrap();
+//! block!(esp.connect(&mut sock, remote))?;
+//! block!(TcpClientStack::send(&mut esp, &mut sock, b"hello"))?;
+//! ```
+//!
+//! Every link gets its own receive buffer, filled from `+IPD,<id>,<len>:` as
+//! the module reports data. UDP datagrams are kept apart with a 2-byte length
+//! prefix in the buffer, so `receive` returns one datagram at a time.
+
+use core::net::SocketAddr;
+
+use embedded_hal::serial;
+use embedded_nal::{TcpClientStack, TcpError, TcpErrorKind, UdpClientStack};
+
+use super::parser::Event;
+use super::{EspAt, Error, Urc};
+use crate::ring::RingBuffer;
+
+/// Links of `AT+CIPMUX=1`, single connection mode only uses link 0
+pub const MAX_LINKS: usize = 5;
+
+/// Receive buffer size of each link, TCP data past it is lost
+pub const LINK_BUFFER_CAPACITY: usize = 512;
+
+/// Max data length of one `AT+CIPSEND`
+const MAX_SEND: usize = 2048;
+
+/// Connection state of a link
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkState {
+    /// Not used by any socket
+    Free,
+    /// Owned by a socket, not connected yet
+    Allocated,
+    Connected,
+    /// Closed by the remote, buffered data can still be read
+    Closed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// One connection of the module
+pub struct Link {
+    state: LinkState,
+    protocol: Protocol,
+    remote: Option<SocketAddr>,
+    rx: RingBuffer<LINK_BUFFER_CAPACITY>,
+    /// TCP data was lost, the rest of the stream is dropped
+    overrun: bool,
+}
+
+impl Link {
+    pub(crate) const EMPTY: Link = Link {
+        state: LinkState::Free,
+        protocol: Protocol::Tcp,
+        remote: None,
+        rx: RingBuffer::new(),
+        overrun: false,
+    };
+
+    pub fn state(&self) -> LinkState {
+        self.state
+    }
+
+    pub fn remote(&self) -> Option<SocketAddr> {
+        self.remote
+    }
+
+    /// Whether received data is waiting
+    pub fn has_data(&self) -> bool {
+        !self.rx.is_empty()
+    }
+
+    /// Whether TCP data did not fit in the buffer and was lost
+    pub fn overrun(&self) -> bool {
+        self.overrun
+    }
+}
+
+/// A TCP connection over one link
+#[derive(Debug)]
+pub struct TcpSocket {
+    link: u8,
+}
+
+impl TcpSocket {
+    pub fn link(&self) -> u8 {
+        self.link
+    }
+}
+
+/// A UDP "connection" over one link
+#[derive(Debug)]
+pub struct UdpSocket {
+    link: u8,
+}
+
+impl UdpSocket {
+    pub fn link(&self) -> u8 {
+        self.link
+    }
+}
+
+impl TcpError for Error {
+    fn kind(&self) -> TcpErrorKind {
+        match self {
+            Error::Closed => TcpErrorKind::PipeClosed,
+            _ => TcpErrorKind::Other,
+        }
+    }
+}
+
+impl<TX, RX> EspAt<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    /// `AT+CIPMUX=1`, allows up to `MAX_LINKS` connections.
+    /// Must be sent while no connection is open.
+    pub fn enable_mux(&mut self) -> Result<(), Error> {
+        self.send_command("AT+CIPMUX=1")?;
+        self.mux = true;
+        Ok(())
+    }
+
+    pub(crate) fn update_links(&mut self, urc: Urc) {
+        match urc {
+            Urc::Connect(id) => {
+                if let Some(link) = self.links.get_mut(id as usize) {
+                    if link.state == LinkState::Allocated {
+                        link.state = LinkState::Connected;
+                    }
+                }
+            }
+            Urc::Closed(id) => {
+                if let Some(link) = self.links.get_mut(id as usize) {
+                    if link.state != LinkState::Free {
+                        link.state = LinkState::Closed;
+                    }
+                }
+            }
+            Urc::Ipd { link: id, len } => {
+                self.ipd_link = None;
+                if let Some(link) = self.links.get_mut(id as usize) {
+                    if link.state == LinkState::Free {
+                        return;
+                    }
+                    match link.protocol {
+                        Protocol::Tcp => self.ipd_link = Some(id),
+                        Protocol::Udp => {
+                            // keep the whole datagram or nothing
+                            let room = link.rx.capacity() - link.rx.len();
+                            if len <= 0xffff && len + 2 <= room {
+                                link.rx.push((len >> 8) as u8);
+                                link.rx.push(len as u8);
+                                self.ipd_link = Some(id);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn push_data(&mut self, b: u8) {
+        if let Some(id) = self.ipd_link {
+            let link = &mut self.links[id as usize];
+            // a stream with a gap is of no use, so keep what came before it
+            if link.overrun || !link.rx.push(b) {
+                link.overrun = true;
+            }
+        }
+    }
+
+    fn alloc_link(&mut self, protocol: Protocol) -> Result<u8, Error> {
+        let count = if self.mux { MAX_LINKS } else { 1 };
+        let id = self.links[..count]
+            .iter()
+            .position(|l| l.state == LinkState::Free)
+            .ok_or(Error::NoFreeLink)?;
+        let link = &mut self.links[id];
+        link.state = LinkState::Allocated;
+        link.protocol = protocol;
+        link.remote = None;
+        link.rx.clear();
+        link.overrun = false;
+        Ok(id as u8)
+    }
+
+    /// `AT+CIPSTART`
+    fn start_link(&mut self, id: u8, remote: SocketAddr) -> Result<(), Error> {
+        let link = &self.links[id as usize];
+        if link.state == LinkState::Connected {
+            return Ok(());
+        }
+        let kind = match (link.protocol, remote) {
+            (Protocol::Tcp, SocketAddr::V4(_)) => "TCP",
+            (Protocol::Tcp, SocketAddr::V6(_)) => "TCPv6",
+            (Protocol::Udp, SocketAddr::V4(_)) => "UDP",
+            (Protocol::Udp, SocketAddr::V6(_)) => "UDPv6",
+        };
+        let ip = remote.ip();
+        let port = remote.port();
+        let ret = if self.mux {
+            self.send_command_fmt(format_args!(
+                "AT+CIPSTART={},\"{}\",\"{}\",{}",
+                id, kind, ip, port
+            ))
+        } else {
+            self.send_command_fmt(format_args!("AT+CIPSTART=\"{}\",\"{}\",{}", kind, ip, port))
+        };
+        let link = &mut self.links[id as usize];
+        match ret {
+            Ok(()) => {
+                link.state = LinkState::Connected;
+                link.remote = Some(remote);
+                Ok(())
+            }
+            Err(e) => {
+                link.state = LinkState::Allocated;
+                Err(e)
+            }
+        }
+    }
+
+    /// `AT+CIPSEND`, `data` must not exceed `MAX_SEND`
+    fn send_link(&mut self, id: u8, data: &[u8]) -> Result<(), Error> {
+        if self.links[id as usize].state != LinkState::Connected {
+            return Err(Error::Closed);
+        }
+        if self.mux {
+            self.write_command(format_args!("AT+CIPSEND={},{}", id, data.len()))?;
+        } else {
+            self.write_command(format_args!("AT+CIPSEND={}", data.len()))?;
+        }
+        self.wait_result(&mut |_| {})?;
+        self.wait_prompt()?;
+        self.write_bytes(data)?;
+        self.wait_send_result()
+    }
+
+    /// `AT+CIPCLOSE`, and frees the link
+    fn close_link(&mut self, id: u8) -> Result<(), Error> {
+        let ret = if self.links[id as usize].state == LinkState::Connected {
+            if self.mux {
+                self.send_command_fmt(format_args!("AT+CIPCLOSE={}", id))
+            } else {
+                self.send_command("AT+CIPCLOSE")
+            }
+        } else {
+            Ok(())
+        };
+        let link = &mut self.links[id as usize];
+        link.state = LinkState::Free;
+        link.rx.clear();
+        if self.ipd_link == Some(id) {
+            self.ipd_link = None;
+        }
+        match ret {
+            // closed by the remote in the meantime
+            Err(Error::CommandFailed) => Ok(()),
+            ret => ret,
+        }
+    }
+
+    /// Blocks until `>` of `AT+CIPSEND`, or a failure
+    fn wait_prompt(&mut self) -> Result<(), Error> {
+        loop {
+            let b = self.read_byte()?;
+            match self.parser.push(b) {
+                Some(Event::Prompt) => return Ok(()),
+                Some(Event::Error) => return Err(Error::CommandFailed),
+                Some(Event::Fail) => return Err(Error::Fail),
+                Some(Event::Urc(urc)) => self.push_urc(urc),
+                Some(Event::Data(b)) => self.push_data(b),
+                _ => {}
+            }
+        }
+    }
+
+    /// Blocks until `SEND OK` or `SEND FAIL`
+    fn wait_send_result(&mut self) -> Result<(), Error> {
+        loop {
+            let b = self.read_byte()?;
+            match self.parser.push(b) {
+                Some(Event::SendOk) => return Ok(()),
+                Some(Event::SendFail) => return Err(Error::SendFailed),
+                Some(Event::Error) => return Err(Error::CommandFailed),
+                Some(Event::Urc(urc)) => self.push_urc(urc),
+                Some(Event::Data(b)) => self.push_data(b),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<TX, RX> TcpClientStack for EspAt<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    type TcpSocket = TcpSocket;
+    type Error = Error;
+
+    fn socket(&mut self) -> Result<TcpSocket, Error> {
+        let link = self.alloc_link(Protocol::Tcp)?;
+        Ok(TcpSocket { link })
+    }
+
+    fn connect(&mut self, socket: &mut TcpSocket, remote: SocketAddr) -> nb::Result<(), Error> {
+        self.start_link(socket.link, remote)?;
+        Ok(())
+    }
+
+    fn send(&mut self, socket: &mut TcpSocket, buffer: &[u8]) -> nb::Result<usize, Error> {
+        let len = usize::min(buffer.len(), MAX_SEND);
+        self.send_link(socket.link, &buffer[..len])?;
+        Ok(len)
+    }
+
+    fn receive(&mut self, socket: &mut TcpSocket, buffer: &mut [u8]) -> nb::Result<usize, Error> {
+        self.poll()?;
+        let link = &mut self.links[socket.link as usize];
+        if !link.rx.is_empty() {
+            return Ok(link.rx.read(buffer));
+        }
+        if link.overrun {
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        match link.state {
+            LinkState::Connected => Err(nb::Error::WouldBlock),
+            _ => Err(nb::Error::Other(Error::Closed)),
+        }
+    }
+
+    fn close(&mut self, socket: TcpSocket) -> Result<(), Error> {
+        self.close_link(socket.link)
+    }
+}
+
+impl<TX, RX> UdpClientStack for EspAt<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    type UdpSocket = UdpSocket;
+    type Error = Error;
+
+    fn socket(&mut self) -> Result<UdpSocket, Error> {
+        let link = self.alloc_link(Protocol::Udp)?;
+        Ok(UdpSocket { link })
+    }
+
+    fn connect(&mut self, socket: &mut UdpSocket, remote: SocketAddr) -> Result<(), Error> {
+        self.start_link(socket.link, remote)
+    }
+
+    fn send(&mut self, socket: &mut UdpSocket, buffer: &[u8]) -> nb::Result<(), Error> {
+        if buffer.len() > MAX_SEND {
+            return Err(nb::Error::Other(Error::TooLong));
+        }
+        self.send_link(socket.link, buffer)?;
+        Ok(())
+    }
+
+    fn receive(
+        &mut self,
+        socket: &mut UdpSocket,
+        buffer: &mut [u8],
+    ) -> nb::Result<(usize, SocketAddr), Error> {
+        self.poll()?;
+        // a datagram may still be arriving
+        if self.ipd_link == Some(socket.link) && self.parser.in_payload() {
+            return Err(nb::Error::WouldBlock);
+        }
+        let link = &mut self.links[socket.link as usize];
+        let remote = link.remote.ok_or(Error::Closed)?;
+        if link.rx.len() < 2 {
+            return match link.state {
+                LinkState::Connected => Err(nb::Error::WouldBlock),
+                _ => Err(nb::Error::Other(Error::Closed)),
+            };
+        }
+        let hi = link.rx.pop().unwrap_or(0) as usize;
+        let lo = link.rx.pop().unwrap_or(0) as usize;
+        let len = (hi << 8) | lo;
+        // the part not fitting in `buffer` is dropped, as with any UDP socket
+        let mut n = 0;
+        for i in 0..len {
+            let b = link.rx.pop().unwrap_or(0);
+            if i < buffer.len() {
+                buffer[i] = b;
+                n += 1;
+            }
+        }
+        Ok((n, remote))
+    }
+
+    fn close(&mut self, socket: UdpSocket) -> Result<(), Error> {
+        self.close_link(socket.link)
+    }
+}
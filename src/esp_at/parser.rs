@@ -0,0 +1,405 @@
+//! Byte-level parser for the ESP AT response stream
+//!
+//! Pure logic, no I/O: feed every received byte into `Parser::push`, and act on
+//! the returned `Event`s.
+
+/// Longest response line kept, longer lines are truncated
+pub const LINE_CAPACITY: usize = 256;
+
+/// Unsolicited result codes, sent by the module without a command
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Urc {
+    /// `ready`, module (re)booted
+    Ready,
+    /// `WIFI CONNECTED`
+    WifiConnected,
+    /// `WIFI GOT IP`
+    WifiGotIp,
+    /// `WIFI DISCONNECT`
+    WifiDisconnect,
+    /// `<link>,CONNECT`, or `CONNECT` in single connection mode
+    Connect(u8),
+    /// `<link>,CLOSED`, or `CLOSED` in single connection mode
+    Closed(u8),
+    /// `+IPD,<link>,<len>:`, followed by `len` bytes of `Event::Data`
+    Ipd { link: u8, len: usize },
+}
+
+/// What a received byte completed
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    /// Final result `OK`
+    Ok,
+    /// Final result `ERROR`
+    Error,
+    /// Final result `FAIL`
+    Fail,
+    /// `SEND OK`, data of `AT+CIPSEND` accepted
+    SendOk,
+    /// `SEND FAIL`
+    SendFail,
+    /// `>`, module is waiting for `AT+CIPSEND` data
+    Prompt,
+    /// Any other response line, trimmed
+    Line(&'a [u8]),
+    /// Unsolicited result code
+    Urc(Urc),
+    /// One payload byte following `Urc::Ipd`
+    Data(u8),
+}
+
+/// Splits the response stream into lines and payload bytes
+pub struct Parser {
+    buf: [u8; LINE_CAPACITY],
+    len: usize,
+    payload: usize,
+    truncated: bool,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            buf: [0; LINE_CAPACITY],
+            len: 0,
+            payload: 0,
+            truncated: false,
+        }
+    }
+
+    /// Drops any partial line or payload
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.payload = 0;
+        self.truncated = false;
+    }
+
+    /// Whether the last `Event::Line` was longer than `LINE_CAPACITY`
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Whether `+IPD` payload bytes are still expected
+    pub fn in_payload(&self) -> bool {
+        self.payload > 0
+    }
+
+    /// Feeds one received byte
+    pub fn push(&mut self, b: u8) -> Option<Event<'_>> {
+        if self.payload > 0 {
+            self.payload -= 1;
+            return Some(Event::Data(b));
+        }
+
+        if b == b'\n' {
+            let len = self.len;
+            self.len = 0;
+            let line = trim(&self.buf[..len]);
+            if line.is_empty() {
+                return None;
+            }
+            return Some(classify(line));
+        }
+
+        if self.len == 0 {
+            self.truncated = false;
+        }
+        if self.len < LINE_CAPACITY {
+            self.buf[self.len] = b;
+            self.len += 1;
+        } else {
+            self.truncated = true;
+        }
+
+        // prompt of AT+CIPSEND comes without line ending
+        if b == b'>' && self.len == 1 {
+            self.len = 0;
+            return Some(Event::Prompt);
+        }
+
+        // payload of +IPD is raw bytes, not a line
+        if b == b':' && self.buf[..self.len].starts_with(b"+IPD,") {
+            let header = &self.buf[5..self.len - 1];
+            self.len = 0;
+            if let Some((link, len)) = parse_ipd(header) {
+                self.payload = len;
+                return Some(Event::Urc(Urc::Ipd { link, len }));
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `<link>,<len>` in multiple connection mode, `<len>` otherwise.
+/// Trailing `,<remote ip>,<remote port>` (AT+CIPDINFO=1) is ignored.
+fn parse_ipd(header: &[u8]) -> Option<(u8, usize)> {
+    let mut fields = header.split(|&c| c == b',');
+    let first = parse_usize(fields.next()?)?;
+    match fields.next().and_then(parse_usize) {
+        Some(len) => Some((first as u8, len)),
+        None => Some((0, first)),
+    }
+}
+
+fn classify(line: &[u8]) -> Event<'_> {
+    match line {
+        b"OK" => return Event::Ok,
+        b"ERROR" => return Event::Error,
+        b"FAIL" => return Event::Fail,
+        b"SEND OK" => return Event::SendOk,
+        b"SEND FAIL" => return Event::SendFail,
+        b"ready" => return Event::Urc(Urc::Ready),
+        b"WIFI CONNECTED" => return Event::Urc(Urc::WifiConnected),
+        b"WIFI GOT IP" => return Event::Urc(Urc::WifiGotIp),
+        b"WIFI DISCONNECT" => return Event::Urc(Urc::WifiDisconnect),
+        b"CONNECT" => return Event::Urc(Urc::Connect(0)),
+        b"CLOSED" => return Event::Urc(Urc::Closed(0)),
+        _ => {}
+    }
+
+    // <link>,CONNECT / <link>,CLOSED
+    if let Some(pos) = line.iter().position(|&c| c == b',') {
+        if let Some(link) = parse_usize(&line[..pos]) {
+            match &line[pos + 1..] {
+                b"CONNECT" => return Event::Urc(Urc::Connect(link as u8)),
+                b"CLOSED" => return Event::Urc(Urc::Closed(link as u8)),
+                _ => {}
+            }
+        }
+    }
+
+    Event::Line(line)
+}
+
+/// Splits the parameters of a response line like `(3,"a,b",-39,"90:12:..",6)`
+///
+/// Quoted fields may contain commas, and are returned without the quotes.
+/// Surrounding parentheses are dropped.
+pub struct Fields<'a> {
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(mut s: &'a [u8]) -> Self {
+        if let [b'(', inner @ .., b')'] = s {
+            s = inner;
+        }
+        Fields { rest: Some(s) }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let s = self.rest?;
+        if let [b'"', quoted @ ..] = s {
+            // closing quote is the one followed by a separator or the end
+            let mut i = 0;
+            while i < quoted.len() {
+                if quoted[i] == b'"' && (i + 1 == quoted.len() || quoted[i + 1] == b',') {
+                    self.rest = quoted.get(i + 2..).filter(|_| i + 1 < quoted.len());
+                    return Some(&quoted[..i]);
+                }
+                i += 1;
+            }
+            self.rest = None;
+            return Some(quoted);
+        }
+        match s.iter().position(|&c| c == b',') {
+            Some(pos) => {
+                self.rest = Some(&s[pos + 1..]);
+                Some(&s[..pos])
+            }
+            None => {
+                self.rest = None;
+                Some(s)
+            }
+        }
+    }
+}
+
+/// Parses a signed decimal number
+pub fn parse_isize(s: &[u8]) -> Option<isize> {
+    match s {
+        [b'-', digits @ ..] => parse_usize(digits).map(|n| -(n as isize)),
+        _ => parse_usize(s).map(|n| n as isize),
+    }
+}
+
+/// Strips leading and trailing whitespace, including `\r`
+pub fn trim(mut s: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = s {
+        if first.is_ascii_whitespace() {
+            s = rest;
+        } else {
+            break;
+        }
+    }
+    while let [rest @ .., last] = s {
+        if last.is_ascii_whitespace() {
+            s = rest;
+        } else {
+            break;
+        }
+    }
+    s
+}
+
+/// Parses an unsigned decimal number
+pub fn parse_usize(s: &[u8]) -> Option<usize> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut n: usize = 0;
+    for &c in s {
+        if !c.is_ascii_digit() {
+            return None;
+        }
+        n = n.checked_mul(10)?.checked_add((c - b'0') as usize)?;
+    }
+    Some(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Events of a byte stream, lines copied out
+    fn events(input: &[u8]) -> Vec<String> {
+        let mut parser = Parser::new();
+        let mut out = Vec::new();
+        for &b in input {
+            match parser.push(b) {
+                Some(Event::Line(line)) => {
+                    out.push(format!("Line({})", String::from_utf8_lossy(line)))
+                }
+                Some(event) => out.push(format!("{:?}", event)),
+                None => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn final_results() {
+        assert_eq!(events(b"\r\nOK\r\n"), ["Ok"]);
+        assert_eq!(events(b"\r\nERROR\r\n"), ["Error"]);
+        assert_eq!(
+            events(b"\r\n+CWJAP:2\r\n\r\nFAIL\r\n"),
+            ["Line(+CWJAP:2)", "Fail"]
+        );
+        assert_eq!(
+            events(b"\r\nRecv 5 bytes\r\n\r\nSEND OK\r\n\r\nSEND FAIL\r\n"),
+            ["Line(Recv 5 bytes)", "SendOk", "SendFail"]
+        );
+        // only whole lines are final results
+        assert_eq!(
+            events(b"OKAY\r\nERRORS\r\n"),
+            ["Line(OKAY)", "Line(ERRORS)"]
+        );
+    }
+
+    #[test]
+    fn urcs() {
+        assert_eq!(
+            events(b"ready\r\nWIFI CONNECTED\r\nWIFI GOT IP\r\nWIFI DISCONNECT\r\n"),
+            [
+                "Urc(Ready)",
+                "Urc(WifiConnected)",
+                "Urc(WifiGotIp)",
+                "Urc(WifiDisconnect)"
+            ]
+        );
+        assert_eq!(
+            events(b"CONNECT\r\n3,CONNECT\r\n3,CLOSED\r\nCLOSED\r\n"),
+            [
+                "Urc(Connect(0))",
+                "Urc(Connect(3))",
+                "Urc(Closed(3))",
+                "Urc(Closed(0))"
+            ]
+        );
+    }
+
+    #[test]
+    fn cwlap() {
+        let input = b"+CWLAP:(3,\"a,\\\"b\",-39,\"90:12:34:d4:e4:aa\",6)\r\n";
+        let events = events(input);
+        assert_eq!(
+            events,
+            ["Line(+CWLAP:(3,\"a,\\\"b\",-39,\"90:12:34:d4:e4:aa\",6))"]
+        );
+
+        let line = &input[7..input.len() - 2];
+        let fields: Vec<&[u8]> = Fields::new(line).collect();
+        assert_eq!(
+            fields,
+            [&b"3"[..], b"a,\\\"b", b"-39", b"90:12:34:d4:e4:aa", b"6"]
+        );
+        // an empty SSID
+        let fields: Vec<&[u8]> = Fields::new(b"(3,\"\",-40)").collect();
+        assert_eq!(fields, [&b"3"[..], b"", b"-40"]);
+    }
+
+    #[test]
+    fn ipd_payload() {
+        // the payload may contain line endings and result codes
+        let input = b"\r\n+IPD,1,9:OK\r\nab\r\n>\r\n+IPD,3:xyz";
+        let mut parser = Parser::new();
+        let mut urcs = Vec::new();
+        let mut data = Vec::new();
+        for &b in input.iter() {
+            match parser.push(b) {
+                Some(Event::Urc(urc)) => urcs.push(urc),
+                Some(Event::Data(b)) => data.push(b),
+                Some(event) => panic!("unexpected {:?}", event),
+                None => {}
+            }
+        }
+        assert_eq!(
+            urcs,
+            [Urc::Ipd { link: 1, len: 9 }, Urc::Ipd { link: 0, len: 3 }]
+        );
+        assert_eq!(data, b"OK\r\nab\r\n>xyz");
+        assert!(!parser.in_payload());
+
+        // remote address of AT+CIPDINFO=1
+        assert_eq!(parse_ipd(b"2,5,192.168.1.2,8080"), Some((2, 5)));
+    }
+
+    #[test]
+    fn prompt_and_long_lines() {
+        assert_eq!(events(b"\r\nOK\r\n> "), ["Ok", "Prompt"]);
+        // a > inside a line is not a prompt
+        assert_eq!(events(b"a>b\r\n"), ["Line(a>b)"]);
+
+        let mut parser = Parser::new();
+        let mut long = vec![b'x'; LINE_CAPACITY + 10];
+        long.extend_from_slice(b"\r\n");
+        let mut len = 0;
+        for &b in &long {
+            if let Some(Event::Line(line)) = parser.push(b) {
+                len = line.len();
+            }
+        }
+        assert_eq!(len, LINE_CAPACITY);
+        assert!(parser.truncated());
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_usize(b"2048"), Some(2048));
+        assert_eq!(parse_usize(b""), None);
+        assert_eq!(parse_usize(b"1a"), None);
+        assert_eq!(parse_usize(b"99999999999999999999999"), None);
+        assert_eq!(parse_isize(b"-39"), Some(-39));
+        assert_eq!(trim(b" \r\nab c\r\n"), b"ab c");
+    }
+}
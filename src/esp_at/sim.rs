@@ -0,0 +1,649 @@
+//! Scriptable fake ESP AT modem, for running `esp_at` without hardware
+//!
+//! `SimTx`/`SimRx` implement the same `embedded_hal::serial` traits as the
+//! HAL's `Tx`/`Rx`, so an `EspAt` client can be driven on a Linux host:
+//!
+//! ```ignore
+//! use core::cell::RefCell;
+//! use longan_nano_playground::esp_at::sim::{self, Modem, Rule, SimRx, SimTx};
+//!
+//! let rules = [Rule::new("AT+CWLAP", sim::CWLAP)];
+//! let modem = RefCell::new(Modem::new(&rules));
+//! let mut esp = EspAt::new(SimTx(&modem), SimRx(&modem));
+//! esp.set_timeout(10);
+//!
+//! let mut aps = [AccessPoint::default(); 8];
+//! assert_eq!(esp.scan(&mut aps), Ok(4));
+//!
+//! modem.borrow_mut().inject_ipd(0, b"hello");
+//! ```
+//!
+//! Commands are matched against the script first. Without a matching rule,
+//! `AT`, `ATE0`, `AT+CIPMUX`, `AT+CIPSTART`, `AT+CIPSEND` and `AT+CIPCLOSE`
+//! get the answers of a healthy module, anything else gets `ERROR`.
+//! Data sent with `AT+CIPSEND` is recorded and can be taken with
+//! `Modem::take_sent`.
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::{self, Write};
+
+use embedded_hal::serial;
+
+use super::parser::{parse_usize, trim, Fields};
+use crate::ring::RingBuffer;
+
+/// Bytes waiting to be read by the client
+const OUTPUT_CAPACITY: usize = 4096;
+/// Longest command accepted
+const COMMAND_CAPACITY: usize = 256;
+/// Recorded `AT+CIPSEND` payload
+const SENT_CAPACITY: usize = 2048;
+
+// Transcripts captured with scripts/esp8266.py
+
+/// `AT+CWLAP`
+pub const CWLAP: &[u8] = b"\r\n\
++CWLAP:(3,\"cjyy\",-39,\"90:12:34:d4:e4:aa\",6)\r\n\
++CWLAP:(3,\"\",-40,\"90:12:34:d4:e4:ab\",6)\r\n\
++CWLAP:(3,\"Galaxy Note10+ 5G574e\",-58,\"4a:eb:62:15:3a:e2\",11)\r\n\
++CWLAP:(3,\"feather\",-65,\"04:d9:f5:c4:93:98\",11)\r\n\
+\r\nOK\r\n";
+
+/// `AT+CWJAP="feather","-------"`, with the ESP32 debug log
+pub const CWJAP_OK: &[u8] = b"\r\n\
+I (2053728) wifi: state: 0 -> 2 (b0)\r\n\
+I (2053733) wifi: state: 2 -> 3 (0)\r\n\
+I (2053740) wifi: state: 3 -> 5 (10)\r\n\
+WIFI CONNECTED\r\n\
+\r\n\
+WIFI GOT IP\r\n\
+\r\nOK\r\n";
+
+/// `AT+CWJAP` with a wrong password
+pub const CWJAP_WRONG_PASSWORD: &[u8] = b"\r\n+CWJAP:2\r\n\r\nFAIL\r\n";
+
+/// `AT+CIPSTA?`
+pub const CIPSTA: &[u8] = b"\r\n\
++CIPSTA:ip:\"192.168.1.9\"\r\n\
++CIPSTA:gateway:\"192.168.1.1\"\r\n\
++CIPSTA:netmask:\"255.255.0.0\"\r\n\
+\r\nOK\r\n";
+
+/// `AT+CWHOSTNAME?`
+pub const CWHOSTNAME: &[u8] = b"\r\n+CWHOSTNAME:LWIP\r\n\r\nOK\r\n";
+
+/// `AT+CIPSTART` to an unreachable host
+pub const CIPSTART_FAIL: &[u8] = b"\r\nERROR\r\nCLOSED\r\n";
+
+/// `OK`, for commands without a response
+pub const OK: &[u8] = b"\r\nOK\r\n";
+
+/// A canned response
+pub struct Rule<'a> {
+    /// Commands starting with this are answered with `response`
+    pub command: &'a str,
+    pub response: &'a [u8],
+}
+
+impl<'a> Rule<'a> {
+    pub const fn new(command: &'a str, response: &'a [u8]) -> Self {
+        Rule { command, response }
+    }
+}
+
+/// State of the fake modem, shared by `SimTx` and `SimRx`
+pub struct Modem<'a> {
+    rules: &'a [Rule<'a>],
+    output: RingBuffer<OUTPUT_CAPACITY>,
+    /// Command being received
+    line: [u8; COMMAND_CAPACITY],
+    line_len: usize,
+    /// Last complete command
+    last: [u8; COMMAND_CAPACITY],
+    last_len: usize,
+    /// Payload bytes of `AT+CIPSEND` still to come
+    sending: usize,
+    sending_len: usize,
+    sent: RingBuffer<SENT_CAPACITY>,
+    mux: bool,
+    echo: bool,
+    commands: usize,
+}
+
+impl<'a> Modem<'a> {
+    pub fn new(rules: &'a [Rule<'a>]) -> Self {
+        Modem {
+            rules,
+            output: RingBuffer::new(),
+            line: [0; COMMAND_CAPACITY],
+            line_len: 0,
+            last: [0; COMMAND_CAPACITY],
+            last_len: 0,
+            sending: 0,
+            sending_len: 0,
+            sent: RingBuffer::new(),
+            mux: false,
+            echo: true,
+            commands: 0,
+        }
+    }
+
+    /// Replaces the script
+    pub fn set_rules(&mut self, rules: &'a [Rule<'a>]) {
+        self.rules = rules;
+    }
+
+    /// Number of commands received so far
+    pub fn command_count(&self) -> usize {
+        self.commands
+    }
+
+    /// The last command received, without line ending
+    pub fn last_command(&self) -> &[u8] {
+        &self.last[..self.last_len]
+    }
+
+    /// Whether `AT+CIPMUX=1` was received
+    pub fn mux(&self) -> bool {
+        self.mux
+    }
+
+    /// Takes recorded `AT+CIPSEND` payload
+    pub fn take_sent(&mut self, buf: &mut [u8]) -> usize {
+        self.sent.read(buf)
+    }
+
+    /// Bytes not yet read by the client
+    pub fn pending_output(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Queues raw bytes for the client
+    pub fn inject(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.output.push(b);
+        }
+    }
+
+    /// Queues `+IPD` with `data` on `link`
+    pub fn inject_ipd(&mut self, link: u8, data: &[u8]) {
+        if self.mux {
+            let _ = write!(self, "\r\n+IPD,{},{}:", link, data.len());
+        } else {
+            let _ = write!(self, "\r\n+IPD,{}:", data.len());
+        }
+        self.inject(data);
+    }
+
+    /// Queues `<link>,CLOSED`, as if the remote closed the connection
+    pub fn disconnect(&mut self, link: u8) {
+        if self.mux {
+            let _ = write!(self, "{},CLOSED\r\n", link);
+        } else {
+            self.inject(b"CLOSED\r\n");
+        }
+    }
+
+    /// Queues `WIFI DISCONNECT`
+    pub fn wifi_disconnect(&mut self) {
+        self.inject(b"WIFI DISCONNECT\r\n");
+    }
+
+    /// Feeds one byte written by the client
+    fn receive(&mut self, b: u8) {
+        if self.sending > 0 {
+            self.sent.push_overwrite(b);
+            self.sending -= 1;
+            if self.sending == 0 {
+                let len = self.sending_len;
+                let _ = write!(self, "\r\nRecv {} bytes\r\n\r\nSEND OK\r\n", len);
+            }
+            return;
+        }
+
+        if b != b'\n' {
+            if self.line_len < COMMAND_CAPACITY {
+                self.line[self.line_len] = b;
+                self.line_len += 1;
+            }
+            return;
+        }
+
+        let line = trim(&self.line[..self.line_len]);
+        self.last_len = line.len();
+        self.last[..line.len()].copy_from_slice(line);
+        self.line_len = 0;
+        if self.last_len > 0 {
+            self.commands += 1;
+            self.execute();
+        }
+    }
+
+    fn execute(&mut self) {
+        let mut command = [0u8; COMMAND_CAPACITY];
+        let len = self.last_len;
+        command[..len].copy_from_slice(&self.last[..len]);
+        let command = &command[..len];
+
+        if self.echo {
+            self.inject(command);
+            self.inject(b"\r\n");
+        }
+
+        let rules = self.rules;
+        if let Some(rule) = rules
+            .iter()
+            .find(|r| command.starts_with(r.command.as_bytes()))
+        {
+            self.inject(rule.response);
+            if command.starts_with(b"AT+CIPSEND=") {
+                self.begin_send(&command[11..]);
+            }
+            return;
+        }
+
+        match command {
+            b"AT" => self.inject(OK),
+            b"ATE0" => {
+                self.echo = false;
+                self.inject(OK);
+            }
+            b"ATE1" => {
+                self.echo = true;
+                self.inject(OK);
+            }
+            b"AT+CIPMUX=0" | b"AT+CIPMUX=1" => {
+                self.mux = command[10] == b'1';
+                self.inject(OK);
+            }
+            _ if command.starts_with(b"AT+CIPSTART=") => {
+                let link = self.link_of(&command[12..]);
+                if self.mux {
+                    let _ = write!(self, "{},CONNECT\r\n", link);
+                } else {
+                    self.inject(b"CONNECT\r\n");
+                }
+                self.inject(OK);
+            }
+            _ if command.starts_with(b"AT+CIPSEND=") => {
+                if self.begin_send(&command[11..]) {
+                    self.inject(b"\r\nOK\r\n> ");
+                } else {
+                    self.inject(b"\r\nERROR\r\n");
+                }
+            }
+            _ if command.starts_with(b"AT+CIPCLOSE") => {
+                let link = command.get(12..).map(|p| self.link_of(p)).unwrap_or(0);
+                self.disconnect(link);
+                self.inject(OK);
+            }
+            _ => self.inject(b"\r\nERROR\r\n"),
+        }
+    }
+
+    /// `<link>,` of a multiple connection mode command
+    fn link_of(&self, params: &[u8]) -> u8 {
+        if self.mux {
+            Fields::new(params)
+                .next()
+                .and_then(parse_usize)
+                .unwrap_or(0) as u8
+        } else {
+            0
+        }
+    }
+
+    /// Parses `[<link>,]<len>` of `AT+CIPSEND`, and starts taking payload
+    fn begin_send(&mut self, params: &[u8]) -> bool {
+        let mut fields = Fields::new(params);
+        if self.mux {
+            fields.next();
+        }
+        match fields.next().and_then(parse_usize) {
+            Some(len) if len > 0 => {
+                self.sending = len;
+                self.sending_len = len;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Write for Modem<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inject(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Transmit half, what the client writes goes to the modem
+pub struct SimTx<'m, 'a>(pub &'m RefCell<Modem<'a>>);
+
+/// Receive half, reads what the modem answers
+pub struct SimRx<'m, 'a>(pub &'m RefCell<Modem<'a>>);
+
+impl serial::Write<u8> for SimTx<'_, '_> {
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+        self.0.borrow_mut().receive(word);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+impl serial::Read<u8> for SimRx<'_, '_> {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        self.0
+            .borrow_mut()
+            .output
+            .pop()
+            .ok_or(nb::Error::WouldBlock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::net::{Ipv4Addr, SocketAddr};
+
+    use embedded_nal::{TcpClientStack, UdpClientStack};
+
+    use super::*;
+    use crate::esp_at::{
+        AccessPoint, Encryption, EspAt, Error, LinkState, Urc, LINK_BUFFER_CAPACITY,
+    };
+
+    type Client<'m, 'a> = EspAt<SimTx<'m, 'a>, SimRx<'m, 'a>>;
+
+    fn client<'m, 'a>(modem: &'m RefCell<Modem<'a>>) -> Client<'m, 'a> {
+        let mut esp = EspAt::new(SimTx(modem), SimRx(modem));
+        esp.set_timeout(10);
+        esp.init().unwrap();
+        esp
+    }
+
+    fn remote() -> SocketAddr {
+        "192.168.1.198:2000".parse().unwrap()
+    }
+
+    #[test]
+    fn scan() {
+        let rules = [Rule::new("AT+CWLAP", CWLAP)];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = client(&modem);
+
+        let mut aps = [AccessPoint::default(); 8];
+        assert_eq!(esp.scan(&mut aps), Ok(4));
+        assert_eq!(aps[0].ssid.as_str(), "cjyy");
+        assert_eq!(aps[0].ecn, Encryption::Wpa2Psk);
+        assert_eq!(aps[0].rssi, -39);
+        assert_eq!(aps[0].mac, [0x90, 0x12, 0x34, 0xd4, 0xe4, 0xaa]);
+        assert_eq!(aps[0].channel, 6);
+        assert_eq!(aps[1].ssid.as_str(), "");
+        assert_eq!(aps[2].ssid.as_str(), "Galaxy Note10+ 5G574e");
+        assert_eq!((aps[3].rssi, aps[3].channel), (-65, 11));
+
+        // records that do not fit are dropped
+        let mut two = [AccessPoint::default(); 2];
+        assert_eq!(esp.scan(&mut two), Ok(2));
+        assert_eq!(modem.borrow().last_command(), b"AT+CWLAP");
+    }
+
+    #[test]
+    fn join() {
+        let rules = [Rule::new("AT+CWJAP=", CWJAP_WRONG_PASSWORD)];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = client(&modem);
+        assert_eq!(esp.join("feather", "wrong"), Err(Error::JoinFailed(2)));
+        assert_eq!(
+            modem.borrow().last_command(),
+            &b"AT+CWJAP=\"feather\",\"wrong\""[..]
+        );
+
+        // special characters are escaped
+        let rules = [Rule::new("AT+CWJAP=", CWJAP_OK)];
+        modem.borrow_mut().set_rules(&rules);
+        assert_eq!(esp.join("a,b", "p\"w"), Ok(()));
+        assert_eq!(
+            modem.borrow().last_command(),
+            &b"AT+CWJAP=\"a\\,b\",\"p\\\"w\""[..]
+        );
+        assert_eq!(esp.next_urc(), Some(Urc::WifiConnected));
+        assert_eq!(esp.next_urc(), Some(Urc::WifiGotIp));
+        assert_eq!(esp.next_urc(), None);
+    }
+
+    #[test]
+    fn ip_config() {
+        let rules = [
+            Rule::new("AT+CIPSTA?", CIPSTA),
+            Rule::new("AT+CWHOSTNAME?", CWHOSTNAME),
+        ];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = client(&modem);
+
+        let config = esp.ip_config().unwrap();
+        assert_eq!(config.ip, Ipv4Addr::new(192, 168, 1, 9));
+        assert_eq!(config.gateway, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(config.netmask, Ipv4Addr::new(255, 255, 0, 0));
+
+        let mut buf = [0u8; 16];
+        assert_eq!(esp.hostname(&mut buf), Ok("LWIP"));
+        let mut small = [0u8; 2];
+        assert_eq!(esp.hostname(&mut small), Err(Error::Overflow));
+    }
+
+    #[test]
+    fn tcp() {
+        let modem = RefCell::new(Modem::new(&[]));
+        let mut esp = client(&modem);
+        esp.enable_mux().unwrap();
+        assert!(modem.borrow().mux());
+
+        let mut first = TcpClientStack::socket(&mut esp).unwrap();
+        let mut sock = TcpClientStack::socket(&mut esp).unwrap();
+        assert_eq!((first.link(), sock.link()), (0, 1));
+        TcpClientStack::connect(&mut esp, &mut sock, remote()).unwrap();
+        assert_eq!(
+            modem.borrow().last_command(),
+            &b"AT+CIPSTART=1,\"TCP\",\"192.168.1.198\",2000"[..]
+        );
+        assert_eq!(esp.link(1).unwrap().state(), LinkState::Connected);
+        assert_eq!(esp.link(1).unwrap().remote(), Some(remote()));
+
+        assert_eq!(TcpClientStack::send(&mut esp, &mut sock, b"hello"), Ok(5));
+        let mut sent = [0u8; 16];
+        let n = modem.borrow_mut().take_sent(&mut sent);
+        assert_eq!(&sent[..n], b"hello");
+
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Err(nb::Error::WouldBlock)
+        );
+        modem.borrow_mut().inject_ipd(1, b"wor");
+        modem.borrow_mut().inject_ipd(1, b"ld\r\nOK\r\n");
+        // other links keep their data apart
+        modem.borrow_mut().inject_ipd(0, b"x");
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok(11)
+        );
+        assert_eq!(&buf[..11], b"world\r\nOK\r\n");
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut first, &mut buf),
+            Ok(1)
+        );
+        assert_eq!(buf[0], b'x');
+
+        TcpClientStack::close(&mut esp, sock).unwrap();
+        assert_eq!(modem.borrow().last_command(), b"AT+CIPCLOSE=1");
+        assert_eq!(esp.link(1).unwrap().state(), LinkState::Free);
+    }
+
+    #[test]
+    fn tcp_closed_by_remote() {
+        let rules = [Rule::new("AT+CIPSTART=0", CIPSTART_FAIL)];
+        let modem = RefCell::new(Modem::new(&rules));
+        let mut esp = client(&modem);
+        esp.enable_mux().unwrap();
+
+        // unreachable host
+        let mut sock = TcpClientStack::socket(&mut esp).unwrap();
+        assert_eq!(
+            TcpClientStack::connect(&mut esp, &mut sock, remote()),
+            Err(nb::Error::Other(Error::CommandFailed))
+        );
+        assert_eq!(esp.link(0).unwrap().state(), LinkState::Allocated);
+        TcpClientStack::close(&mut esp, sock).unwrap();
+
+        modem.borrow_mut().set_rules(&[]);
+        let mut sock = TcpClientStack::socket(&mut esp).unwrap();
+        TcpClientStack::connect(&mut esp, &mut sock, remote()).unwrap();
+        modem.borrow_mut().inject_ipd(0, b"bye");
+        modem.borrow_mut().disconnect(0);
+
+        // buffered data first, then the error
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok(3)
+        );
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Err(nb::Error::Other(Error::Closed))
+        );
+        assert_eq!(
+            TcpClientStack::send(&mut esp, &mut sock, b"x"),
+            Err(nb::Error::Other(Error::Closed))
+        );
+        // no AT+CIPCLOSE for a closed link
+        let commands = modem.borrow().command_count();
+        TcpClientStack::close(&mut esp, sock).unwrap();
+        assert_eq!(modem.borrow().command_count(), commands);
+    }
+
+    #[test]
+    fn tcp_overrun() {
+        let modem = RefCell::new(Modem::new(&[]));
+        let mut esp = client(&modem);
+        let mut sock = TcpClientStack::socket(&mut esp).unwrap();
+        TcpClientStack::connect(&mut esp, &mut sock, remote()).unwrap();
+
+        // the ring keeps one byte free
+        let room = LINK_BUFFER_CAPACITY - 1;
+        let data = [b'a'; LINK_BUFFER_CAPACITY];
+        modem.borrow_mut().inject_ipd(0, &data[..room]);
+        modem.borrow_mut().inject_ipd(0, b"lost");
+        let mut buf = [0u8; LINK_BUFFER_CAPACITY];
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok(room)
+        );
+        assert!(esp.link(0).unwrap().overrun());
+        // what comes after the gap is dropped too
+        modem.borrow_mut().inject_ipd(0, b"more");
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Err(nb::Error::Other(Error::Overrun))
+        );
+
+        // a new socket on the link starts clean
+        TcpClientStack::close(&mut esp, sock).unwrap();
+        let mut sock = TcpClientStack::socket(&mut esp).unwrap();
+        TcpClientStack::connect(&mut esp, &mut sock, remote()).unwrap();
+        assert!(!esp.link(0).unwrap().overrun());
+        modem.borrow_mut().inject_ipd(0, b"ok");
+        assert_eq!(
+            TcpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn udp_datagrams() {
+        let modem = RefCell::new(Modem::new(&[]));
+        let mut esp = client(&modem);
+
+        // single connection mode
+        let mut sock = UdpClientStack::socket(&mut esp).unwrap();
+        assert_eq!(
+            UdpClientStack::socket(&mut esp).unwrap_err(),
+            Error::NoFreeLink
+        );
+        UdpClientStack::connect(&mut esp, &mut sock, remote()).unwrap();
+        assert_eq!(
+            modem.borrow().last_command(),
+            &b"AT+CIPSTART=\"UDP\",\"192.168.1.198\",2000"[..]
+        );
+
+        UdpClientStack::send(&mut esp, &mut sock, b"ping").unwrap();
+        assert_eq!(modem.borrow().last_command(), b"AT+CIPSEND=4");
+        let too_long = [0u8; 2049];
+        assert_eq!(
+            UdpClientStack::send(&mut esp, &mut sock, &too_long),
+            Err(nb::Error::Other(Error::TooLong))
+        );
+
+        modem.borrow_mut().inject_ipd(0, b"one");
+        modem.borrow_mut().inject_ipd(0, b"second");
+        modem.borrow_mut().inject_ipd(0, b"");
+        modem.borrow_mut().inject_ipd(0, b"3rd");
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok((3, remote()))
+        );
+        assert_eq!(&buf[..3], b"one");
+        // the rest of a datagram that does not fit is dropped
+        let mut small = [0u8; 2];
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut small),
+            Ok((2, remote()))
+        );
+        assert_eq!(&small, b"se");
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok((0, remote()))
+        );
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok((3, remote()))
+        );
+        assert_eq!(&buf[..3], b"3rd");
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Err(nb::Error::WouldBlock)
+        );
+    }
+
+    #[test]
+    fn udp_partial_datagram() {
+        let modem = RefCell::new(Modem::new(&[]));
+        let mut esp = client(&modem);
+        let mut sock = UdpClientStack::socket(&mut esp).unwrap();
+        UdpClientStack::connect(&mut esp, &mut sock, remote()).unwrap();
+
+        // a datagram still arriving is not returned in parts
+        modem.borrow_mut().inject(b"\r\n+IPD,6:hel");
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Err(nb::Error::WouldBlock)
+        );
+        modem.borrow_mut().inject(b"lo!");
+        assert_eq!(
+            UdpClientStack::receive(&mut esp, &mut sock, &mut buf),
+            Ok((6, remote()))
+        );
+        assert_eq!(&buf[..6], b"hello!");
+    }
+}
@@ -0,0 +1,325 @@
+//! Wi-Fi station management
+//!
+//! ```ignore
+//! esp.set_mode(WifiMode::Station)?;
+//!
+//! let mut aps = [AccessPoint::default(); 8];
+//! let n = esp.scan(&mut aps)?;
+//!
+//! esp.join("feather", "-------")?;
+//! let ip = esp.ip_config()?;
+//! ```
+
+use core::fmt;
+use core::net::Ipv4Addr;
+use core::str;
+
+use embedded_hal::serial;
+
+use super::parser::{parse_isize, parse_usize, Fields};
+use super::{EspAt, Error};
+
+/// `AT+CWJAP` waits for DHCP, allow it this many times the command timeout
+const JOIN_TIMEOUT_FACTOR: u32 = 10;
+
+/// Max SSID length in bytes
+pub const SSID_CAPACITY: usize = 32;
+
+/// `AT+CWMODE` values
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum WifiMode {
+    /// Wi-Fi off
+    Off = 0,
+    Station = 1,
+    SoftAp = 2,
+    StationSoftAp = 3,
+}
+
+/// Encryption of an access point, `<ecn>` of `AT+CWLAP`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encryption {
+    Open,
+    Wep,
+    WpaPsk,
+    Wpa2Psk,
+    WpaWpa2Psk,
+    Wpa2Enterprise,
+    Wpa3Psk,
+    Wpa2Wpa3Psk,
+    Unknown(u8),
+}
+
+impl From<u8> for Encryption {
+    fn from(ecn: u8) -> Self {
+        match ecn {
+            0 => Encryption::Open,
+            1 => Encryption::Wep,
+            2 => Encryption::WpaPsk,
+            3 => Encryption::Wpa2Psk,
+            4 => Encryption::WpaWpa2Psk,
+            5 => Encryption::Wpa2Enterprise,
+            6 => Encryption::Wpa3Psk,
+            7 => Encryption::Wpa2Wpa3Psk,
+            n => Encryption::Unknown(n),
+        }
+    }
+}
+
+/// A fixed-capacity SSID
+#[derive(Clone, Copy, PartialEq)]
+pub struct Ssid {
+    buf: [u8; SSID_CAPACITY],
+    len: u8,
+}
+
+impl Ssid {
+    /// Truncates to `SSID_CAPACITY` bytes
+    pub fn new(s: &[u8]) -> Self {
+        let len = usize::min(s.len(), SSID_CAPACITY);
+        let mut buf = [0u8; SSID_CAPACITY];
+        buf[..len].copy_from_slice(&s[..len]);
+        Ssid {
+            buf,
+            len: len as u8,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+
+    /// SSID as string, empty if it is not valid UTF-8
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(self.as_bytes()).unwrap_or("")
+    }
+}
+
+impl Default for Ssid {
+    fn default() -> Self {
+        Ssid::new(b"")
+    }
+}
+
+impl fmt::Debug for Ssid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// One `+CWLAP` record
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccessPoint {
+    pub ecn: Encryption,
+    pub ssid: Ssid,
+    /// Signal strength in dBm
+    pub rssi: i8,
+    pub mac: [u8; 6],
+    pub channel: u8,
+}
+
+impl Default for AccessPoint {
+    fn default() -> Self {
+        AccessPoint {
+            ecn: Encryption::Open,
+            ssid: Ssid::default(),
+            rssi: 0,
+            mac: [0; 6],
+            channel: 0,
+        }
+    }
+}
+
+impl AccessPoint {
+    /// Parses the part after `+CWLAP:`, e.g. `(3,"cjyy",-39,"90:12:34:d4:e4:aa",6)`.
+    /// Fields newer firmwares append after the channel are ignored.
+    pub fn parse(s: &[u8]) -> Option<Self> {
+        let mut fields = Fields::new(s);
+        let ecn = parse_usize(fields.next()?)? as u8;
+        let ssid = Ssid::new(fields.next()?);
+        let rssi = parse_isize(fields.next()?)? as i8;
+        let mac = parse_mac(fields.next()?)?;
+        let channel = parse_usize(fields.next()?)? as u8;
+        Some(AccessPoint {
+            ecn: ecn.into(),
+            ssid,
+            rssi,
+            mac,
+            channel,
+        })
+    }
+}
+
+/// Station address, from `AT+CIPSTA?`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IpConfig {
+    pub ip: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+impl IpConfig {
+    /// Takes one `+CIPSTA:` line, like `+CIPSTA:ip:"192.168.1.9"`.
+    /// Returns false if the line is not understood.
+    pub fn parse_line(&mut self, line: &[u8]) -> bool {
+        let rest = match line.iter().position(|&c| c == b':') {
+            Some(pos) if line.starts_with(b"+CIPSTA") => &line[pos + 1..],
+            _ => return false,
+        };
+        let (slot, value) = if let Some(v) = rest.strip_prefix(b"ip:") {
+            (&mut self.ip, v)
+        } else if let Some(v) = rest.strip_prefix(b"gateway:") {
+            (&mut self.gateway, v)
+        } else if let Some(v) = rest.strip_prefix(b"netmask:") {
+            (&mut self.netmask, v)
+        } else {
+            return false;
+        };
+        match Fields::new(value).next().and_then(parse_ipv4) {
+            Some(addr) => {
+                *slot = addr;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for IpConfig {
+    fn default() -> Self {
+        IpConfig {
+            ip: Ipv4Addr::UNSPECIFIED,
+            gateway: Ipv4Addr::UNSPECIFIED,
+            netmask: Ipv4Addr::UNSPECIFIED,
+        }
+    }
+}
+
+/// Parses `90:12:34:d4:e4:aa`
+pub fn parse_mac(s: &[u8]) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut parts = s.split(|&c| c == b':');
+    for byte in mac.iter_mut() {
+        let part = str::from_utf8(parts.next()?).ok()?;
+        *byte = u8::from_str_radix(part, 16).ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(mac)
+}
+
+pub(crate) fn parse_ipv4(s: &[u8]) -> Option<Ipv4Addr> {
+    str::from_utf8(s).ok()?.parse().ok()
+}
+
+/// Quotes a string parameter, escaping `"`, `,` and `\`
+pub(crate) struct Quoted<'a>(pub &'a str);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use fmt::Write;
+
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            if c == '"' || c == ',' || c == '\\' {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        f.write_char('"')
+    }
+}
+
+impl<TX, RX> EspAt<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    /// `AT+CWMODE=<mode>`
+    pub fn set_mode(&mut self, mode: WifiMode) -> Result<(), Error> {
+        self.send_command_fmt(format_args!("AT+CWMODE={}", mode as u8))
+    }
+
+    /// `AT+CWLAP`, fills `aps` with found access points, returns how many.
+    /// Records that do not fit are dropped.
+    pub fn scan(&mut self, aps: &mut [AccessPoint]) -> Result<usize, Error> {
+        let mut n = 0;
+        let timeout = self.timeout;
+        self.timeout = timeout.saturating_mul(JOIN_TIMEOUT_FACTOR);
+        let ret = self.command_lines(format_args!("AT+CWLAP"), |line| {
+            if let Some(rest) = line.strip_prefix(b"+CWLAP:") {
+                if n < aps.len() {
+                    if let Some(ap) = AccessPoint::parse(rest) {
+                        aps[n] = ap;
+                        n += 1;
+                    }
+                }
+            }
+        });
+        self.timeout = timeout;
+        ret.map(|_| n)
+    }
+
+    /// `AT+CWJAP="<ssid>","<password>"`, connects to an access point
+    pub fn join(&mut self, ssid: &str, password: &str) -> Result<(), Error> {
+        let mut code = 0;
+        let timeout = self.timeout;
+        self.timeout = timeout.saturating_mul(JOIN_TIMEOUT_FACTOR);
+        let ret = self.command_lines(
+            format_args!("AT+CWJAP={},{}", Quoted(ssid), Quoted(password)),
+            |line| {
+                if let Some(rest) = line.strip_prefix(b"+CWJAP:") {
+                    code = parse_usize(rest).unwrap_or(0) as u8;
+                }
+            },
+        );
+        self.timeout = timeout;
+        match ret {
+            Err(Error::Fail) | Err(Error::CommandFailed) if code != 0 => {
+                Err(Error::JoinFailed(code))
+            }
+            ret => ret,
+        }
+    }
+
+    /// `AT+CWQAP`, disconnects from the access point
+    pub fn leave(&mut self) -> Result<(), Error> {
+        self.send_command("AT+CWQAP")
+    }
+
+    /// `AT+CIPSTA?`, station IP, gateway and netmask
+    pub fn ip_config(&mut self) -> Result<IpConfig, Error> {
+        let mut config = IpConfig::default();
+        let mut seen = false;
+        self.command_lines(format_args!("AT+CIPSTA?"), |line| {
+            seen |= config.parse_line(line);
+        })?;
+        if !seen {
+            return Err(Error::Parse);
+        }
+        Ok(config)
+    }
+
+    /// `AT+CWHOSTNAME="<name>"`, station must be enabled
+    pub fn set_hostname(&mut self, hostname: &str) -> Result<(), Error> {
+        self.send_command_fmt(format_args!("AT+CWHOSTNAME={}", Quoted(hostname)))
+    }
+
+    /// `AT+CWHOSTNAME?`, copies the host name into `buf`
+    pub fn hostname<'b>(&mut self, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+        let mut len = None;
+        self.command_lines(format_args!("AT+CWHOSTNAME?"), |line| {
+            if let Some(name) = line.strip_prefix(b"+CWHOSTNAME:") {
+                if name.len() <= buf.len() {
+                    buf[..name.len()].copy_from_slice(name);
+                    len = Some(Ok(name.len()));
+                } else {
+                    len = Some(Err(Error::Overflow));
+                }
+            }
+        })?;
+        let len = len.ok_or(Error::Parse)??;
+        str::from_utf8(&buf[..len]).map_err(|_| Error::Parse)
+    }
+}
@@ -0,0 +1,275 @@
+//! Font blob format
+//!
+//! A font is one binary blob, `include_bytes!` in flash or a file on the SD
+//! card, little endian:
+//!
+//! ```text
+//! offset  size  field
+//!      0     4  magic "LNF" and version 1
+//!      4     4  glyph count n
+//!      8     1  height, in pixels
+//!      9     1  bits per pixel, 1, 2 or 4
+//!     10     1  baseline, rows from the top
+//!     11     1  widest glyph, in pixels
+//!     12     4  codepoint of the replacement glyph, 0xffffffff for none
+//!     16    8n  glyph records sorted by codepoint:
+//!                 u32 codepoint
+//!                 u32 width in the low 8 bits, bitmap offset above
+//! 16 + 8n       bitmaps
+//! ```
+//!
+//! Each bitmap is `height` rows of `ceil(width * bpp / 8)` bytes, leftmost
+//! pixel in the most significant bits. At 2 and 4 bits per pixel, a pixel is
+//! the coverage of the glyph, from 0 for background to all ones for text
+//! colour, for anti-aliased text. Glyphs are found by binary search, and
+//! widths vary per glyph, so 8x16 half-width and 16x16 full-width glyphs live
+//! in one font.
+//!
+//! Fonts are built from BDF, PCF or outline fonts with `tools/font-compiler`,
+//! which shares this file with the firmware.
+
+/// "LNF" and the format version
+pub const MAGIC: [u8; 4] = *b"LNF\x01";
+/// Size of the header
+pub const HEADER_LEN: usize = 16;
+/// Size of one glyph record
+pub const RECORD_LEN: usize = 8;
+/// No replacement glyph
+pub const NO_REPLACEMENT: u32 = 0xffff_ffff;
+
+/// Font errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Not a font, or another version
+    Magic,
+    /// Shorter than its header, records or bitmaps
+    Truncated,
+    /// Bits per pixel other than 1, 2 or 4
+    Depth,
+}
+
+/// Font header
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    pub count: u32,
+    pub height: u8,
+    pub bpp: u8,
+    pub baseline: u8,
+    pub max_width: u8,
+    pub replacement: u32,
+}
+
+impl Header {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        if data[..4] != MAGIC {
+            return Err(Error::Magic);
+        }
+        let header = Header {
+            count: read_u32(data, 4),
+            height: data[8],
+            bpp: data[9],
+            baseline: data[10],
+            max_width: data[11],
+            replacement: read_u32(data, 12),
+        };
+        if ![1, 2, 4].contains(&header.bpp) {
+            return Err(Error::Depth);
+        }
+        Ok(header)
+    }
+
+    /// Writes the header into the first `HEADER_LEN` bytes of `out`
+    pub fn write(&self, out: &mut [u8]) {
+        out[..4].copy_from_slice(&MAGIC);
+        out[4..8].copy_from_slice(&self.count.to_le_bytes());
+        out[8] = self.height;
+        out[9] = self.bpp;
+        out[10] = self.baseline;
+        out[11] = self.max_width;
+        out[12..16].copy_from_slice(&self.replacement.to_le_bytes());
+    }
+
+    /// Start of the bitmaps, `Truncated` if a bogus count puts it past the
+    /// address space
+    pub fn bitmaps_offset(&self) -> Result<usize, Error> {
+        (self.count as usize)
+            .checked_mul(RECORD_LEN)
+            .and_then(|len| len.checked_add(HEADER_LEN))
+            .ok_or(Error::Truncated)
+    }
+
+    /// Bytes per bitmap row of a glyph `width` pixels wide
+    pub fn stride(&self, width: u8) -> usize {
+        (width as usize * self.bpp as usize).div_ceil(8)
+    }
+
+    /// Bytes of the bitmap of a glyph `width` pixels wide
+    pub fn bitmap_len(&self, width: u8) -> usize {
+        self.stride(width) * self.height as usize
+    }
+}
+
+/// Glyph record
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    pub codepoint: u32,
+    pub width: u8,
+    /// From the start of the bitmaps
+    pub offset: u32,
+}
+
+impl Record {
+    pub fn parse(data: &[u8]) -> Self {
+        let packed = read_u32(data, 4);
+        Record {
+            codepoint: read_u32(data, 0),
+            width: packed as u8,
+            offset: packed >> 8,
+        }
+    }
+
+    /// Writes the record into the first `RECORD_LEN` bytes of `out`
+    pub fn write(&self, out: &mut [u8]) {
+        out[..4].copy_from_slice(&self.codepoint.to_le_bytes());
+        let packed = self.offset << 8 | self.width as u32;
+        out[4..8].copy_from_slice(&packed.to_le_bytes());
+    }
+}
+
+/// One glyph bitmap
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph<'a> {
+    pub width: u8,
+    pub height: u8,
+    /// Bits per pixel
+    pub bpp: u8,
+    pub bitmap: &'a [u8],
+}
+
+impl<'a> Glyph<'a> {
+    /// Coverage of a pixel, 0 to 255, 0 outside the glyph
+    pub fn coverage(&self, x: u32, y: u32) -> u8 {
+        if x >= self.width as u32 || y >= self.height as u32 {
+            return 0;
+        }
+        let bpp = self.bpp as u32;
+        let stride = (self.width as u32 * bpp).div_ceil(8);
+        let bit = x * bpp;
+        let max = (1 << bpp) - 1;
+        self.bitmap
+            .get((y * stride + bit / 8) as usize)
+            .map_or(0, |&b| {
+                let level = (b as u32 >> (8 - bpp - bit % 8)) & max;
+                (level * 255 / max) as u8
+            })
+    }
+
+    /// Whether a pixel is at least half covered, `false` outside the glyph
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        self.coverage(x, y) >= 0x80
+    }
+}
+
+/// A font blob
+#[derive(Clone, Copy, Debug)]
+pub struct BitmapFont<'a> {
+    data: &'a [u8],
+    header: Header,
+}
+
+impl<'a> BitmapFont<'a> {
+    /// Checks the header and that the records fit. Bitmaps are checked when
+    /// glyphs are looked up.
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        let header = Header::parse(data)?;
+        if data.len() < header.bitmaps_offset()? {
+            return Err(Error::Truncated);
+        }
+        Ok(BitmapFont { data, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn height(&self) -> u32 {
+        self.header.height as u32
+    }
+
+    /// Number of glyphs
+    pub fn len(&self) -> usize {
+        self.header.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.count == 0
+    }
+
+    /// The `i`th glyph record, by codepoint order
+    pub fn record(&self, i: usize) -> Record {
+        let at = HEADER_LEN + i * RECORD_LEN;
+        Record::parse(&self.data[at..at + RECORD_LEN])
+    }
+
+    /// Index of the record of `codepoint`
+    pub fn find(&self, codepoint: u32) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let at = HEADER_LEN + mid * RECORD_LEN;
+            let c = read_u32(self.data, at);
+            if c == codepoint {
+                return Some(mid);
+            } else if c < codepoint {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        None
+    }
+
+    /// Glyph of `c`, `None` if the font lacks it
+    pub fn glyph(&self, c: char) -> Option<Glyph<'a>> {
+        let record = self.record(self.find(c as u32)?);
+        let start = self
+            .header
+            .bitmaps_offset()
+            .ok()?
+            .checked_add(record.offset as usize)?;
+        let end = start.checked_add(self.header.bitmap_len(record.width))?;
+        let bitmap = self.data.get(start..end)?;
+        Some(Glyph {
+            width: record.width,
+            height: self.header.height,
+            bpp: self.header.bpp,
+            bitmap,
+        })
+    }
+
+    /// Glyph of `c`, or the replacement glyph
+    pub fn glyph_or_replacement(&self, c: char) -> Option<Glyph<'a>> {
+        self.glyph(c).or_else(|| {
+            let replacement = core::char::from_u32(self.header.replacement)?;
+            self.glyph(replacement)
+        })
+    }
+
+    /// Advance of `c`: its width, the replacement's, or half the height
+    pub fn char_width(&self, c: char) -> u32 {
+        self.glyph_or_replacement(c)
+            .map_or(self.height() / 2, |g| g.width as u32)
+    }
+
+    /// Width of a line of text
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.chars().map(|c| self.char_width(c)).sum()
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
@@ -0,0 +1,594 @@
+//! Breaking text into lines that fit a box
+//!
+//! Instead of counting "160 / 8 = 20 chars per line", lay the text out with
+//! the widths of the font it is drawn in:
+//!
+//! ```ignore
+//! let font = Unifont16::font();
+//! let mut layout = Layout::new("温度 23.5°C, humidity 61%", 160, 80).align(Align::Center);
+//! for line in layout.lines(font) {
+//!     font::draw_text(&mut lcd, &font, line.text, Point::new(line.x as i32, line.y as i32), fg, None)?;
+//! }
+//! let overflow = layout.rest();
+//! ```
+//!
+//! Lines break at `\n`, after spaces and hyphens between words, and on
+//! either side of CJK characters, which have no spaces between words.
+//! Kinsoku rules keep closing punctuation like `。` and `」` off the start of
+//! a line and opening punctuation like `「` off its end. A word wider than
+//! the box is cut wherever it runs out of room.
+//!
+//! Spaces at the end of a line are dropped and do not count towards its
+//! width; spaces at the start of the text or after a `\n` are kept.
+//!
+//! This is plain logic over character widths, without drawing, so
+//! `tools/lcd-sim` compiles it in as well.
+
+pub use crate::ui::Align;
+
+use super::format::BitmapFont;
+
+/// Widths of characters and lines, in pixels
+pub trait Metrics {
+    /// Advance of `c`
+    fn char_width(&mut self, c: char) -> u32;
+
+    /// Distance from the top of one line to the next
+    fn line_height(&mut self) -> u32;
+}
+
+impl<M: Metrics + ?Sized> Metrics for &mut M {
+    fn char_width(&mut self, c: char) -> u32 {
+        (**self).char_width(c)
+    }
+
+    fn line_height(&mut self) -> u32 {
+        (**self).line_height()
+    }
+}
+
+impl Metrics for BitmapFont<'_> {
+    fn char_width(&mut self, c: char) -> u32 {
+        BitmapFont::char_width(self, c)
+    }
+
+    fn line_height(&mut self) -> u32 {
+        self.height()
+    }
+}
+
+/// Every character in a cell of the same size, like `Font8x16`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Monospace {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Monospace {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Monospace { width, height }
+    }
+}
+
+impl Metrics for Monospace {
+    fn char_width(&mut self, _: char) -> u32 {
+        self.width
+    }
+
+    fn line_height(&mut self) -> u32 {
+        self.height
+    }
+}
+
+/// One line of laid out text
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Line<'a> {
+    /// The characters to draw, without the line break and trailing spaces
+    pub text: &'a str,
+    /// Byte offset of `text` in the whole text
+    pub start: usize,
+    /// Left edge, from the left of the box
+    pub x: u32,
+    /// Top edge, from the top of the box
+    pub y: u32,
+    /// Width of `text`
+    pub width: u32,
+}
+
+/// Text being broken into lines, one at a time
+///
+/// The layout does not keep the metrics, so a font can draw each line as
+/// soon as it is laid out, like a `StreamFont` that needs `&mut` for both.
+#[derive(Clone, Copy, Debug)]
+pub struct Layout<'a> {
+    text: &'a str,
+    width: u32,
+    height: u32,
+    align: Align,
+    /// Start of the next line
+    pos: usize,
+    /// Top of the next line
+    y: u32,
+}
+
+impl<'a> Layout<'a> {
+    /// Lays `text` out in a box of `width` by `height` pixels
+    pub fn new(text: &'a str, width: u32, height: u32) -> Self {
+        Layout {
+            text,
+            width,
+            height,
+            align: Align::Left,
+            pos: 0,
+            y: 0,
+        }
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Text not laid out yet. Once there are no more lines, this is what did
+    /// not fit in the box, empty if everything did.
+    pub fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// The remaining lines
+    pub fn lines<'l, M: Metrics>(&'l mut self, metrics: M) -> Lines<'a, 'l, M> {
+        Lines {
+            layout: self,
+            metrics,
+        }
+    }
+
+    /// Lays out the rest and returns what did not fit
+    pub fn overflow<M: Metrics>(&mut self, metrics: M) -> &'a str {
+        self.lines(metrics).for_each(drop);
+        self.rest()
+    }
+
+    /// Next line, or `None` at the end of the text or the bottom of the box
+    pub fn next_line<M: Metrics + ?Sized>(&mut self, metrics: &mut M) -> Option<Line<'a>> {
+        let line_height = metrics.line_height();
+        if self.pos >= self.text.len() || self.y + line_height > self.height {
+            return None;
+        }
+
+        let start = self.pos;
+        let (end, width, next) = self.find_break(metrics);
+        let width_left = self.width.saturating_sub(width);
+        let x = match self.align {
+            Align::Left => 0,
+            Align::Center => width_left / 2,
+            Align::Right => width_left,
+        };
+        let line = Line {
+            text: &self.text[start..end],
+            start,
+            x,
+            y: self.y,
+            width,
+        };
+        self.pos = next;
+        self.y += line_height;
+        Some(line)
+    }
+
+    /// End and width of the line from `pos`, and where the next one starts
+    fn find_break<M: Metrics + ?Sized>(&self, metrics: &mut M) -> (usize, u32, usize) {
+        let start = self.pos;
+        let mut chars = self.text[start..].char_indices().peekable();
+        // width up to the current character, spaces included
+        let mut width = 0;
+        // the line so far without trailing spaces
+        let mut end = start;
+        let mut end_width = 0;
+        // last place to break: end, width and start of the next line
+        let mut last_break: Option<(usize, u32, usize)> = None;
+        let mut prev: Option<char> = None;
+        let mut before_prev: Option<char> = None;
+
+        while let Some((i, c)) = chars.next() {
+            let at = start + i;
+            match c {
+                '\n' => return (end, end_width, at + 1),
+                '\r' => {
+                    let next = match chars.peek() {
+                        Some(&(_, '\n')) => at + 2,
+                        _ => at + 1,
+                    };
+                    return (end, end_width, next);
+                }
+                ' ' | '\t' => {
+                    // spaces may hang past the edge
+                    width += metrics.char_width(c);
+                    before_prev = prev;
+                    prev = Some(c);
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some(p) = prev {
+                let after_space = p == ' ' || p == '\t';
+                if after_space || can_break(before_prev, p, c) {
+                    last_break = Some((end, end_width, at));
+                }
+            }
+
+            let w = metrics.char_width(c);
+            // at least one character per line, after any indent
+            if width + w > self.width && end > start {
+                return match last_break {
+                    Some(b) if b.0 > start => b,
+                    // one word wider than the box
+                    _ => (end, end_width, at),
+                };
+            }
+            width += w;
+            end = at + c.len_utf8();
+            end_width = width;
+            before_prev = prev;
+            prev = Some(c);
+        }
+        (end, end_width, self.text.len())
+    }
+}
+
+/// Iterator over the remaining lines of a `Layout`
+pub struct Lines<'a, 'l, M> {
+    layout: &'l mut Layout<'a>,
+    metrics: M,
+}
+
+impl<'a, M: Metrics> Iterator for Lines<'a, '_, M> {
+    type Item = Line<'a>;
+
+    fn next(&mut self) -> Option<Line<'a>> {
+        self.layout.next_line(&mut self.metrics)
+    }
+}
+
+/// Whether a line may break between `a` and `b`, with `a` after `before`.
+/// Spaces and line breaks are handled by the caller.
+fn can_break(before: Option<char>, a: char, b: char) -> bool {
+    if no_end(a) || no_start(b) {
+        return false;
+    }
+    if is_cjk(a) || is_cjk(b) {
+        return true;
+    }
+    // "read-only", but not "-5"
+    a == '-' && b.is_alphanumeric() && before.is_some_and(char::is_alphanumeric)
+}
+
+/// Ideographs, kana, hangul and fullwidth forms, written without spaces
+/// between words
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11ff
+        | 0x2e80..=0x2fff
+        | 0x3000..=0x303f
+        | 0x3040..=0x30ff
+        | 0x3100..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7af
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xffef
+        | 0x20000..=0x3ffff)
+}
+
+/// Kinsoku: closing punctuation, small kana and marks that must not start
+/// a line
+pub fn no_start(c: char) -> bool {
+    matches!(
+        c,
+        '!' | '%'
+            | ')'
+            | ','
+            | '.'
+            | ':'
+            | ';'
+            | '?'
+            | ']'
+            | '}'
+            | '¢'
+            | '°'
+            | '’'
+            | '”'
+            | '‰'
+            | '′'
+            | '″'
+            | '℃'
+            | '‥'
+            | '…'
+            | '、'
+            | '。'
+            | '々'
+            | '〉'
+            | '》'
+            | '」'
+            | '』'
+            | '】'
+            | '〕'
+            | '〗'
+            | '〙'
+            | '〛'
+            | '〜'
+            | 'ゝ'
+            | 'ゞ'
+            | '・'
+            | 'ヽ'
+            | 'ヾ'
+            | 'ー'
+            | 'ぁ'
+            | 'ぃ'
+            | 'ぅ'
+            | 'ぇ'
+            | 'ぉ'
+            | 'っ'
+            | 'ゃ'
+            | 'ゅ'
+            | 'ょ'
+            | 'ゎ'
+            | 'ゕ'
+            | 'ゖ'
+            | 'ァ'
+            | 'ィ'
+            | 'ゥ'
+            | 'ェ'
+            | 'ォ'
+            | 'ッ'
+            | 'ャ'
+            | 'ュ'
+            | 'ョ'
+            | 'ヮ'
+            | 'ヵ'
+            | 'ヶ'
+            | '！'
+            | '％'
+            | '）'
+            | '，'
+            | '．'
+            | '：'
+            | '；'
+            | '？'
+            | '］'
+            | '｝'
+            | '～'
+            | '｡'
+            | '｣'
+            | '､'
+            | '･'
+            | 'ｰ'
+            | 'ﾞ'
+            | 'ﾟ'
+    ) || ('ｧ'..='ｯ').contains(&c)
+}
+
+/// Kinsoku: opening brackets and quotes, and currency signs, that must not
+/// end a line
+pub fn no_end(c: char) -> bool {
+    matches!(
+        c,
+        '$' | '('
+            | '['
+            | '{'
+            | '£'
+            | '¥'
+            | '‘'
+            | '“'
+            | '〈'
+            | '《'
+            | '「'
+            | '『'
+            | '【'
+            | '〔'
+            | '〖'
+            | '〘'
+            | '〚'
+            | '（'
+            | '［'
+            | '｛'
+            | '｢'
+            | '￡'
+            | '￥'
+            | '＄'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::format::{Header, Record, HEADER_LEN, RECORD_LEN};
+
+    /// 8x16 half-width and 16x16 full-width cells, like Unifont
+    struct Unifont;
+
+    impl Metrics for Unifont {
+        fn char_width(&mut self, c: char) -> u32 {
+            if is_cjk(c) {
+                16
+            } else {
+                8
+            }
+        }
+
+        fn line_height(&mut self) -> u32 {
+            16
+        }
+    }
+
+    /// Lines of `text` in a box `chars` half-width cells wide and tall enough
+    fn wrap<M: Metrics>(text: &str, chars: u32, metrics: M) -> Vec<String> {
+        Layout::new(text, chars * 8, 1000)
+            .lines(metrics)
+            .map(|line| line.text.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_words() {
+        let mono = Monospace::new(8, 16);
+        assert_eq!(
+            wrap("The quick brown fox jumps", 10, mono),
+            ["The quick", "brown fox", "jumps"]
+        );
+        // a word that exactly fills the line
+        assert_eq!(wrap("abcde fghij", 5, mono), ["abcde", "fghij"]);
+        assert_eq!(wrap("read-only mode", 7, mono), ["read-", "only", "mode"]);
+        // a minus sign stays with its number
+        assert_eq!(wrap("ab -5678", 5, mono), ["ab", "-5678"]);
+    }
+
+    #[test]
+    fn drops_trailing_spaces() {
+        let mut layout = Layout::new("hello     world  ", 48, 32);
+        let lines: Vec<Line> = layout.lines(Monospace::new(8, 16)).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].text, lines[0].width), ("hello", 40));
+        assert_eq!(
+            (lines[1].text, lines[1].start, lines[1].width),
+            ("world", 10, 40)
+        );
+        assert_eq!(layout.rest(), "");
+
+        // indents are kept
+        assert_eq!(
+            wrap("  ab\n    cd", 10, Monospace::new(8, 16)),
+            ["  ab", "    cd"]
+        );
+    }
+
+    #[test]
+    fn cuts_long_words() {
+        assert_eq!(
+            wrap("abcdefghijkl", 5, Monospace::new(8, 16)),
+            ["abcde", "fghij", "kl"]
+        );
+        // wider than the box on its own
+        assert_eq!(wrap("温a", 1, Unifont), ["温", "a"]);
+    }
+
+    #[test]
+    fn breaks_at_newlines() {
+        let mono = Monospace::new(8, 16);
+        assert_eq!(wrap("a\n\nb\r\nc\rd", 10, mono), ["a", "", "b", "c", "d"]);
+        assert_eq!(wrap("a  \nb\n", 10, mono), ["a", "b"]);
+        assert!(wrap("", 10, mono).is_empty());
+    }
+
+    #[test]
+    fn breaks_between_ideographs() {
+        assert_eq!(wrap("温度湿度气压", 6, Unifont), ["温度湿", "度气压"]);
+        assert_eq!(wrap("Rust编程语言", 5, Unifont), ["Rust", "编程", "语言"]);
+        assert_eq!(wrap("温度 23.5°C", 6, Unifont), ["温度", "23.5°C"]);
+        assert_eq!(
+            wrap("こんにちは世界", 6, Unifont),
+            ["こんに", "ちは世", "界"]
+        );
+    }
+
+    #[test]
+    fn applies_kinsoku() {
+        // 。 does not start a line, so 湿 moves down with it
+        assert_eq!(wrap("温度湿。气压", 6, Unifont), ["温度", "湿。气", "压"]);
+        // 「 does not end a line, 」 does not start one
+        assert_eq!(wrap("温度「湿度」", 6, Unifont), ["温度", "「湿", "度」"]);
+        assert_eq!(
+            wrap("東京（とうきょう）", 6, Unifont),
+            ["東京", "（とう", "きょ", "う）"]
+        );
+        // small kana stay with the one before
+        assert_eq!(wrap("今日はちょっと", 8, Unifont), ["今日は", "ちょっと"]);
+    }
+
+    #[test]
+    fn aligns_lines() {
+        let text = "ab cdef";
+        let place = |align| -> Vec<(u32, u32, u32)> {
+            Layout::new(text, 40, 100)
+                .align(align)
+                .lines(Monospace::new(8, 16))
+                .map(|line| (line.x, line.y, line.width))
+                .collect()
+        };
+        assert_eq!(place(Align::Left), [(0, 0, 16), (0, 16, 32)]);
+        assert_eq!(place(Align::Center), [(12, 0, 16), (4, 16, 32)]);
+        assert_eq!(place(Align::Right), [(24, 0, 16), (8, 16, 32)]);
+
+        // a line wider than the box starts at its left edge
+        let line = Layout::new("温", 8, 16)
+            .align(Align::Right)
+            .next_line(&mut Unifont)
+            .unwrap();
+        assert_eq!((line.x, line.width), (0, 16));
+    }
+
+    #[test]
+    fn reports_overflow() {
+        let text = "one two three four";
+        let mut layout = Layout::new(text, 40, 40);
+        let lines: Vec<&str> = layout
+            .lines(Monospace::new(8, 16))
+            .map(|l| l.text)
+            .collect();
+        assert_eq!(lines, ["one", "two"]);
+        assert_eq!(layout.rest(), "three four");
+
+        let mut layout = Layout::new(text, 40, 40);
+        assert_eq!(layout.overflow(Monospace::new(8, 16)), "three four");
+        let mut layout = Layout::new(text, 40, 64);
+        assert_eq!(layout.overflow(Monospace::new(8, 16)), "");
+        // shorter than a line
+        let mut layout = Layout::new(text, 40, 15);
+        assert_eq!(layout.overflow(Monospace::new(8, 16)), text);
+
+        // the "160 / 8 = 20 chars per line" screen
+        let mut layout = Layout::new("温度 23.5°C 湿度 61% 气压 1013hPa 风速 3.4m/s 晴", 160, 32);
+        assert_eq!(layout.overflow(Unifont), "3.4m/s 晴");
+    }
+
+    /// A 16 pixel high font blob of the given widths, `?` as the replacement
+    fn blob(widths: &[(char, u8)]) -> Vec<u8> {
+        let header = Header {
+            count: widths.len() as u32,
+            height: 16,
+            bpp: 1,
+            baseline: 14,
+            max_width: 16,
+            replacement: '?' as u32,
+        };
+        let mut data = vec![0; header.bitmaps_offset().unwrap()];
+        header.write(&mut data);
+        let mut offset = 0;
+        for (i, &(c, width)) in widths.iter().enumerate() {
+            let at = HEADER_LEN + i * RECORD_LEN;
+            Record {
+                codepoint: c as u32,
+                width,
+                offset,
+            }
+            .write(&mut data[at..]);
+            offset += header.bitmap_len(width) as u32;
+        }
+        data.resize(header.bitmaps_offset().unwrap() + offset as usize, 0);
+        data
+    }
+
+    #[test]
+    fn uses_font_widths() {
+        let data = blob(&[(' ', 4), ('?', 8), ('i', 3), ('m', 10), ('温', 16)]);
+        let font = BitmapFont::new(&data).unwrap();
+
+        let mut layout = Layout::new("mmm iii 温温 x", 40, 64);
+        let lines: Vec<(&str, u32)> = layout.lines(font).map(|l| (l.text, l.width)).collect();
+        // x is missing and as wide as ?
+        assert_eq!(lines, [("mmm", 30), ("iii 温", 29), ("温 x", 28)]);
+    }
+}
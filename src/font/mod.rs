@@ -0,0 +1,291 @@
+//! Bitmap fonts for any subset of Unicode
+//!
+//! Fonts small enough for flash are embedded with `bitmap_font!`, which
+//! declares an embedded-graphics font over a blob:
+//!
+//! ```ignore
+//! bitmap_font!(pub Unifont16 = include_bytes!("../font.bin"));
+//!
+//! let style = text_style!(font = Unifont16, text_color = Rgb565::WHITE);
+//! Text::new("温度 23.5°C", Point::new(0, 0)).into_styled(style).draw(&mut lcd)?;
+//! ```
+//!
+//! `Text` looks the glyph up again for every pixel, longer text is faster
+//! through the declared font's `draw_text`, see `bitmap_font!`.
+//!
+//! Larger fonts, like a whole GB2312 or BMP font, stay on the SD card and are
+//! read glyph by glyph with `stream::StreamFont`. The blob format is in
+//! `format`.
+//!
+//! Fonts of 2 or 4 bits per pixel are anti-aliased: `draw_text` and
+//! `StreamFont::draw_text` blend each pixel between the text and background
+//! colours. embedded-graphics only knows pixels that are on or off, so
+//! through `bitmap_font!` they are drawn where at least half covered.
+//!
+//! ```ignore
+//! bitmap_font!(pub Sans12 = include_bytes!("../sans12-4bpp.bin"));
+//!
+//! font::draw_text(&mut lcd, &Sans12::font(), "Hello", Point::new(0, 0), Rgb565::WHITE, Some(Rgb565::BLACK))?;
+//! ```
+//!
+//! Paragraphs are wrapped to a box by `layout`, with the widths of the font:
+//!
+//! ```ignore
+//! let area = Rectangle::new(Point::new(0, 0), Point::new(159, 79));
+//! let overflow = font::draw_layout(&mut lcd, &Unifont16::font(), text, area, Align::Left, Rgb565::WHITE, None)?;
+//! ```
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::fonts::Font;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::DrawTarget;
+
+pub mod format;
+pub mod layout;
+pub mod stream;
+
+pub use self::format::{BitmapFont, Error, Glyph, Header, Record};
+pub use self::layout::{Align, Layout, Line, Metrics};
+
+/// Mixes `alpha` of `fg` over `bg`, alpha from 0 to 255
+pub fn blend(fg: Rgb565, bg: Rgb565, alpha: u8) -> Rgb565 {
+    let a = alpha as u32;
+    let mix = |f: u8, b: u8| ((f as u32 * a + b as u32 * (255 - a) + 127) / 255) as u8;
+    Rgb565::new(
+        mix(fg.r(), bg.r()),
+        mix(fg.g(), bg.g()),
+        mix(fg.b(), bg.b()),
+    )
+}
+
+/// Draws a glyph with its top left corner at `top_left`. With a background
+/// colour, every pixel is drawn blended between `fg` and `bg`; without one,
+/// only pixels at least half covered are drawn, in `fg`.
+pub fn draw_glyph<D: DrawTarget<Rgb565>>(
+    target: &mut D,
+    glyph: &Glyph,
+    top_left: Point,
+    fg: Rgb565,
+    bg: Option<Rgb565>,
+) -> Result<(), D::Error> {
+    let (width, height) = (glyph.width as u32, glyph.height as u32);
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let color = match (glyph.coverage(x, y), bg) {
+                (0xff, _) => Some(fg),
+                (alpha, Some(bg)) => Some(blend(fg, bg, alpha)),
+                (alpha, None) if alpha >= 0x80 => Some(fg),
+                _ => None,
+            };
+            color.map(|c| Pixel(top_left + Point::new(x as i32, y as i32), c))
+        });
+    target.draw_iter(pixels)
+}
+
+/// Draws text in a font blob from its top left corner, lines split at `\n`,
+/// anti-aliased if the font is. Returns where the next character goes.
+pub fn draw_text<D: DrawTarget<Rgb565>>(
+    target: &mut D,
+    font: &BitmapFont,
+    text: &str,
+    position: Point,
+    fg: Rgb565,
+    bg: Option<Rgb565>,
+) -> Result<Point, D::Error> {
+    let height = font.height() as i32;
+    let mut at = position;
+    for c in text.chars() {
+        if c == '\n' {
+            at = Point::new(position.x, at.y + height);
+            continue;
+        }
+        at.x += match font.glyph_or_replacement(c) {
+            Some(glyph) => {
+                draw_glyph(target, &glyph, at, fg, bg)?;
+                glyph.width as i32
+            }
+            None => height / 2,
+        };
+    }
+    Ok(at)
+}
+
+/// Wraps text in a font blob to `bounds`, inclusive, and draws the lines
+/// that fit. Returns the text that did not, empty if all of it did.
+pub fn draw_layout<'t, D: DrawTarget<Rgb565>>(
+    target: &mut D,
+    font: &BitmapFont,
+    text: &'t str,
+    bounds: Rectangle,
+    align: Align,
+    fg: Rgb565,
+    bg: Option<Rgb565>,
+) -> Result<&'t str, D::Error> {
+    let (width, height) = box_size(&bounds);
+    let mut layout = Layout::new(text, width, height).align(align);
+    for line in layout.lines(*font) {
+        let at = bounds.top_left + Point::new(line.x as i32, line.y as i32);
+        draw_text(target, font, line.text, at, fg, bg)?;
+    }
+    Ok(layout.rest())
+}
+
+/// Width and height of an inclusive rectangle
+pub(crate) fn box_size(bounds: &Rectangle) -> (u32, u32) {
+    (
+        (bounds.bottom_right.x - bounds.top_left.x + 1).max(0) as u32,
+        (bounds.bottom_right.y - bounds.top_left.y + 1).max(0) as u32,
+    )
+}
+
+/// Layout widths of an embedded-graphics font, like `Font8x16` or one
+/// declared with `bitmap_font!`
+#[derive(Clone, Copy, Debug)]
+pub struct FontMetrics<F>(pub F);
+
+impl<F: Font> Metrics for FontMetrics<F> {
+    fn char_width(&mut self, c: char) -> u32 {
+        F::char_width(c) + F::CHARACTER_SPACING
+    }
+
+    fn line_height(&mut self) -> u32 {
+        F::CHARACTER_SIZE.height
+    }
+}
+
+/// Declares a zero-sized embedded-graphics `Font` over a font blob, which
+/// must be valid
+///
+/// ```ignore
+/// bitmap_font!(pub Wenquanyi12 = include_bytes!("../wqy12.bin"));
+/// ```
+///
+/// embedded-graphics asks a `Font` for one pixel at a time, and the type has
+/// nowhere to keep the glyph between calls, so drawn through `Text` every
+/// pixel parses the header and binary searches the index again: a 16x16
+/// glyph of a 7000 character font costs 256 lookups of 13 steps. Fine for a
+/// few characters; for more, the generated `draw_text` looks each glyph up
+/// once:
+///
+/// ```ignore
+/// Wenquanyi12::draw_text(&mut lcd, "温度 23.5°C", Point::new(0, 0), Rgb565::WHITE, None)?;
+/// ```
+#[macro_export]
+macro_rules! bitmap_font {
+    ($(#[$attr:meta])* $vis:vis $name:ident = $data:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug)]
+        $vis struct $name;
+
+        impl $name {
+            const DATA: &'static [u8] = $data;
+
+            /// The font blob
+            pub fn font() -> $crate::font::BitmapFont<'static> {
+                $crate::font::BitmapFont::new(Self::DATA).expect("invalid font")
+            }
+
+            /// Draws text with one lookup per character, see
+            /// `font::draw_text`
+            pub fn draw_text<D>(
+                target: &mut D,
+                text: &str,
+                position: $crate::__embedded_graphics::geometry::Point,
+                fg: $crate::__embedded_graphics::pixelcolor::Rgb565,
+                bg: Option<$crate::__embedded_graphics::pixelcolor::Rgb565>,
+            ) -> Result<$crate::__embedded_graphics::geometry::Point, D::Error>
+            where
+                D: $crate::__embedded_graphics::DrawTarget<
+                    $crate::__embedded_graphics::pixelcolor::Rgb565,
+                >,
+            {
+                $crate::font::draw_text(target, &Self::font(), text, position, fg, bg)
+            }
+        }
+
+        impl $crate::__embedded_graphics::fonts::Font for $name {
+            const FONT_IMAGE: &'static [u8] = $name::DATA;
+            const FONT_IMAGE_WIDTH: u32 = 0;
+            const CHARACTER_SIZE: $crate::__embedded_graphics::geometry::Size =
+                $crate::__embedded_graphics::geometry::Size::new(
+                    $name::DATA[11] as u32,
+                    $name::DATA[8] as u32,
+                );
+            const VARIABLE_WIDTH: bool = true;
+
+            fn char_offset(_: char) -> u32 {
+                // glyphs are looked up by character_pixel
+                0
+            }
+
+            fn char_width(c: char) -> u32 {
+                Self::font().char_width(c)
+            }
+
+            // a lookup per pixel, see above
+            fn character_pixel(c: char, x: u32, y: u32) -> bool {
+                Self::font()
+                    .glyph_or_replacement(c)
+                    .map_or(false, |g| g.pixel(x, y))
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::fonts::Text;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::text_style;
+
+    use super::*;
+    use crate::ui::tests::{Canvas, BLANK};
+
+    /// 1bpp, 2 rows: `?` 3 wide and `a` 2 wide
+    #[rustfmt::skip]
+    const TINY: [u8; 36] = [
+        b'L', b'N', b'F', 1, 2, 0, 0, 0, 2, 1, 2, 3, b'?', 0, 0, 0,
+        b'?', 0, 0, 0, 3, 0, 0, 0,
+        b'a', 0, 0, 0, 2, 2, 0, 0,
+        0b1110_0000, 0b1010_0000,
+        0b1000_0000, 0b0100_0000,
+    ];
+
+    crate::bitmap_font!(Tiny = &TINY);
+
+    #[test]
+    fn draws_like_text() {
+        let mut direct = Canvas::new();
+        let end = Tiny::draw_text(&mut direct, "a?b", Point::new(1, 1), Rgb565::RED, None);
+        // b is missing, drawn as ?
+        assert_eq!(end.unwrap(), Point::new(9, 1));
+
+        let mut text = Canvas::new();
+        Text::new("a?b", Point::new(1, 1))
+            .into_styled(text_style!(font = Tiny, text_color = Rgb565::RED))
+            .draw(&mut text)
+            .unwrap();
+        assert_eq!(direct.pixels[..], text.pixels[..]);
+        assert_eq!(direct.pixel(1, 1), Rgb565::RED);
+        assert_eq!(direct.pixel(2, 1), BLANK);
+        assert_eq!(direct.pixel(4, 2), BLANK);
+        assert_eq!(
+            direct.find(Rgb565::RED),
+            Some(Rectangle::new(Point::new(1, 1), Point::new(8, 2)))
+        );
+    }
+    #[test]
+    fn rejects_bogus_counts() {
+        let mut data = TINY;
+        data[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            format::BitmapFont::new(&data).unwrap_err(),
+            format::Error::Truncated
+        );
+        let header = format::Header::parse(&data).unwrap();
+        assert!(header.bitmaps_offset().unwrap_or(usize::MAX) > data.len());
+    }
+}
@@ -0,0 +1,658 @@
+//! Fonts read glyph by glyph from a file
+//!
+//! A 16x16 font of GB2312 or of the whole BMP is hundreds of KB, more than
+//! the flash. `StreamFont` keeps the header and a sparse index of the glyph
+//! records in RAM, reads each glyph from a `GlyphSource` the first time it is
+//! drawn, and keeps the `CACHE_SLOTS` most recently used ones, so redrawing
+//! the same text does not touch the card.
+//!
+//! With the `sdcard` feature, a font file on the SD card is opened through
+//! the embedded-sdmmc `Controller`, as in `examples/bad_apple.rs`:
+//!
+//! ```ignore
+//! let mut vol = cntlr.get_volume(sdmmc::VolumeIdx(0)).unwrap();
+//! let dir = cntlr.open_root_dir(&vol).unwrap();
+//! // must be a DOS 8.3 name
+//! let file = SdFile::open(&mut cntlr, &mut vol, &dir, "UNIFONT.BIN").unwrap();
+//! let mut font = StreamFont::new(file).unwrap();
+//! font.draw_text(&mut lcd, "温度 23.5°C", Point::new(0, 16), Rgb565::WHITE, None)?;
+//! ```
+//!
+//! Lookups binary search the records on the card, after narrowing them down
+//! to one of `INDEX_LEN` buckets in RAM. Characters missing from the font
+//! are cached too.
+
+use core::convert::{Infallible, TryFrom};
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::DrawTarget;
+
+#[cfg(feature = "sdcard")]
+use embedded_sdmmc as sdmmc;
+
+use super::format::{Error, Glyph, Header, Record, HEADER_LEN, RECORD_LEN};
+use super::layout::{Align, Layout, Metrics};
+
+/// Glyphs kept in RAM
+pub const CACHE_SLOTS: usize = 16;
+/// Largest cached glyph bitmap, 32x32 at 1bpp or 16x16 at 4bpp
+pub const SLOT_LEN: usize = 128;
+/// Codepoints sampled from the records to narrow down lookups, 1 KiB of RAM.
+/// The ~57k glyphs of Unifont leave 224 records, 8 reads, per bucket.
+pub const INDEX_LEN: usize = 256;
+
+/// Random access to a font blob
+pub trait GlyphSource {
+    type Error;
+
+    /// Reads up to `buf.len()` bytes at `offset`, returns how many were read,
+    /// 0 at the end
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// A blob in memory, mostly for testing
+impl GlyphSource for &[u8] {
+    type Error = Infallible;
+
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let data = self.get(offset as usize..).unwrap_or(&[]);
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+/// Stream font errors
+#[derive(Debug)]
+pub enum StreamError<E> {
+    /// Invalid or truncated font
+    Font(Error),
+    /// The source failed
+    Source(E),
+    /// Glyph bitmap larger than `SLOT_LEN`
+    TooLarge,
+    /// The draw target failed
+    Draw,
+}
+
+impl<E> From<Error> for StreamError<E> {
+    fn from(e: Error) -> Self {
+        StreamError::Font(e)
+    }
+}
+
+/// One cached glyph
+#[derive(Clone, Copy)]
+struct Slot {
+    codepoint: u32,
+    /// `false` if the font lacks it
+    found: bool,
+    width: u8,
+    /// Clock of the last use, 0 for an empty slot
+    used: u32,
+    bitmap: [u8; SLOT_LEN],
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        codepoint: 0,
+        found: false,
+        width: 0,
+        used: 0,
+        bitmap: [0; SLOT_LEN],
+    };
+}
+
+/// A font read on demand, with an LRU glyph cache
+pub struct StreamFont<S> {
+    source: S,
+    header: Header,
+    /// Codepoints of the records at `bucket_start(i)`
+    index: [u32; INDEX_LEN],
+    slots: [Slot; CACHE_SLOTS],
+    clock: u32,
+    hits: u32,
+    misses: u32,
+}
+
+impl<S: GlyphSource> StreamFont<S> {
+    /// Reads the header and samples the records
+    pub fn new(source: S) -> Result<Self, StreamError<S::Error>> {
+        let mut font = StreamFont {
+            source,
+            header: Header {
+                count: 0,
+                height: 0,
+                bpp: 1,
+                baseline: 0,
+                max_width: 0,
+                replacement: 0,
+            },
+            index: [0; INDEX_LEN],
+            slots: [Slot::EMPTY; CACHE_SLOTS],
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        };
+        let mut header = [0; HEADER_LEN];
+        font.read_exact(0, &mut header)?;
+        font.header = Header::parse(&header)?;
+        // records are read at u32 offsets
+        if u32::try_from(font.header.bitmaps_offset()?).is_err() {
+            return Err(StreamError::Font(Error::Truncated));
+        }
+        if font.header.count > 0 {
+            for i in 0..INDEX_LEN {
+                font.index[i] = font.read_record(font.bucket_start(i)?)?.codepoint;
+            }
+        }
+        Ok(font)
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn height(&self) -> u32 {
+        self.header.height as u32
+    }
+
+    /// Cache (hits, misses) since the start
+    pub fn stats(&self) -> (u32, u32) {
+        (self.hits, self.misses)
+    }
+
+    /// Empties the cache
+    pub fn clear_cache(&mut self) {
+        self.slots = [Slot::EMPTY; CACHE_SLOTS];
+    }
+
+    /// Gives the source back, e.g. to close the file
+    pub fn release(self) -> S {
+        self.source
+    }
+
+    /// Glyph of `c`, `None` if the font lacks it
+    pub fn glyph(&mut self, c: char) -> Result<Option<Glyph<'_>>, StreamError<S::Error>> {
+        Ok(self.lookup(c as u32)?.map(move |i| self.slot_glyph(i)))
+    }
+
+    /// Glyph of `c`, or the replacement glyph
+    pub fn glyph_or_replacement(
+        &mut self,
+        c: char,
+    ) -> Result<Option<Glyph<'_>>, StreamError<S::Error>> {
+        let mut slot = self.lookup(c as u32)?;
+        if slot.is_none() && core::char::from_u32(self.header.replacement).is_some() {
+            slot = self.lookup(self.header.replacement)?;
+        }
+        Ok(slot.map(move |i| self.slot_glyph(i)))
+    }
+
+    /// Advance of `c`: its width, the replacement's, or half the height
+    pub fn char_width(&mut self, c: char) -> Result<u32, StreamError<S::Error>> {
+        let half = self.height() / 2;
+        Ok(self
+            .glyph_or_replacement(c)?
+            .map_or(half, |g| g.width as u32))
+    }
+
+    /// Width of a line of text
+    pub fn text_width(&mut self, text: &str) -> Result<u32, StreamError<S::Error>> {
+        text.chars().map(|c| self.char_width(c)).sum()
+    }
+
+    /// Draws text from its top left corner, lines split at `\n`, background
+    /// pixels only if `bg` is given. Returns where the next character goes.
+    pub fn draw_text<D: DrawTarget<Rgb565>>(
+        &mut self,
+        target: &mut D,
+        text: &str,
+        position: Point,
+        fg: Rgb565,
+        bg: Option<Rgb565>,
+    ) -> Result<Point, StreamError<S::Error>> {
+        let height = self.height() as i32;
+        let half = height / 2;
+        let mut at = position;
+        for c in text.chars() {
+            if c == '\n' {
+                at = Point::new(position.x, at.y + height);
+                continue;
+            }
+            at.x += match self.glyph_or_replacement(c)? {
+                Some(glyph) => {
+                    super::draw_glyph(target, &glyph, at, fg, bg).map_err(|_| StreamError::Draw)?;
+                    glyph.width as i32
+                }
+                None => half,
+            };
+        }
+        Ok(at)
+    }
+
+    /// Wraps text to `bounds`, inclusive, and draws the lines that fit.
+    /// Returns the text that did not, empty if all of it did.
+    ///
+    /// Each line is measured, then drawn, so its glyphs are looked up twice.
+    /// The second lookup hits the cache as long as a line has at most
+    /// `CACHE_SLOTS` distinct characters, beyond that every glyph is read
+    /// from the source twice.
+    pub fn draw_layout<'t, D: DrawTarget<Rgb565>>(
+        &mut self,
+        target: &mut D,
+        text: &'t str,
+        bounds: Rectangle,
+        align: Align,
+        fg: Rgb565,
+        bg: Option<Rgb565>,
+    ) -> Result<&'t str, StreamError<S::Error>> {
+        let (width, height) = super::box_size(&bounds);
+        let mut layout = Layout::new(text, width, height).align(align);
+        while let Some(line) = layout.next_line(self) {
+            let at = bounds.top_left + Point::new(line.x as i32, line.y as i32);
+            self.draw_text(target, line.text, at, fg, bg)?;
+        }
+        Ok(layout.rest())
+    }
+
+    /// Slot of `codepoint`, read into the least recently used slot if not
+    /// cached. `None` if the font lacks it.
+    fn lookup(&mut self, codepoint: u32) -> Result<Option<usize>, StreamError<S::Error>> {
+        self.clock = self.clock.wrapping_add(1).max(1);
+        if let Some(i) = self
+            .slots
+            .iter()
+            .position(|s| s.used != 0 && s.codepoint == codepoint)
+        {
+            self.hits += 1;
+            self.slots[i].used = self.clock;
+            return Ok(if self.slots[i].found { Some(i) } else { None });
+        }
+
+        self.misses += 1;
+        let i = (0..CACHE_SLOTS)
+            .min_by_key(|&i| self.slots[i].used)
+            .unwrap_or(0);
+        // empty until read completely
+        self.slots[i].used = 0;
+        let record = self.find(codepoint)?;
+        if let Some(record) = record {
+            let len = self.header.bitmap_len(record.width);
+            if len > SLOT_LEN {
+                return Err(StreamError::TooLarge);
+            }
+            let offset = (self.header.bitmaps_offset()? as u32)
+                .checked_add(record.offset)
+                .ok_or(Error::Truncated)?;
+            let mut bitmap = [0; SLOT_LEN];
+            self.read_exact(offset, &mut bitmap[..len])?;
+            self.slots[i].bitmap = bitmap;
+        }
+        let slot = &mut self.slots[i];
+        slot.codepoint = codepoint;
+        slot.found = record.is_some();
+        slot.width = record.map_or(0, |r| r.width);
+        slot.used = self.clock;
+        Ok(if slot.found { Some(i) } else { None })
+    }
+
+    fn slot_glyph(&self, i: usize) -> Glyph<'_> {
+        let slot = &self.slots[i];
+        Glyph {
+            width: slot.width,
+            height: self.header.height,
+            bpp: self.header.bpp,
+            bitmap: &slot.bitmap[..self.header.bitmap_len(slot.width)],
+        }
+    }
+
+    /// First record of a bucket of the index
+    fn bucket_start(&self, bucket: usize) -> Result<usize, Error> {
+        bucket
+            .checked_mul(self.header.count as usize)
+            .map(|n| n / INDEX_LEN)
+            .ok_or(Error::Truncated)
+    }
+
+    /// Record of `codepoint`, by binary search within its bucket
+    fn find(&mut self, codepoint: u32) -> Result<Option<Record>, StreamError<S::Error>> {
+        if self.header.count == 0 || codepoint < self.index[0] {
+            return Ok(None);
+        }
+        let bucket = self
+            .index
+            .iter()
+            .rposition(|&c| c <= codepoint)
+            .unwrap_or(0);
+        let mut lo = self.bucket_start(bucket)?;
+        let mut hi = if bucket + 1 < INDEX_LEN {
+            self.bucket_start(bucket + 1)?
+        } else {
+            self.header.count as usize
+        };
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let record = self.read_record(mid)?;
+            if record.codepoint == codepoint {
+                return Ok(Some(record));
+            } else if record.codepoint < codepoint {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_record(&mut self, i: usize) -> Result<Record, StreamError<S::Error>> {
+        let mut record = [0; RECORD_LEN];
+        self.read_exact((HEADER_LEN + i * RECORD_LEN) as u32, &mut record)?;
+        Ok(Record::parse(&record))
+    }
+
+    fn read_exact(
+        &mut self,
+        mut offset: u32,
+        mut buf: &mut [u8],
+    ) -> Result<(), StreamError<S::Error>> {
+        while !buf.is_empty() {
+            let n = self
+                .source
+                .read_at(offset, buf)
+                .map_err(StreamError::Source)?;
+            if n == 0 {
+                return Err(StreamError::Font(Error::Truncated));
+            }
+            offset += n as u32;
+            buf = &mut buf[n..];
+        }
+        Ok(())
+    }
+}
+
+impl<S: GlyphSource> Metrics for StreamFont<S> {
+    fn char_width(&mut self, c: char) -> u32 {
+        // a read error comes up again when the line is drawn
+        let half = self.height() / 2;
+        StreamFont::char_width(self, c).unwrap_or(half)
+    }
+
+    fn line_height(&mut self) -> u32 {
+        self.height()
+    }
+}
+
+/// A file on a FAT volume of the SD card
+#[cfg(feature = "sdcard")]
+pub struct SdFile<'a, D, T>
+where
+    D: sdmmc::BlockDevice,
+    T: sdmmc::TimeSource,
+{
+    controller: &'a mut sdmmc::Controller<D, T>,
+    volume: &'a mut sdmmc::Volume,
+    file: sdmmc::File,
+}
+
+#[cfg(feature = "sdcard")]
+impl<'a, D, T> SdFile<'a, D, T>
+where
+    D: sdmmc::BlockDevice,
+    D::Error: core::fmt::Debug,
+    T: sdmmc::TimeSource,
+{
+    /// Opens `name` in `dir` read only, `name` being a DOS 8.3 name
+    pub fn open(
+        controller: &'a mut sdmmc::Controller<D, T>,
+        volume: &'a mut sdmmc::Volume,
+        dir: &sdmmc::Directory,
+        name: &str,
+    ) -> Result<Self, sdmmc::Error<D::Error>> {
+        let file = controller.open_file_in_dir(volume, dir, name, sdmmc::Mode::ReadOnly)?;
+        Ok(SdFile {
+            controller,
+            volume,
+            file,
+        })
+    }
+
+    pub fn close(self) -> Result<(), sdmmc::Error<D::Error>> {
+        self.controller.close_file(self.volume, self.file)
+    }
+}
+
+#[cfg(feature = "sdcard")]
+impl<'a, D, T> GlyphSource for SdFile<'a, D, T>
+where
+    D: sdmmc::BlockDevice,
+    D::Error: core::fmt::Debug,
+    T: sdmmc::TimeSource,
+{
+    type Error = sdmmc::Error<D::Error>;
+
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.file
+            .seek_from_start(offset)
+            .map_err(|_| sdmmc::Error::EndOfFile)?;
+        self.controller.read(self.volume, &mut self.file, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::format::NO_REPLACEMENT;
+
+    /// A blob counting the reads
+    struct Counted<'a> {
+        data: &'a [u8],
+        reads: usize,
+    }
+
+    impl GlyphSource for Counted<'_> {
+        type Error = Infallible;
+
+        fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.reads += 1;
+            self.data.read_at(offset, buf)
+        }
+    }
+
+    /// Writes a 1bpp font of `(codepoint, width)` glyphs, each bitmap filled
+    /// with the low byte of its codepoint, returns its length
+    fn blob(out: &mut [u8], height: u8, glyphs: &[(u32, u8)]) -> usize {
+        let header = Header {
+            count: glyphs.len() as u32,
+            height,
+            bpp: 1,
+            baseline: height,
+            max_width: glyphs.iter().map(|g| g.1).max().unwrap_or(0),
+            replacement: NO_REPLACEMENT,
+        };
+        header.write(out);
+        let mut offset = 0;
+        for (i, &(codepoint, width)) in glyphs.iter().enumerate() {
+            let record = Record {
+                codepoint,
+                width,
+                offset,
+            };
+            record.write(&mut out[HEADER_LEN + i * RECORD_LEN..]);
+            let start = header.bitmaps_offset().unwrap() + offset as usize;
+            let len = header.bitmap_len(width);
+            out[start..start + len].fill(codepoint as u8);
+            offset += len as u32;
+        }
+        header.bitmaps_offset().unwrap() + offset as usize
+    }
+
+    /// `count` glyphs 8 wide and 2 high, at even codepoints from 0x100
+    fn evens(out: &mut [u8], count: u32) -> usize {
+        let mut glyphs = [(0, 8); 100];
+        for (i, glyph) in glyphs.iter_mut().enumerate() {
+            glyph.0 = 0x100 + 2 * i as u32;
+        }
+        blob(out, 2, &glyphs[..count as usize])
+    }
+
+    fn bitmap<S: GlyphSource>(font: &mut StreamFont<S>, codepoint: u32) -> Option<[u8; 2]>
+    where
+        S::Error: core::fmt::Debug,
+    {
+        let c = core::char::from_u32(codepoint).unwrap();
+        let glyph = font.glyph(c).unwrap()?;
+        assert_eq!((glyph.width, glyph.height), (8, 2));
+        Some([glyph.bitmap[0], glyph.bitmap[1]])
+    }
+
+    #[test]
+    fn finds_glyphs() {
+        let mut data = [0; 2048];
+        for &count in &[1, 3, 31, 32, 33, 100] {
+            let len = evens(&mut data, count);
+            let mut font = StreamFont::new(&data[..len]).unwrap();
+            for i in 0..count {
+                let c = 0x100 + 2 * i;
+                assert_eq!(
+                    bitmap(&mut font, c),
+                    Some([c as u8; 2]),
+                    "{} of {}",
+                    c,
+                    count
+                );
+                assert_eq!(bitmap(&mut font, c + 1), None, "{} of {}", c + 1, count);
+            }
+            assert_eq!(bitmap(&mut font, 0xff), None);
+            assert_eq!(bitmap(&mut font, 0x100 + 2 * count), None);
+            assert_eq!(bitmap(&mut font, 0xffff), None);
+        }
+    }
+
+    #[test]
+    fn narrows_down_to_a_bucket() {
+        let mut data = [0; 2048];
+        let len = evens(&mut data, 100);
+        let mut font = StreamFont::new(Counted {
+            data: &data[..len],
+            reads: 0,
+        })
+        .unwrap();
+        // the header and one record per bucket
+        assert_eq!(font.source.reads, 1 + INDEX_LEN);
+        for i in 0..100 {
+            font.source.reads = 0;
+            font.clear_cache();
+            assert!(font
+                .glyph(core::char::from_u32(0x100 + 2 * i).unwrap())
+                .unwrap()
+                .is_some());
+            // fewer records than buckets: the one record, and the bitmap
+            assert_eq!(font.source.reads, 2);
+        }
+    }
+
+    /// Whether looking glyph `i` of `evens` up hit the cache
+    fn hit(font: &mut StreamFont<&[u8]>, i: u32) -> bool {
+        let (hits, _) = font.stats();
+        bitmap(font, 0x100 + 2 * i);
+        font.stats().0 > hits
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut data = [0; 2048];
+        let len = evens(&mut data, 100);
+        let mut font = StreamFont::new(&data[..len]).unwrap();
+        for i in 0..CACHE_SLOTS as u32 {
+            assert!(!hit(&mut font, i));
+        }
+        // 0 is used again, so 1 and 2 go first
+        assert!(hit(&mut font, 0));
+        assert!(!hit(&mut font, 50));
+        assert!(!hit(&mut font, 51));
+        assert!(hit(&mut font, 0));
+        assert!(!hit(&mut font, 1));
+        assert!(!hit(&mut font, 2));
+        // then the oldest of the rest, 3 and 4
+        assert!(hit(&mut font, 5));
+        assert!(hit(&mut font, 50));
+        assert!(!hit(&mut font, 3));
+        assert!(hit(&mut font, 50));
+        assert!(hit(&mut font, 51));
+    }
+
+    #[test]
+    fn caches_missing_glyphs() {
+        let mut data = [0; 2048];
+        let len = evens(&mut data, 100);
+        let mut font = StreamFont::new(Counted {
+            data: &data[..len],
+            reads: 0,
+        })
+        .unwrap();
+        font.source.reads = 0;
+        assert!(font.glyph('\u{101}').unwrap().is_none());
+        assert!(font.source.reads > 0);
+        font.source.reads = 0;
+        assert!(font.glyph('\u{101}').unwrap().is_none());
+        assert_eq!(font.source.reads, 0);
+        assert_eq!(font.stats(), (1, 1));
+
+        // no replacement, so half the height
+        assert_eq!(font.char_width('\u{101}').unwrap(), 1);
+        assert_eq!(font.source.reads, 0);
+    }
+
+    #[test]
+    fn rejects_large_glyphs() {
+        let mut data = [0; 2048];
+        // 9 bytes by 16 rows
+        let len = blob(&mut data, 16, &[('W' as u32, 72), ('a' as u32, 8)]);
+        let mut font = StreamFont::new(&data[..len]).unwrap();
+        assert!(matches!(font.glyph('W'), Err(StreamError::TooLarge)));
+        // the slot it was read into stays empty
+        assert!(font.glyph('a').unwrap().is_some());
+        assert!(matches!(font.glyph('W'), Err(StreamError::TooLarge)));
+        assert_eq!(font.stats(), (0, 3));
+    }
+
+    #[test]
+    fn reports_truncated_blobs() {
+        let mut data = [0; 2048];
+        let len = evens(&mut data, 100);
+        for &cut in &[0, HEADER_LEN - 1, HEADER_LEN + 8 * 50] {
+            assert!(matches!(
+                StreamFont::new(&data[..cut]),
+                Err(StreamError::Font(Error::Truncated))
+            ));
+        }
+        // records complete, the last bitmap cut short
+        let mut font = StreamFont::new(&data[..len - 1]).unwrap();
+        assert!(font.glyph('\u{100}').unwrap().is_some());
+        assert!(matches!(
+            font.glyph(core::char::from_u32(0x100 + 2 * 99).unwrap()),
+            Err(StreamError::Font(Error::Truncated))
+        ));
+
+        // records past the 4 GiB a source can address
+        let mut huge = [0; HEADER_LEN];
+        Header {
+            count: u32::MAX,
+            ..*StreamFont::new(&data[..len]).unwrap().header()
+        }
+        .write(&mut huge);
+        assert!(matches!(
+            StreamFont::new(&huge[..]),
+            Err(StreamError::Font(Error::Truncated))
+        ));
+
+        data[0] = b'X';
+        assert!(matches!(
+            StreamFont::new(&data[..len]),
+            Err(StreamError::Font(Error::Magic))
+        ));
+    }
+}
@@ -9,8 +9,17 @@ use gd32vf103xx_hal::gpio::{Alternate, Floating, Input, Output, PushPull};
 use gd32vf103xx_hal::pac::SPI0;
 use gd32vf103xx_hal::rcu::Rcu;
 use gd32vf103xx_hal::spi::{Spi, MODE_0};
-use gd32vf103xx_hal::time::U32Ext;
-use st7735_lcd::{Orientation, ST7735};
+use st7735_lcd::ST7735;
+
+pub mod config;
+pub mod console;
+pub mod dma;
+pub mod framebuffer;
+pub mod raw;
+
+use self::config::{ColorOrder, LcdConfig};
+use self::framebuffer::Rect;
+use self::raw::RawLcd;
 
 /// Sets up all the needed GPIO pins for the LCD
 ///
@@ -44,6 +53,23 @@ type SpiType = Spi<SPI0, (SckPin, MisoPin, MosiPin)>;
 /// On board LCD 160x80
 pub type Lcd = ST7735<SpiType, DcPin, RstPin>;
 
+/// LCD errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Size and offset do not fit in the ST7735 frame memory
+    InvalidSize,
+    /// Panel reset or init sequence failed
+    Init,
+    /// Setting the orientation failed
+    Orientation,
+    /// SPI or DC pin error while sending
+    Transfer,
+    /// Pixel data does not match the window
+    Length,
+    /// Window outside the screen, or with its corners swapped
+    OutOfBounds,
+}
+
 /// Pins consumed by LCD driver
 pub struct LcdPins {
     /// SPI0 miso, unused
@@ -61,25 +87,86 @@ pub struct LcdPins {
 }
 
 /// Constructs LCD driver from the required components
-pub fn configure(spi: SPI0, pins: LcdPins, afio: &mut Afio, rcu: &mut Rcu) -> Lcd {
-    let (width, height) = (160, 80);
+///
+/// ```
+/// let lcd = lcd::configure(dp.SPI0, lcd_pins, LcdConfig::default(), &mut afio, &mut rcu)?;
+/// ```
+pub fn configure(
+    spi: SPI0,
+    pins: LcdPins,
+    config: LcdConfig,
+    afio: &mut Afio,
+    rcu: &mut Rcu,
+) -> Result<Lcd, Error> {
+    if !config.is_valid() {
+        return Err(Error::InvalidSize);
+    }
+    let (width, height) = config.screen_size();
+    let (dx, dy) = config.screen_offset();
+
     let spi0 = Spi::spi0(
         spi,
         (pins.sck, pins.miso, pins.mosi),
         afio,
         MODE_0,
-        16.mhz(),
+        config.frequency,
         rcu,
     );
 
     let mut cs = pins.cs;
-    cs.set_low().unwrap();
+    // infallible
+    let _ = cs.set_low();
 
-    let mut lcd = ST7735::new(spi0, pins.dc, pins.rst, false, true, width, height);
+    let rgb = config.color_order == ColorOrder::Rgb;
+    let mut lcd = ST7735::new(
+        spi0,
+        pins.dc,
+        pins.rst,
+        rgb,
+        config.inverted,
+        width as u32,
+        height as u32,
+    );
     let mut delay = McycleDelay::new(&rcu.clocks);
-    lcd.init(&mut delay).unwrap();
-    lcd.set_orientation(&Orientation::Landscape).unwrap();
-    lcd.set_offset(1, 26);
+    lcd.init(&mut delay).map_err(|_| Error::Init)?;
+    lcd.set_orientation(&config.orientation.into())
+        .map_err(|_| Error::Orientation)?;
+    lcd.set_offset(dx, dy);
+    raw::set_geometry(raw::Geometry {
+        width,
+        height,
+        dx,
+        dy,
+    });
+
+    Ok(lcd)
+}
+
+/// Checks that `rect` is on a `width` x `height` screen, and that `len` bytes
+/// of RGB565 fill it
+pub(crate) fn check_window(rect: Rect, width: u16, height: u16, len: usize) -> Result<(), Error> {
+    // Rect::area underflows on swapped corners
+    if rect.x0 > rect.x1 || rect.y0 > rect.y1 || rect.x1 >= width || rect.y1 >= height {
+        return Err(Error::OutOfBounds);
+    }
+    if len != rect.area() as usize * 2 {
+        return Err(Error::Length);
+    }
+    Ok(())
+}
 
-    lcd
+/// Copies big endian RGB565 `data`, like an `ImageRaw` buffer, to `rect`.
+///
+/// The address window is set once and the bytes are sent as they are, several
+/// times faster than drawing an `Image`. See `dma::LcdDma::write_window` to
+/// send them without the CPU.
+///
+/// ```ignore
+/// // 106x80 frame, centered
+/// lcd::blit(&mut lcd, Rect::new(27, 0, 132, 79), &frame)?;
+/// ```
+pub fn blit(_lcd: &mut Lcd, rect: Rect, data: &[u8]) -> Result<(), Error> {
+    // the driver is borrowed, nothing else is using SPI0
+    let mut raw = unsafe { RawLcd::steal() }.ok_or(Error::Init)?;
+    raw.blit(rect, data)
 }
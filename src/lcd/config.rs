@@ -0,0 +1,201 @@
+//! LCD configuration
+//!
+//! ```ignore
+//! // panel mounted upside down
+//! let config = LcdConfig::default().orientation(Orientation::LandscapeSwapped);
+//! let mut lcd = lcd::configure(dp.SPI0, lcd_pins, config, &mut afio, &mut rcu)?;
+//! ```
+
+use gd32vf103xx_hal::time::{Hertz, U32Ext};
+
+/// ST7735 frame memory, in portrait
+pub const MEMORY_WIDTH: u16 = 132;
+pub const MEMORY_HEIGHT: u16 = 162;
+
+/// Scan direction of the panel, `st7735_lcd::Orientation` without the
+/// `Clone` that `LcdConfig` needs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+    PortraitSwapped,
+    LandscapeSwapped,
+}
+
+impl From<Orientation> for st7735_lcd::Orientation {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Portrait => st7735_lcd::Orientation::Portrait,
+            Orientation::Landscape => st7735_lcd::Orientation::Landscape,
+            Orientation::PortraitSwapped => st7735_lcd::Orientation::PortraitSwapped,
+            Orientation::LandscapeSwapped => st7735_lcd::Orientation::LandscapeSwapped,
+        }
+    }
+}
+
+/// Sub-pixel order of the panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorOrder {
+    Rgb,
+    Bgr,
+}
+
+/// Configuration for `lcd::configure`, defaults to the Longan Nano panel
+#[derive(Clone, Copy)]
+pub struct LcdConfig {
+    pub(crate) orientation: Orientation,
+    pub(crate) frequency: Hertz,
+    pub(crate) inverted: bool,
+    pub(crate) color_order: ColorOrder,
+    /// (width, height) in landscape, swapped for portrait
+    pub(crate) size: (u16, u16),
+    /// (x, y) in landscape, swapped for portrait
+    pub(crate) offset: (u16, u16),
+}
+
+impl Default for LcdConfig {
+    fn default() -> Self {
+        LcdConfig {
+            orientation: Orientation::Landscape,
+            frequency: 16.mhz().into(),
+            inverted: true,
+            color_order: ColorOrder::Bgr,
+            size: (160, 80),
+            offset: (1, 26),
+        }
+    }
+}
+
+impl LcdConfig {
+    /// change the orientation, the offsets follow
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    /// change the SPI clock, the ST7735 is specified up to 15MHz but most
+    /// panels run fine faster
+    pub fn frequency<F: Into<Hertz>>(mut self, frequency: F) -> Self {
+        self.frequency = frequency.into();
+        self
+    }
+
+    /// change color inversion
+    pub fn inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    /// change the sub-pixel order
+    pub fn color_order(mut self, color_order: ColorOrder) -> Self {
+        self.color_order = color_order;
+        self
+    }
+
+    /// change the visible size, given in landscape
+    pub fn size(mut self, width: u16, height: u16) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    /// change the position of the visible area in frame memory, given in
+    /// landscape
+    pub fn offset(mut self, x: u16, y: u16) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    pub(crate) fn is_portrait(&self) -> bool {
+        matches!(
+            self.orientation,
+            Orientation::Portrait | Orientation::PortraitSwapped
+        )
+    }
+
+    /// (width, height) in the configured orientation
+    pub fn screen_size(&self) -> (u16, u16) {
+        let (w, h) = self.size;
+        if self.is_portrait() {
+            (h, w)
+        } else {
+            (w, h)
+        }
+    }
+
+    /// (x, y) offset in the configured orientation. The swapped orientations
+    /// scan frame memory from the other corner, so the offset is mirrored.
+    pub fn screen_offset(&self) -> (u16, u16) {
+        let (w, h) = self.size;
+        let (x, y) = match self.orientation {
+            Orientation::LandscapeSwapped | Orientation::PortraitSwapped => (
+                MEMORY_HEIGHT.saturating_sub(w + self.offset.0),
+                MEMORY_WIDTH.saturating_sub(h + self.offset.1),
+            ),
+            _ => self.offset,
+        };
+        if self.is_portrait() {
+            (y, x)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Whether the visible area fits in frame memory
+    pub(crate) fn is_valid(&self) -> bool {
+        let (w, h) = self.size;
+        let (x, y) = self.offset;
+        w > 0 && h > 0 && x + w <= MEMORY_HEIGHT && y + h <= MEMORY_WIDTH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Screen size and offset of `config` in `orientation`
+    fn screen(config: LcdConfig, orientation: Orientation) -> ((u16, u16), (u16, u16)) {
+        let config = config.orientation(orientation);
+        assert!(config.is_valid());
+        (config.screen_size(), config.screen_offset())
+    }
+
+    #[test]
+    fn follows_orientation() {
+        // 150x80 at (2, 10) in landscape: 10 px right of it, 42 px below
+        let config = || LcdConfig::default().size(150, 80).offset(2, 10);
+        assert_eq!(
+            screen(config(), Orientation::Landscape),
+            ((150, 80), (2, 10))
+        );
+        assert_eq!(
+            screen(config(), Orientation::LandscapeSwapped),
+            ((150, 80), (10, 42))
+        );
+        assert_eq!(
+            screen(config(), Orientation::Portrait),
+            ((80, 150), (10, 2))
+        );
+        assert_eq!(
+            screen(config(), Orientation::PortraitSwapped),
+            ((80, 150), (42, 10))
+        );
+    }
+
+    #[test]
+    fn centered_offset() {
+        // the Longan Nano panel is centered in frame memory
+        let default = LcdConfig::default;
+        assert_eq!(screen(default(), Orientation::Landscape).1, (1, 26));
+        assert_eq!(screen(default(), Orientation::LandscapeSwapped).1, (1, 26));
+        assert_eq!(screen(default(), Orientation::PortraitSwapped).1, (26, 1));
+    }
+
+    #[test]
+    fn checks_size() {
+        assert!(LcdConfig::default().is_valid());
+        assert!(LcdConfig::default().size(162, 132).offset(0, 0).is_valid());
+        assert!(!LcdConfig::default().size(162, 80).is_valid());
+        assert!(!LcdConfig::default().size(160, 80).offset(1, 53).is_valid());
+        assert!(!LcdConfig::default().size(0, 80).is_valid());
+    }
+}
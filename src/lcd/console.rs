@@ -0,0 +1,387 @@
+//! Scrolling text console on the LCD
+//!
+//! `LcdConsole` is a `fmt::Write` terminal on any `DrawTarget<Rgb565>`, with
+//! the 6x8 font: 26 columns by 10 rows on the 160x80 panel.
+//!
+//! ```ignore
+//! let mut console = LcdConsole::new(lcd);
+//! writeln!(console, "\x1b[32mOK\x1b[0m {} samples", n)?;
+//! ```
+//!
+//! It understands `\r`, `\n` (which also returns the carriage), `\t`, `\x08`
+//! and the escape sequences:
+//!
+//! - `ESC[...m`: SGR colours, 0 reset, 1/22 bright, 30-37/90-97 foreground,
+//!   40-47/100-107 background, 39/49 default
+//! - `ESC[K`, `ESC[1K`, `ESC[2K`: clear to the end, start or whole line
+//! - `ESC[J`, `ESC[1J`, `ESC[2J`: clear to the end, start or whole screen
+//! - `ESC[row;colH`: move the cursor, 1-based
+//!
+//! Lines wrap at the right edge and the screen scrolls at the bottom. The
+//! ST7735 vertical scroll moves along its 162 line memory axis, which is
+//! horizontal in landscape, so scrolling redraws the cells that changed.
+//!
+//! To mirror `sprintln!` onto the screen, hand a `'static` console to
+//! `stdout::set_mirror`:
+//!
+//! ```ignore
+//! static mut CONSOLE: Option<LcdConsole<Lcd>> = None;
+//!
+//! unsafe {
+//!     CONSOLE = Some(LcdConsole::new(lcd));
+//!     stdout::set_mirror(CONSOLE.as_mut().map(|c| c as &mut dyn fmt::Write));
+//! }
+//! sprintln!("\x1b[31mhello\x1b[0m");
+//! ```
+
+use core::fmt;
+
+use embedded_graphics::fonts::{Font6x8, Text};
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text_style;
+
+/// Cell size of `Font6x8`
+const CELL_WIDTH: u32 = 6;
+const CELL_HEIGHT: u32 = 8;
+/// Largest grid, 160x80 in portrait is 13x20
+pub const MAX_COLS: usize = 32;
+pub const MAX_ROWS: usize = 20;
+/// Tab stops
+const TAB: usize = 8;
+/// Numeric parameters kept from a CSI sequence
+const MAX_PARAMS: usize = 4;
+
+/// ANSI colours 0-7, then bright 8-15
+pub const PALETTE: [Rgb565; 16] = [
+    Rgb565::new(0, 0, 0),
+    Rgb565::new(21, 0, 0),
+    Rgb565::new(0, 42, 0),
+    Rgb565::new(21, 21, 0),
+    Rgb565::new(0, 0, 21),
+    Rgb565::new(21, 0, 21),
+    Rgb565::new(0, 42, 21),
+    Rgb565::new(21, 42, 21),
+    Rgb565::new(10, 21, 10),
+    Rgb565::new(31, 21, 10),
+    Rgb565::new(10, 63, 10),
+    Rgb565::new(31, 63, 10),
+    Rgb565::new(10, 21, 31),
+    Rgb565::new(31, 21, 31),
+    Rgb565::new(10, 63, 31),
+    Rgb565::new(31, 63, 31),
+];
+
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+
+/// Character and colours of one cell
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: u8,
+    /// Foreground index in the low nibble, background in the high one
+    attr: u8,
+}
+
+impl Cell {
+    const fn blank(attr: u8) -> Self {
+        Cell { ch: b' ', attr }
+    }
+}
+
+/// Escape sequence parser state
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// Text console on a display
+pub struct LcdConsole<D> {
+    display: D,
+    cols: usize,
+    rows: usize,
+    cells: [[Cell; MAX_COLS]; MAX_ROWS],
+    col: usize,
+    row: usize,
+    fg: u8,
+    bg: u8,
+    bright: bool,
+    state: State,
+    params: [u16; MAX_PARAMS],
+    param_len: usize,
+}
+
+impl<D: DrawTarget<Rgb565>> LcdConsole<D> {
+    /// Takes over the display and clears it. A display smaller than a cell
+    /// still gets one, clipped.
+    pub fn new(display: D) -> Self {
+        let size = display.size();
+        let cols = ((size.width / CELL_WIDTH) as usize).clamp(1, MAX_COLS);
+        let rows = ((size.height / CELL_HEIGHT) as usize).clamp(1, MAX_ROWS);
+        let mut console = LcdConsole {
+            display,
+            cols,
+            rows,
+            cells: [[Cell::blank(DEFAULT_BG << 4 | DEFAULT_FG); MAX_COLS]; MAX_ROWS],
+            col: 0,
+            row: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bright: false,
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            param_len: 0,
+        };
+        let _ = console.clear();
+        console
+    }
+
+    /// Gives back the display
+    pub fn release(self) -> D {
+        self.display
+    }
+
+    /// The display, for drawing next to the text
+    pub fn display(&mut self) -> &mut D {
+        &mut self.display
+    }
+
+    /// (columns, rows)
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// (column, row) of the cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.col.min(self.cols - 1), self.row)
+    }
+
+    /// Clears the screen with the current background, and homes the cursor
+    pub fn clear(&mut self) -> Result<(), D::Error> {
+        let blank = Cell::blank(self.attr());
+        for row in self.cells.iter_mut() {
+            row.iter_mut().for_each(|cell| *cell = blank);
+        }
+        self.col = 0;
+        self.row = 0;
+        self.display.clear(PALETTE[self.bg as usize])
+    }
+
+    fn attr(&self) -> u8 {
+        let fg = if self.bright { self.fg | 8 } else { self.fg };
+        self.bg << 4 | fg
+    }
+
+    fn draw_cell(&mut self, col: usize, row: usize) -> Result<(), D::Error> {
+        let cell = self.cells[row][col];
+        let mut buf = [0u8; 1];
+        let s = match cell.ch {
+            0x20..=0x7e => {
+                buf[0] = cell.ch;
+                // printable ASCII, always valid
+                core::str::from_utf8(&buf).unwrap_or(" ")
+            }
+            _ => "?",
+        };
+        let style = text_style!(
+            font = Font6x8,
+            text_color = PALETTE[(cell.attr & 0xf) as usize],
+            background_color = PALETTE[(cell.attr >> 4) as usize]
+        );
+        let at = Point::new(
+            (col as u32 * CELL_WIDTH) as i32,
+            (row as u32 * CELL_HEIGHT) as i32,
+        );
+        Text::new(s, at).into_styled(style).draw(&mut self.display)
+    }
+
+    fn set_cell(&mut self, col: usize, row: usize, cell: Cell) -> Result<(), D::Error> {
+        if self.cells[row][col] == cell {
+            return Ok(());
+        }
+        self.cells[row][col] = cell;
+        self.draw_cell(col, row)
+    }
+
+    /// Blanks `cols` of `row`
+    fn clear_cells(&mut self, row: usize, cols: core::ops::Range<usize>) -> Result<(), D::Error> {
+        let blank = Cell::blank(self.attr());
+        for col in cols {
+            self.set_cell(col, row, blank)?;
+        }
+        Ok(())
+    }
+
+    /// Moves everything up a line, redrawing the cells that change
+    fn scroll(&mut self) -> Result<(), D::Error> {
+        for row in 0..self.rows - 1 {
+            for col in 0..self.cols {
+                let cell = self.cells[row + 1][col];
+                self.set_cell(col, row, cell)?;
+            }
+        }
+        self.clear_cells(self.rows - 1, 0..self.cols)
+    }
+
+    fn line_feed(&mut self) -> Result<(), D::Error> {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            Ok(())
+        } else {
+            self.scroll()
+        }
+    }
+
+    fn put(&mut self, ch: u8) -> Result<(), D::Error> {
+        // wrap pending from the previous character
+        if self.col >= self.cols {
+            self.line_feed()?;
+        }
+        let cell = Cell {
+            ch,
+            attr: self.attr(),
+        };
+        self.set_cell(self.col, self.row, cell)?;
+        self.col += 1;
+        Ok(())
+    }
+
+    fn control(&mut self, ch: char) -> Result<(), D::Error> {
+        match ch {
+            '\n' => self.line_feed()?,
+            '\r' => self.col = 0,
+            '\x08' => self.col = self.col.min(self.cols).saturating_sub(1),
+            '\t' => {
+                let next = (self.col / TAB + 1) * TAB;
+                while self.col < next.min(self.cols) {
+                    self.put(b' ')?;
+                }
+            }
+            '\x1b' => {
+                self.state = State::Escape;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn param(&self, i: usize, default: u16) -> u16 {
+        match self.params[i] {
+            0 => default,
+            n => n,
+        }
+    }
+
+    fn sgr(&mut self) {
+        for i in 0..self.param_len.clamp(1, MAX_PARAMS) {
+            match self.params[i] {
+                0 => {
+                    self.fg = DEFAULT_FG;
+                    self.bg = DEFAULT_BG;
+                    self.bright = false;
+                }
+                1 => self.bright = true,
+                22 => self.bright = false,
+                n @ 30..=37 => self.fg = (n - 30) as u8,
+                39 => self.fg = DEFAULT_FG,
+                n @ 40..=47 => self.bg = (n - 40) as u8,
+                49 => self.bg = DEFAULT_BG,
+                n @ 90..=97 => self.fg = (n - 90) as u8 | 8,
+                n @ 100..=107 => self.bg = (n - 100) as u8 | 8,
+                _ => {}
+            }
+        }
+    }
+
+    fn csi(&mut self, final_byte: char) -> Result<(), D::Error> {
+        let (col, row) = self.cursor();
+        match final_byte {
+            'm' => self.sgr(),
+            'K' => match self.params[0] {
+                0 => self.clear_cells(row, col..self.cols)?,
+                1 => self.clear_cells(row, 0..col + 1)?,
+                2 => self.clear_cells(row, 0..self.cols)?,
+                _ => {}
+            },
+            'J' => match self.params[0] {
+                0 => {
+                    self.clear_cells(row, col..self.cols)?;
+                    for r in row + 1..self.rows {
+                        self.clear_cells(r, 0..self.cols)?;
+                    }
+                }
+                1 => {
+                    for r in 0..row {
+                        self.clear_cells(r, 0..self.cols)?;
+                    }
+                    self.clear_cells(row, 0..col + 1)?;
+                }
+                2 => {
+                    for r in 0..self.rows {
+                        self.clear_cells(r, 0..self.cols)?;
+                    }
+                }
+                _ => {}
+            },
+            'H' | 'f' => {
+                self.row = (self.param(0, 1) as usize - 1).min(self.rows - 1);
+                self.col = (self.param(1, 1) as usize - 1).min(self.cols - 1);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn feed(&mut self, ch: char) -> Result<(), D::Error> {
+        match self.state {
+            State::Ground => match ch {
+                ' '..='~' => self.put(ch as u8)?,
+                '\0'..='\x1f' | '\x7f' => self.control(ch)?,
+                _ => self.put(b'?')?,
+            },
+            State::Escape => {
+                if ch == '[' {
+                    self.params = [0; MAX_PARAMS];
+                    self.param_len = 0;
+                    self.state = State::Csi;
+                } else {
+                    // unsupported escape, dropped
+                    self.state = State::Ground;
+                }
+            }
+            State::Csi => match ch {
+                '0'..='9' => {
+                    let i = self.param_len.max(1) - 1;
+                    self.param_len = self.param_len.max(1);
+                    if i < MAX_PARAMS {
+                        let digit = ch as u16 - '0' as u16;
+                        self.params[i] = self.params[i].saturating_mul(10).saturating_add(digit);
+                    }
+                }
+                ';' => {
+                    // an empty first parameter still counts
+                    self.param_len = self.param_len.max(1) + 1;
+                }
+                '\x40'..='\x7e' => {
+                    self.state = State::Ground;
+                    self.csi(ch)?;
+                }
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+}
+
+impl<D: DrawTarget<Rgb565>> fmt::Write for LcdConsole<D> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.chars() {
+            self.feed(ch).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
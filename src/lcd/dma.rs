@@ -0,0 +1,397 @@
+//! DMA transfers of pixel data to the LCD
+//!
+//! Pixels go out on DMA0 channel 2, the SPI0 TX request, while the CPU does
+//! something else. Blocking:
+//!
+//! ```ignore
+//! let raw = unsafe { RawLcd::steal() }.unwrap();
+//! let mut dma = LcdDma::new(dp.DMA0, raw, &mut rcu);
+//! dma.write_window(Rect::new(0, 0, 105, 79), frame)?;
+//! dma.flush(&mut framebuffer);
+//! ```
+//!
+//! Or started, then polled with `nb` or completed by the interrupt:
+//!
+//! ```ignore
+//! dma::set_callback(Some(frame_sent));
+//! dma.listen();
+//! ECLIC::setup(Interrupt::DMA0_CHANNEL2, TriggerType::Level, Level::L1, Priority::P1);
+//! unsafe { ECLIC::unmask(Interrupt::DMA0_CHANNEL2) };
+//!
+//! dma.start_window(rect, &FRAMES[0])?;
+//! decode_next_frame(&mut FRAMES[1]);
+//! block!(dma.wait()).unwrap();
+//!
+//! #[no_mangle]
+//! fn DMA0_CHANNEL2() {
+//!     lcd::dma::handle_interrupt();
+//! }
+//! ```
+//!
+//! The `Lcd` driver shares SPI0, don't use it while a transfer runs.
+
+use core::convert::Infallible;
+
+use riscv::interrupt;
+
+use super::framebuffer::{Format, Framebuffer, Rect};
+use super::raw::RawLcd;
+use super::{check_window, Error};
+use crate::hal::rcu::Rcu;
+use crate::pac::{dma0, spi0, DMA0, RCU, SPI0};
+
+/// Longest single transfer, CHxCNT is 16 bits
+pub const MAX_TRANSFER: usize = 0xffff;
+/// Pixels per line buffer in `flush`, longer rows are sent in pieces
+const LINE_PIXELS: u16 = 256;
+
+/// RCU AHBEN bit of DMA0
+const RCU_AHBEN_DMA0EN: u32 = 1 << 0;
+/// CH2CTL bits: enable, full transfer interrupt, memory to peripheral,
+/// memory increment, high priority
+const CTL_CHEN: u32 = 1 << 0;
+const CTL_FTFIE: u32 = 1 << 1;
+const CTL_DIR: u32 = 1 << 4;
+const CTL_MNAGA: u32 = 1 << 7;
+const CTL_PRIO_HIGH: u32 = 0b10 << 12;
+/// INTF/INTC bits of channel 2
+const CH2_FLAGS: u32 = 0xf << 8;
+const CH2_FTFIF: u32 = 1 << 9;
+/// SPI bits
+const SPI_CTL1_DMATEN: u16 = 1 << 1;
+const SPI_STAT_TBE: u16 = 1 << 1;
+const SPI_STAT_TRANS: u16 = 1 << 7;
+
+/// Called from `handle_interrupt` when a transfer completes
+static mut CALLBACK: Option<fn()> = None;
+
+fn dma() -> &'static dma0::RegisterBlock {
+    unsafe { &*DMA0::ptr() }
+}
+
+fn spi() -> &'static spi0::RegisterBlock {
+    unsafe { &*SPI0::ptr() }
+}
+
+/// Sets the function called when a transfer completes, in interrupt context
+pub fn set_callback(callback: Option<fn()>) {
+    interrupt::free(|_| unsafe { CALLBACK = callback })
+}
+
+/// Handler of the DMA0 channel 2 interrupt
+pub fn handle_interrupt() {
+    if dma().intf.read().bits() & CH2_FTFIF == 0 {
+        return;
+    }
+    finish();
+    if let Some(callback) = interrupt::free(|_| unsafe { CALLBACK }) {
+        callback();
+    }
+}
+
+/// Starts sending `data`, at most `MAX_TRANSFER` bytes
+fn start(data: &[u8], listen: bool) {
+    let dma = dma();
+    unsafe {
+        dma.ch2ctl.write(|w| w.bits(0));
+        dma.intc.write(|w| w.bits(CH2_FLAGS));
+        dma.ch2paddr
+            .write(|w| w.bits(&spi().data as *const _ as u32));
+        dma.ch2maddr.write(|w| w.bits(data.as_ptr() as u32));
+        dma.ch2cnt.write(|w| w.bits(data.len() as u32));
+        let ie = if listen { CTL_FTFIE } else { 0 };
+        dma.ch2ctl
+            .write(|w| w.bits(CTL_DIR | CTL_MNAGA | CTL_PRIO_HIGH | ie | CTL_CHEN));
+        spi().ctl1.modify(|r, w| w.bits(r.bits() | SPI_CTL1_DMATEN));
+    }
+}
+
+/// Stops the channel once the last byte is out
+fn finish() {
+    let (dma, spi) = (dma(), spi());
+    unsafe {
+        dma.ch2ctl.modify(|r, w| w.bits(r.bits() & !CTL_CHEN));
+        dma.intc.write(|w| w.bits(CH2_FLAGS));
+    }
+    while spi.stat.read().bits() & SPI_STAT_TBE == 0 {}
+    while spi.stat.read().bits() & SPI_STAT_TRANS != 0 {}
+    unsafe {
+        spi.ctl1.modify(|r, w| w.bits(r.bits() & !SPI_CTL1_DMATEN));
+    }
+    // nothing reads RX during DMA, clear the overrun the HAL would report
+    let _ = spi.data.read().bits();
+    let _ = spi.stat.read().bits();
+}
+
+fn is_running() -> bool {
+    let dma = dma();
+    dma.ch2ctl.read().bits() & CTL_CHEN != 0 && dma.intf.read().bits() & CH2_FTFIF == 0
+}
+
+/// LCD with DMA0
+pub struct LcdDma {
+    _dma: DMA0,
+    lcd: RawLcd,
+    listen: bool,
+}
+
+impl LcdDma {
+    pub fn new(dma: DMA0, lcd: RawLcd, _rcu: &mut Rcu) -> Self {
+        let rcu = unsafe { &*RCU::ptr() };
+        rcu.ahben
+            .modify(|r, w| unsafe { w.bits(r.bits() | RCU_AHBEN_DMA0EN) });
+        LcdDma {
+            _dma: dma,
+            lcd,
+            listen: false,
+        }
+    }
+
+    /// Completes transfers in the interrupt, see `handle_interrupt`
+    pub fn listen(&mut self) {
+        self.listen = true;
+    }
+
+    pub fn unlisten(&mut self) {
+        self.listen = false;
+    }
+
+    /// The LCD, once the running transfer is done
+    pub fn lcd(&mut self) -> &mut RawLcd {
+        self.wait_done();
+        &mut self.lcd
+    }
+
+    pub fn is_busy(&self) -> bool {
+        is_running()
+    }
+
+    /// Completes the running transfer, if any
+    pub fn wait(&mut self) -> nb::Result<(), Infallible> {
+        if is_running() {
+            return Err(nb::Error::WouldBlock);
+        }
+        if dma().ch2ctl.read().bits() & CTL_CHEN != 0 {
+            finish();
+        }
+        Ok(())
+    }
+
+    fn wait_done(&mut self) {
+        while self.wait().is_err() {}
+    }
+
+    fn begin_window(&mut self, rect: Rect, len: usize) -> Result<(), Error> {
+        check_window(rect, self.lcd.width(), self.lcd.height(), len)?;
+        self.wait_done();
+        self.lcd
+            .set_address_window(rect.x0, rect.y0, rect.x1, rect.y1);
+        self.lcd.start_pixels();
+        Ok(())
+    }
+
+    /// Sends big endian RGB565 `data` to `rect`, blocking
+    pub fn write_window(&mut self, rect: Rect, data: &[u8]) -> Result<(), Error> {
+        self.begin_window(rect, data.len())?;
+        for chunk in data.chunks(MAX_TRANSFER) {
+            start(chunk, false);
+            self.wait_done();
+        }
+        Ok(())
+    }
+
+    /// Starts sending big endian RGB565 `data` to `rect`, returns at once.
+    /// Complete it with `wait`, or the interrupt if listening.
+    ///
+    /// The DMA keeps reading `data` after this returns, with nothing to stop
+    /// the caller from dropping or refilling a borrowed buffer meanwhile, so
+    /// it must be `'static`. Send borrowed data with `write_window`, which
+    /// blocks until it is out.
+    pub fn start_window(&mut self, rect: Rect, data: &'static [u8]) -> Result<(), Error> {
+        if data.len() > MAX_TRANSFER {
+            return Err(Error::Length);
+        }
+        self.begin_window(rect, data.len())?;
+        start(data, self.listen);
+        Ok(())
+    }
+
+    /// Flushes the dirty rectangles of `fb`. Rows are converted into one line
+    /// buffer while the other is sent, see `pieces`.
+    pub fn flush(&mut self, fb: &mut Framebuffer) {
+        let mut lines = [[0u8; 2 * LINE_PIXELS as usize]; 2];
+        while let Some(rect) = fb.pop_dirty() {
+            let len = rect.area() as usize * 2;
+            // framebuffer larger than the screen
+            if self.begin_window(rect, len).is_err() {
+                continue;
+            }
+            pieces(rect, fb.width(), fb.format(), |piece| match piece {
+                Piece::Bytes { start: at, len } => {
+                    self.wait_done();
+                    start(&fb.as_bytes()[at..at + len], false);
+                }
+                Piece::Expand { y, x0, x1, line } => {
+                    // the other line may still be going out
+                    let n = fb.expand_row(y, x0, x1, &mut lines[line]);
+                    self.wait_done();
+                    start(&lines[line][..n], false);
+                }
+            });
+            self.wait_done();
+        }
+    }
+}
+
+/// One transfer of `flush`
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Piece {
+    /// `len` bytes of the framebuffer from `start`, sent as they are
+    Bytes { start: usize, len: usize },
+    /// Pixels `x0..=x1` of row `y`, expanded into line buffer `line`
+    Expand {
+        y: u16,
+        x0: u16,
+        x1: u16,
+        line: usize,
+    },
+}
+
+/// Splits `rect` of a framebuffer `width` pixels wide into transfers, in
+/// order. RGB565 is sent straight from the framebuffer, in `MAX_TRANSFER`
+/// chunks of a whole rectangle of full rows or of each row otherwise. Palette
+/// rows are expanded in pieces of `LINE_PIXELS`, into the two line buffers in
+/// turn.
+fn pieces(rect: Rect, width: u16, format: Format, mut send: impl FnMut(Piece)) {
+    if format == Format::Rgb565 {
+        let at = |y: u16| (y as usize * width as usize + rect.x0 as usize) * 2;
+        let mut bytes = |start: usize, len: usize| {
+            let mut sent = 0;
+            while sent < len {
+                let n = (len - sent).min(MAX_TRANSFER);
+                send(Piece::Bytes {
+                    start: start + sent,
+                    len: n,
+                });
+                sent += n;
+            }
+        };
+        if rect.x0 == 0 && rect.x1 == width - 1 {
+            // full width rows are contiguous
+            bytes(at(rect.y0), rect.area() as usize * 2);
+        } else {
+            for y in rect.y0..=rect.y1 {
+                bytes(at(y), rect.width() as usize * 2);
+            }
+        }
+        return;
+    }
+
+    let mut line = 0;
+    for y in rect.y0..=rect.y1 {
+        let mut x0 = rect.x0;
+        loop {
+            let x1 = rect.x1.min(x0.saturating_add(LINE_PIXELS - 1));
+            send(Piece::Expand { y, x0, x1, line });
+            line ^= 1;
+            if x1 == rect.x1 {
+                break;
+            }
+            x0 = x1 + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pieces of `rect`, at most 8
+    fn split(rect: Rect, width: u16, format: Format) -> ([Piece; 8], usize) {
+        let mut out = [Piece::Bytes { start: 0, len: 0 }; 8];
+        let mut n = 0;
+        pieces(rect, width, format, |piece| {
+            out[n] = piece;
+            n += 1;
+        });
+        (out, n)
+    }
+
+    #[test]
+    fn sends_full_rows_at_once() {
+        let (out, n) = split(Rect::new(0, 10, 159, 79), 160, Format::Rgb565);
+        assert_eq!(
+            out[..n],
+            [Piece::Bytes {
+                start: 10 * 320,
+                len: 70 * 320
+            }]
+        );
+
+        // more than one transfer can take
+        let (out, n) = split(Rect::new(0, 0, 199, 199), 200, Format::Rgb565);
+        assert_eq!(
+            out[..n],
+            [
+                Piece::Bytes {
+                    start: 0,
+                    len: MAX_TRANSFER
+                },
+                Piece::Bytes {
+                    start: MAX_TRANSFER,
+                    len: 80_000 - MAX_TRANSFER
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sends_partial_rows_one_by_one() {
+        let (out, n) = split(Rect::new(10, 2, 19, 4), 160, Format::Rgb565);
+        assert_eq!(
+            out[..n],
+            [
+                Piece::Bytes {
+                    start: 2 * 320 + 20,
+                    len: 20
+                },
+                Piece::Bytes {
+                    start: 3 * 320 + 20,
+                    len: 20
+                },
+                Piece::Bytes {
+                    start: 4 * 320 + 20,
+                    len: 20
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_rows_in_turns() {
+        let expand = |y, x0, x1, line| Piece::Expand { y, x0, x1, line };
+        let (out, n) = split(Rect::new(0, 0, 159, 2), 160, Format::Indexed4);
+        assert_eq!(
+            out[..n],
+            [
+                expand(0, 0, 159, 0),
+                expand(1, 0, 159, 1),
+                expand(2, 0, 159, 0)
+            ]
+        );
+
+        // wider than a line buffer
+        let (out, n) = split(Rect::new(5, 7, 299, 8), 300, Format::Indexed8);
+        assert_eq!(
+            out[..n],
+            [
+                expand(7, 5, 260, 0),
+                expand(7, 261, 299, 1),
+                expand(8, 5, 260, 0),
+                expand(8, 261, 299, 1),
+            ]
+        );
+        let (out, n) = split(Rect::new(0, 0, 511, 0), 512, Format::Indexed8);
+        assert_eq!(out[..n], [expand(0, 0, 255, 0), expand(0, 256, 511, 1)]);
+    }
+}
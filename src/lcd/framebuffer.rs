@@ -0,0 +1,642 @@
+//! RAM framebuffer with dirty rectangle tracking
+//!
+//! Drawing goes to RAM, `flush` sends only the changed regions, each with a
+//! single address window, so there is no flicker from clearing and redrawing:
+//!
+//! ```ignore
+//! static mut FB: [u8; Framebuffer::buffer_len(160, 80, Format::Indexed4)] = [0; 6400];
+//!
+//! let mut fb = Framebuffer::new(unsafe { &mut FB }, 160, 80, Format::Indexed4).unwrap();
+//! fb.set_palette(1, Rgb565::GREEN);
+//! fb.clear(Rgb565::BLACK)?;
+//! Text::new("hello", Point::new(0, 0)).into_styled(style).draw(&mut fb)?;
+//! fb.flush(&mut lcd)?;
+//! ```
+//!
+//! 16bpp takes 25.6KB for the full screen, the palette modes keep 8 or 4 bits
+//! per pixel and expand them to RGB565 while flushing. In palette modes drawn
+//! colors map to the nearest palette entry.
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Size;
+use embedded_graphics::pixelcolor::raw::{RawData, RawU16};
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::DrawTarget;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::v2::OutputPin;
+use st7735_lcd::ST7735;
+
+use super::Error;
+
+/// Max number of separate dirty rectangles, more get merged
+const MAX_DIRTY: usize = 4;
+/// Wasted pixels accepted when merging two dirty rectangles
+const MERGE_SLACK: u32 = 64;
+
+/// Pixel storage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// 16 bits per pixel, no palette
+    Rgb565,
+    /// 8 bits per pixel, 256 color palette
+    Indexed8,
+    /// 4 bits per pixel, 16 color palette, left pixel in the high nibble
+    Indexed4,
+}
+
+impl Format {
+    const fn row_len(self, width: u16) -> usize {
+        match self {
+            Format::Rgb565 => width as usize * 2,
+            Format::Indexed8 => width as usize,
+            Format::Indexed4 => (width as usize + 1) / 2,
+        }
+    }
+
+    const fn palette_len(self) -> usize {
+        match self {
+            Format::Rgb565 => 0,
+            Format::Indexed8 => 256,
+            Format::Indexed4 => 16,
+        }
+    }
+}
+
+/// Screen rectangle, corners inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x0: u16,
+    pub y0: u16,
+    pub x1: u16,
+    pub y1: u16,
+}
+
+impl Rect {
+    pub const fn new(x0: u16, y0: u16, x1: u16, y1: u16) -> Self {
+        Rect { x0, y0, x1, y1 }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.x1 - self.x0 + 1
+    }
+
+    pub fn height(&self) -> u16 {
+        self.y1 - self.y0 + 1
+    }
+
+    pub fn area(&self) -> u32 {
+        self.width() as u32 * self.height() as u32
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.x0 <= other.x0 && self.y0 <= other.y0 && self.x1 >= other.x1 && self.y1 >= other.y1
+    }
+
+    /// Smallest rectangle covering both
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+}
+
+/// Something pixels can be streamed to, one rectangle at a time
+pub trait Window {
+    type Error;
+
+    /// Sets the address window to `rect` and writes its pixels, row by row
+    fn write_window(
+        &mut self,
+        rect: Rect,
+        pixels: &mut dyn Iterator<Item = u16>,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<SPI, DC, RST> Window for ST7735<SPI, DC, RST>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    RST: OutputPin,
+{
+    type Error = Error;
+
+    fn write_window(
+        &mut self,
+        rect: Rect,
+        pixels: &mut dyn Iterator<Item = u16>,
+    ) -> Result<(), Error> {
+        self.set_pixels(rect.x0, rect.y0, rect.x1, rect.y1, pixels)
+            .map_err(|_| Error::Transfer)
+    }
+}
+
+/// Framebuffer over a borrowed buffer
+pub struct Framebuffer<'a> {
+    buf: &'a mut [u8],
+    width: u16,
+    height: u16,
+    format: Format,
+    palette: [u16; 256],
+    /// last color mapped to the palette, and its index
+    last_match: Option<(Rgb565, u8)>,
+    dirty: [Rect; MAX_DIRTY],
+    dirty_len: usize,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// Bytes needed for a framebuffer of this size and format
+    pub const fn buffer_len(width: u16, height: u16, format: Format) -> usize {
+        format.row_len(width) * height as usize
+    }
+
+    /// Creates a framebuffer, `None` if `buf` is too small. The palette starts
+    /// as black, white and then the primary colors.
+    pub fn new(buf: &'a mut [u8], width: u16, height: u16, format: Format) -> Option<Self> {
+        if width == 0 || height == 0 || buf.len() < Self::buffer_len(width, height, format) {
+            return None;
+        }
+        let mut palette = [0u16; 256];
+        let defaults = [
+            Rgb565::BLACK,
+            Rgb565::WHITE,
+            Rgb565::RED,
+            Rgb565::GREEN,
+            Rgb565::BLUE,
+            Rgb565::YELLOW,
+            Rgb565::CYAN,
+            Rgb565::MAGENTA,
+        ];
+        for (entry, color) in palette.iter_mut().zip(defaults.iter()) {
+            *entry = RawU16::from(*color).into_inner();
+        }
+        let mut fb = Framebuffer {
+            buf,
+            width,
+            height,
+            format,
+            palette,
+            last_match: None,
+            dirty: [Rect::new(0, 0, 0, 0); MAX_DIRTY],
+            dirty_len: 0,
+        };
+        fb.mark_all_dirty();
+        Some(fb)
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The raw buffer, rows of packed pixels
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..Self::buffer_len(self.width, self.height, self.format)]
+    }
+
+    /// Sets a palette entry, redrawing everything on the next flush
+    pub fn set_palette(&mut self, index: u8, color: Rgb565) {
+        if (index as usize) < self.format.palette_len() {
+            self.palette[index as usize] = RawU16::from(color).into_inner();
+            self.last_match = None;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn palette(&self, index: u8) -> Rgb565 {
+        RawU16::new(self.palette[index as usize]).into()
+    }
+
+    /// Sets a pixel to a palette index, ignored in `Format::Rgb565`
+    pub fn set_index(&mut self, x: u16, y: u16, index: u8) {
+        if x >= self.width || y >= self.height || self.format == Format::Rgb565 {
+            return;
+        }
+        self.store(x, y, index as u16);
+        self.mark_dirty(Rect::new(x, y, x, y));
+    }
+
+    /// Color of a pixel
+    pub fn pixel(&self, x: u16, y: u16) -> Option<Rgb565> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(RawU16::new(self.load(x, y)).into())
+    }
+
+    /// Rectangles changed since the last flush
+    pub fn dirty_rects(&self) -> &[Rect] {
+        &self.dirty[..self.dirty_len]
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_len > 0
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty[0] = Rect::new(0, 0, self.width - 1, self.height - 1);
+        self.dirty_len = 1;
+    }
+
+    /// Adds a changed region, clipped to the screen
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        if rect.x0 >= self.width || rect.y0 >= self.height {
+            return;
+        }
+        let rect = Rect {
+            x1: rect.x1.min(self.width - 1),
+            y1: rect.y1.min(self.height - 1),
+            ..rect
+        };
+        let dirty = &self.dirty[..self.dirty_len];
+        if dirty.iter().any(|d| d.contains(&rect)) {
+            return;
+        }
+
+        // cheapest merge: the union wasting the fewest pixels
+        let waste = |d: &Rect| d.union(&rect).area().saturating_sub(d.area() + rect.area());
+        let best = (0..dirty.len()).min_by_key(|&i| waste(&dirty[i]));
+        match best {
+            Some(i) if waste(&dirty[i]) <= MERGE_SLACK || self.dirty_len == MAX_DIRTY => {
+                self.dirty[i] = self.dirty[i].union(&rect);
+            }
+            _ => {
+                self.dirty[self.dirty_len] = rect;
+                self.dirty_len += 1;
+            }
+        }
+    }
+
+    /// Sends the dirty rectangles, one address window each
+    pub fn flush<W: Window>(&mut self, lcd: &mut W) -> Result<(), W::Error> {
+        while self.dirty_len > 0 {
+            let rect = self.dirty[self.dirty_len - 1];
+            let fb = &*self;
+            let mut pixels = (rect.y0..=rect.y1)
+                .flat_map(move |y| (rect.x0..=rect.x1).map(move |x| fb.load(x, y)));
+            lcd.write_window(rect, &mut pixels)?;
+            self.dirty_len -= 1;
+        }
+        Ok(())
+    }
+
+    /// Takes one dirty rectangle, for flushing by other means, like DMA
+    pub fn pop_dirty(&mut self) -> Option<Rect> {
+        if self.dirty_len == 0 {
+            return None;
+        }
+        self.dirty_len -= 1;
+        Some(self.dirty[self.dirty_len])
+    }
+
+    /// Big endian RGB565 bytes of pixels `x0..=x1` of row `y`, only in
+    /// `Format::Rgb565`, where they can be sent without conversion
+    pub fn row_bytes(&self, y: u16, x0: u16, x1: u16) -> Option<&[u8]> {
+        if self.format != Format::Rgb565 || y >= self.height || x0 > x1 || x1 >= self.width {
+            return None;
+        }
+        let row = y as usize * self.format.row_len(self.width);
+        Some(&self.buf[row + x0 as usize * 2..row + x1 as usize * 2 + 2])
+    }
+
+    /// Expands pixels `x0..=x1` of row `y` to big endian RGB565 bytes into
+    /// `out`, returns the number of bytes written
+    pub fn expand_row(&self, y: u16, x0: u16, x1: u16, out: &mut [u8]) -> usize {
+        let x1 = x1.min(self.width - 1);
+        if y >= self.height || x0 > x1 {
+            return 0;
+        }
+        let mut n = 0;
+        for (x, px) in (x0..=x1).zip(out.chunks_exact_mut(2)) {
+            px.copy_from_slice(&self.load(x, y).to_be_bytes());
+            n += 2;
+        }
+        n
+    }
+
+    /// RGB565 of a pixel in bounds
+    fn load(&self, x: u16, y: u16) -> u16 {
+        let row = y as usize * self.format.row_len(self.width);
+        match self.format {
+            Format::Rgb565 => {
+                let i = row + x as usize * 2;
+                u16::from_be_bytes([self.buf[i], self.buf[i + 1]])
+            }
+            Format::Indexed8 => self.palette[self.buf[row + x as usize] as usize],
+            Format::Indexed4 => {
+                let b = self.buf[row + x as usize / 2];
+                let index = if x % 2 == 0 { b >> 4 } else { b & 0x0f };
+                self.palette[index as usize]
+            }
+        }
+    }
+
+    /// Stores RGB565, or a palette index, of a pixel in bounds
+    fn store(&mut self, x: u16, y: u16, value: u16) {
+        let row = y as usize * self.format.row_len(self.width);
+        match self.format {
+            Format::Rgb565 => {
+                let i = row + x as usize * 2;
+                self.buf[i..i + 2].copy_from_slice(&value.to_be_bytes());
+            }
+            Format::Indexed8 => self.buf[row + x as usize] = value as u8,
+            Format::Indexed4 => {
+                let b = &mut self.buf[row + x as usize / 2];
+                *b = if x % 2 == 0 {
+                    (*b & 0x0f) | ((value as u8) << 4)
+                } else {
+                    (*b & 0xf0) | (value as u8 & 0x0f)
+                };
+            }
+        }
+    }
+
+    /// Value stored for `color`: RGB565, or the nearest palette index
+    fn encode(&mut self, color: Rgb565) -> u16 {
+        if self.format == Format::Rgb565 {
+            return RawU16::from(color).into_inner();
+        }
+        if let Some((last, index)) = self.last_match {
+            if last == color {
+                return index as u16;
+            }
+        }
+        let distance = |raw: u16| {
+            let c: Rgb565 = RawU16::new(raw).into();
+            let dr = c.r() as i32 - color.r() as i32;
+            let dg = c.g() as i32 - color.g() as i32;
+            let db = c.b() as i32 - color.b() as i32;
+            // green has one bit more
+            (dr * dr * 4 + dg * dg + db * db * 4) as u32
+        };
+        let palette = &self.palette[..self.format.palette_len()];
+        let index = (0..palette.len())
+            .min_by_key(|&i| distance(palette[i]))
+            .unwrap_or(0) as u8;
+        self.last_match = Some((color, index));
+        index as u16
+    }
+}
+
+impl DrawTarget<Rgb565> for Framebuffer<'_> {
+    type Error = core::convert::Infallible;
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+        let Pixel(point, color) = pixel;
+        if point.x < 0
+            || point.y < 0
+            || point.x >= self.width as i32
+            || point.y >= self.height as i32
+        {
+            return Ok(());
+        }
+        let (x, y) = (point.x as u16, point.y as u16);
+        let value = self.encode(color);
+        self.store(x, y, value);
+        self.mark_dirty(Rect::new(x, y, x, y));
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+
+    fn clear(&mut self, color: Rgb565) -> Result<(), Self::Error> {
+        let value = self.encode(color);
+        let len = Self::buffer_len(self.width, self.height, self.format);
+        let buf = &mut self.buf[..len];
+        match self.format {
+            Format::Rgb565 => {
+                for px in buf.chunks_exact_mut(2) {
+                    px.copy_from_slice(&value.to_be_bytes());
+                }
+            }
+            Format::Indexed8 => buf.iter_mut().for_each(|b| *b = value as u8),
+            Format::Indexed4 => buf.iter_mut().for_each(|b| *b = (value as u8) * 0x11),
+        }
+        self.mark_all_dirty();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::geometry::Point;
+
+    use super::*;
+
+    /// Records the windows flushed to it
+    struct Recorder {
+        rects: [Rect; 8],
+        len: usize,
+        pixels: [u16; 64],
+        count: usize,
+    }
+
+    impl Recorder {
+        fn new() -> Self {
+            Recorder {
+                rects: [Rect::new(0, 0, 0, 0); 8],
+                len: 0,
+                pixels: [0; 64],
+                count: 0,
+            }
+        }
+    }
+
+    impl Window for Recorder {
+        type Error = ();
+
+        fn write_window(
+            &mut self,
+            rect: Rect,
+            pixels: &mut dyn Iterator<Item = u16>,
+        ) -> Result<(), ()> {
+            self.rects[self.len] = rect;
+            self.len += 1;
+            for p in pixels {
+                if let Some(slot) = self.pixels.get_mut(self.count) {
+                    *slot = p;
+                }
+                self.count += 1;
+            }
+            Ok(())
+        }
+    }
+
+    fn raw(color: Rgb565) -> u16 {
+        RawU16::from(color).into_inner()
+    }
+
+    fn plot(fb: &mut Framebuffer, x: i32, y: i32, color: Rgb565) {
+        fb.draw_pixel(Pixel(Point::new(x, y), color)).unwrap();
+    }
+
+    #[test]
+    fn checks_the_buffer() {
+        let mut buf = [0; 160 * 80 * 2];
+        assert_eq!(Framebuffer::buffer_len(160, 80, Format::Rgb565), 25600);
+        assert_eq!(Framebuffer::buffer_len(160, 80, Format::Indexed8), 12800);
+        assert_eq!(Framebuffer::buffer_len(5, 2, Format::Indexed4), 6);
+        assert!(Framebuffer::new(&mut buf[..25599], 160, 80, Format::Rgb565).is_none());
+        assert!(Framebuffer::new(&mut buf, 0, 80, Format::Rgb565).is_none());
+        let fb = Framebuffer::new(&mut buf, 160, 80, Format::Rgb565).unwrap();
+        assert_eq!(fb.dirty_rects(), [Rect::new(0, 0, 159, 79)]);
+    }
+
+    #[test]
+    fn merges_dirty_rects() {
+        let mut buf = [0; 160 * 80];
+        let mut fb = Framebuffer::new(&mut buf, 160, 80, Format::Indexed8).unwrap();
+        while fb.pop_dirty().is_some() {}
+        assert!(!fb.is_dirty());
+
+        // neighbours grow one rectangle
+        plot(&mut fb, 10, 10, Rgb565::WHITE);
+        plot(&mut fb, 11, 10, Rgb565::WHITE);
+        plot(&mut fb, 10, 11, Rgb565::WHITE);
+        assert_eq!(fb.dirty_rects(), [Rect::new(10, 10, 11, 11)]);
+        // inside it, nothing changes
+        fb.mark_dirty(Rect::new(11, 11, 11, 11));
+        assert_eq!(fb.dirty_rects(), [Rect::new(10, 10, 11, 11)]);
+        // close enough to merge within the slack
+        fb.mark_dirty(Rect::new(14, 10, 15, 11));
+        assert_eq!(fb.dirty_rects(), [Rect::new(10, 10, 15, 11)]);
+
+        // far apart, kept separate up to MAX_DIRTY
+        fb.mark_dirty(Rect::new(100, 10, 101, 11));
+        fb.mark_dirty(Rect::new(10, 60, 11, 61));
+        fb.mark_dirty(Rect::new(100, 60, 101, 61));
+        assert_eq!(fb.dirty_rects().len(), MAX_DIRTY);
+        // then merged where the least is wasted
+        fb.mark_dirty(Rect::new(100, 70, 101, 71));
+        assert_eq!(
+            fb.dirty_rects(),
+            [
+                Rect::new(10, 10, 15, 11),
+                Rect::new(100, 10, 101, 11),
+                Rect::new(10, 60, 11, 61),
+                Rect::new(100, 60, 101, 71),
+            ]
+        );
+    }
+
+    #[test]
+    fn clips_dirty_rects() {
+        let mut buf = [0; 40];
+        let mut fb = Framebuffer::new(&mut buf, 10, 4, Format::Indexed8).unwrap();
+        while fb.pop_dirty().is_some() {}
+        fb.mark_dirty(Rect::new(10, 0, 12, 1));
+        fb.mark_dirty(Rect::new(0, 4, 1, 5));
+        plot(&mut fb, -1, 0, Rgb565::WHITE);
+        plot(&mut fb, 0, 4, Rgb565::WHITE);
+        assert!(!fb.is_dirty());
+        fb.mark_dirty(Rect::new(8, 2, 20, 20));
+        assert_eq!(fb.dirty_rects(), [Rect::new(8, 2, 9, 3)]);
+    }
+
+    #[test]
+    fn pops_dirty_rects() {
+        let mut buf = [0; 160 * 80];
+        let mut fb = Framebuffer::new(&mut buf, 160, 80, Format::Indexed8).unwrap();
+        assert_eq!(fb.pop_dirty(), Some(Rect::new(0, 0, 159, 79)));
+        fb.mark_dirty(Rect::new(0, 0, 1, 1));
+        fb.mark_dirty(Rect::new(100, 50, 101, 51));
+        assert_eq!(fb.pop_dirty(), Some(Rect::new(100, 50, 101, 51)));
+        assert_eq!(fb.pop_dirty(), Some(Rect::new(0, 0, 1, 1)));
+        assert_eq!(fb.pop_dirty(), None);
+        assert!(!fb.is_dirty());
+    }
+
+    #[test]
+    fn flushes_windows() {
+        let mut buf = [0; 2 * 8 * 4];
+        let mut fb = Framebuffer::new(&mut buf, 8, 4, Format::Rgb565).unwrap();
+        fb.clear(Rgb565::BLUE).unwrap();
+        let mut lcd = Recorder::new();
+        fb.flush(&mut lcd).unwrap();
+        assert_eq!(lcd.rects[..lcd.len], [Rect::new(0, 0, 7, 3)]);
+        assert_eq!(lcd.count, 32);
+        assert!(lcd.pixels[..32].iter().all(|&p| p == raw(Rgb565::BLUE)));
+
+        plot(&mut fb, 2, 1, Rgb565::RED);
+        plot(&mut fb, 3, 1, Rgb565::GREEN);
+        let mut lcd = Recorder::new();
+        fb.flush(&mut lcd).unwrap();
+        assert_eq!(lcd.rects[..lcd.len], [Rect::new(2, 1, 3, 1)]);
+        assert_eq!(lcd.pixels[..2], [raw(Rgb565::RED), raw(Rgb565::GREEN)]);
+        assert!(!fb.is_dirty());
+
+        assert_eq!(fb.row_bytes(1, 2, 3), Some(&[0xf8, 0x00, 0x07, 0xe0][..]));
+        assert_eq!(fb.row_bytes(1, 3, 2), None);
+        assert_eq!(fb.row_bytes(1, 7, 8), None);
+    }
+
+    #[test]
+    fn expands_indexed8() {
+        let mut buf = [0; 4 * 2];
+        let mut fb = Framebuffer::new(&mut buf, 4, 2, Format::Indexed8).unwrap();
+        fb.set_palette(200, Rgb565::new(1, 2, 3));
+        fb.set_index(1, 1, 200);
+        fb.set_index(2, 1, 2);
+        assert_eq!(fb.pixel(1, 1), Some(Rgb565::new(1, 2, 3)));
+        assert_eq!(fb.row_bytes(1, 0, 3), None);
+
+        let mut out = [0xaa; 10];
+        assert_eq!(fb.expand_row(1, 0, 3, &mut out), 8);
+        assert_eq!(out, [0, 0, 0x08, 0x43, 0xf8, 0, 0, 0, 0xaa, 0xaa]);
+        // clamped to the row and to `out`
+        assert_eq!(fb.expand_row(1, 2, 100, &mut out), 4);
+        assert_eq!(fb.expand_row(1, 0, 3, &mut out[..3]), 2);
+        assert_eq!(fb.expand_row(2, 0, 3, &mut out), 0);
+        assert_eq!(fb.expand_row(1, 3, 2, &mut out), 0);
+    }
+
+    #[test]
+    fn expands_indexed4() {
+        // odd width, the last byte of a row has one pixel
+        let mut buf = [0; 3 * 2];
+        let mut fb = Framebuffer::new(&mut buf, 5, 2, Format::Indexed4).unwrap();
+        fb.clear(Rgb565::BLACK).unwrap();
+        fb.set_index(0, 1, 1);
+        fb.set_index(3, 1, 2);
+        fb.set_index(4, 1, 4);
+        // indices beyond the palette keep to their nibble
+        fb.set_index(1, 0, 0x13);
+        assert_eq!(fb.as_bytes(), [0x03, 0x00, 0x00, 0x10, 0x02, 0x40]);
+
+        let mut out = [0; 10];
+        assert_eq!(fb.expand_row(1, 0, 4, &mut out), 10);
+        assert_eq!(out, [0xff, 0xff, 0, 0, 0, 0, 0xf8, 0, 0, 0x1f]);
+        assert_eq!(fb.expand_row(1, 3, 4, &mut out), 4);
+        assert_eq!(out[..4], [0xf8, 0, 0, 0x1f]);
+    }
+
+    #[test]
+    fn maps_colors_to_the_palette() {
+        let mut buf = [0; 2];
+        let mut fb = Framebuffer::new(&mut buf, 4, 1, Format::Indexed4).unwrap();
+        plot(&mut fb, 0, 0, Rgb565::new(28, 3, 2));
+        plot(&mut fb, 1, 0, Rgb565::new(2, 60, 29));
+        assert_eq!(fb.pixel(0, 0), Some(Rgb565::RED));
+        assert_eq!(fb.pixel(1, 0), Some(Rgb565::CYAN));
+
+        // a new palette entry wins once set
+        fb.set_palette(9, Rgb565::new(28, 3, 2));
+        plot(&mut fb, 2, 0, Rgb565::new(28, 3, 2));
+        assert_eq!(fb.as_bytes(), [0x26, 0x90]);
+        assert_eq!(fb.dirty_rects(), [Rect::new(0, 0, 3, 0)]);
+        // ignored outside the palette
+        fb.set_palette(16, Rgb565::WHITE);
+        assert_eq!(fb.palette(9), Rgb565::new(28, 3, 2));
+    }
+}
@@ -0,0 +1,406 @@
+//! Direct register access to the on-board LCD
+//!
+//! Drives SPI0 and the DC pin (PB0) without owning them, for code that cannot
+//! reach the `Lcd` driver, like the panic handler. Only valid after
+//! `lcd::configure`, and while no `Lcd` transfer is in progress.
+//!
+//! Only transmitting, the received bytes are never read, so SPI0 flags an
+//! overrun. `wait_idle` clears it, as `Lcd` refuses to send while it is set.
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Size;
+use embedded_graphics::pixelcolor::raw::{RawData, RawU16};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::DrawTarget;
+use riscv::interrupt;
+
+use super::framebuffer::Rect;
+use super::{check_window, Error};
+use crate::pac::{GPIOB, SPI0};
+
+/// SPI STAT bits
+const SPI_STAT_TBE: u16 = 1 << 1;
+const SPI_STAT_TRANS: u16 = 1 << 7;
+/// DC is PB0
+const DC_PIN: u32 = 1 << 0;
+
+/// ST7735 commands
+pub const CASET: u8 = 0x2a;
+pub const RASET: u8 = 0x2b;
+pub const RAMWR: u8 = 0x2c;
+
+/// Panel geometry recorded by `lcd::configure`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Geometry {
+    pub width: u16,
+    pub height: u16,
+    pub dx: u16,
+    pub dy: u16,
+}
+
+static mut GEOMETRY: Option<Geometry> = None;
+
+pub(crate) fn set_geometry(geometry: Geometry) {
+    interrupt::free(|_| unsafe { GEOMETRY = Some(geometry) })
+}
+
+/// Byte stream to the controller
+pub trait Bus {
+    /// Waits until the last bit is out, then clears the receive overrun
+    fn wait_idle(&mut self);
+
+    /// Sets DC, high for data and low for commands
+    fn set_dc(&mut self, data: bool);
+
+    fn write_byte(&mut self, b: u8);
+}
+
+/// The registers of SPI0 and PB0
+pub struct Spi0;
+
+impl Spi0 {
+    fn spi(&self) -> &'static crate::pac::spi0::RegisterBlock {
+        unsafe { &*SPI0::ptr() }
+    }
+}
+
+impl Bus for Spi0 {
+    fn wait_idle(&mut self) {
+        let spi = self.spi();
+        while spi.stat.read().bits() & SPI_STAT_TBE == 0 {}
+        while spi.stat.read().bits() & SPI_STAT_TRANS != 0 {}
+        // reading DATA then STAT clears OVR, like `dma::finish`
+        let _ = spi.data.read().bits();
+        let _ = spi.stat.read().bits();
+    }
+
+    fn set_dc(&mut self, data: bool) {
+        self.wait_idle();
+        let gpiob = unsafe { &*GPIOB::ptr() };
+        if data {
+            gpiob.bop.write(|w| unsafe { w.bits(DC_PIN) });
+        } else {
+            gpiob.bc.write(|w| unsafe { w.bits(DC_PIN) });
+        }
+    }
+
+    fn write_byte(&mut self, b: u8) {
+        let spi = self.spi();
+        while spi.stat.read().bits() & SPI_STAT_TBE == 0 {}
+        spi.data.write(|w| unsafe { w.bits(b as u16) });
+    }
+}
+
+/// Raw LCD access
+pub struct RawLcd<B = Spi0> {
+    geometry: Geometry,
+    bus: B,
+}
+
+impl RawLcd {
+    /// Takes over SPI0 and PB0, `None` if the LCD has not been configured.
+    ///
+    /// # Safety
+    ///
+    /// The `Lcd` driver must not be used at the same time.
+    pub unsafe fn steal() -> Option<Self> {
+        interrupt::free(|_| GEOMETRY).map(|geometry| RawLcd {
+            geometry,
+            bus: Spi0,
+        })
+    }
+}
+
+impl<B: Bus> RawLcd<B> {
+    pub fn width(&self) -> u16 {
+        self.geometry.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.geometry.height
+    }
+
+    /// Waits until the last bit has left the shift register
+    pub fn wait_idle(&mut self) {
+        self.bus.wait_idle();
+    }
+
+    /// Sends a command byte followed by its parameters
+    pub fn write_command(&mut self, cmd: u8, params: &[u8]) {
+        self.bus.set_dc(false);
+        self.bus.write_byte(cmd);
+        self.bus.set_dc(true);
+        for &b in params {
+            self.bus.write_byte(b);
+        }
+    }
+
+    /// Sends data bytes, e.g. pixels after `RAMWR`
+    pub fn write_data(&mut self, data: &[u8]) {
+        for &b in data {
+            self.bus.write_byte(b);
+        }
+    }
+
+    /// Sets the window of the next `RAMWR`, in screen coordinates (inclusive)
+    pub fn set_address_window(&mut self, sx: u16, sy: u16, ex: u16, ey: u16) {
+        let (dx, dy) = (self.geometry.dx, self.geometry.dy);
+        let (sx, ex, sy, ey) = (sx + dx, ex + dx, sy + dy, ey + dy);
+        self.write_command(
+            CASET,
+            &[(sx >> 8) as u8, sx as u8, (ex >> 8) as u8, ex as u8],
+        );
+        self.write_command(
+            RASET,
+            &[(sy >> 8) as u8, sy as u8, (ey >> 8) as u8, ey as u8],
+        );
+    }
+
+    /// Starts writing pixels into the address window
+    pub fn start_pixels(&mut self) {
+        self.write_command(RAMWR, &[]);
+    }
+
+    /// Copies big endian RGB565 `data` to `rect`, see `lcd::blit`
+    pub fn blit(&mut self, rect: Rect, data: &[u8]) -> Result<(), Error> {
+        check_window(rect, self.width(), self.height(), data.len())?;
+        self.set_address_window(rect.x0, rect.y0, rect.x1, rect.y1);
+        self.start_pixels();
+        self.write_data(data);
+        self.wait_idle();
+        Ok(())
+    }
+
+    /// Fills the whole screen with `color`
+    pub fn fill(&mut self, color: Rgb565) {
+        let raw = RawU16::from(color).into_inner();
+        let (w, h) = (self.geometry.width, self.geometry.height);
+        self.set_address_window(0, 0, w - 1, h - 1);
+        self.start_pixels();
+        for _ in 0..(w as u32 * h as u32) {
+            self.bus.write_byte((raw >> 8) as u8);
+            self.bus.write_byte(raw as u8);
+        }
+        self.wait_idle();
+    }
+}
+
+impl<B: Bus> DrawTarget<Rgb565> for RawLcd<B> {
+    type Error = core::convert::Infallible;
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+        let Pixel(point, color) = pixel;
+        if point.x < 0 || point.y < 0 {
+            return Ok(());
+        }
+        let (x, y) = (point.x as u16, point.y as u16);
+        if x >= self.geometry.width || y >= self.geometry.height {
+            return Ok(());
+        }
+        let raw = RawU16::from(color).into_inner();
+        self.set_address_window(x, y, x, y);
+        self.write_command(RAMWR, &[(raw >> 8) as u8, raw as u8]);
+        self.wait_idle();
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.geometry.width as u32, self.geometry.height as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::drawable::Drawable;
+    use embedded_graphics::geometry::Point;
+    use embedded_graphics::pixelcolor::RgbColor;
+
+    use super::*;
+
+    const MEMORY_WIDTH: usize = 40;
+    const MEMORY_HEIGHT: usize = 30;
+
+    /// ST7735 frame memory behind the bus, only CASET, RASET and RAMWR
+    struct Panel {
+        memory: [[u16; MEMORY_WIDTH]; MEMORY_HEIGHT],
+        dc: bool,
+        cmd: u8,
+        params: [u8; 4],
+        param_len: usize,
+        /// columns and rows, inclusive
+        columns: (u16, u16),
+        rows: (u16, u16),
+        cursor: (u16, u16),
+        pending: Option<u8>,
+        /// Bytes received but not read, the cause of OVR
+        unread: bool,
+    }
+
+    impl Panel {
+        fn new() -> Self {
+            Panel {
+                memory: [[0; MEMORY_WIDTH]; MEMORY_HEIGHT],
+                dc: true,
+                cmd: 0,
+                params: [0; 4],
+                param_len: 0,
+                columns: (0, 0),
+                rows: (0, 0),
+                cursor: (0, 0),
+                pending: None,
+                unread: false,
+            }
+        }
+
+        fn data(&mut self, b: u8) {
+            match self.cmd {
+                CASET | RASET if self.param_len < 4 => {
+                    self.params[self.param_len] = b;
+                    self.param_len += 1;
+                    let p = self.params;
+                    let range = (
+                        u16::from_be_bytes([p[0], p[1]]),
+                        u16::from_be_bytes([p[2], p[3]]),
+                    );
+                    if self.param_len == 4 && self.cmd == CASET {
+                        self.columns = range;
+                    } else if self.param_len == 4 {
+                        self.rows = range;
+                    }
+                }
+                RAMWR => {
+                    let hi = match self.pending.take() {
+                        Some(hi) => hi,
+                        None => {
+                            self.pending = Some(b);
+                            return;
+                        }
+                    };
+                    let (x, y) = self.cursor;
+                    self.memory[y as usize][x as usize] = u16::from_be_bytes([hi, b]);
+                    self.cursor = if x < self.columns.1 {
+                        (x + 1, y)
+                    } else if y < self.rows.1 {
+                        (self.columns.0, y + 1)
+                    } else {
+                        (self.columns.0, self.rows.0)
+                    };
+                }
+                _ => panic!("data {:#04x} after command {:#04x}", b, self.cmd),
+            }
+        }
+    }
+
+    impl Bus for Panel {
+        fn wait_idle(&mut self) {
+            self.unread = false;
+        }
+
+        fn set_dc(&mut self, data: bool) {
+            self.dc = data;
+        }
+
+        fn write_byte(&mut self, b: u8) {
+            self.unread = true;
+            if self.dc {
+                self.data(b);
+                return;
+            }
+            self.cmd = b;
+            self.param_len = 0;
+            self.pending = None;
+            if b == RAMWR {
+                self.cursor = (self.columns.0, self.rows.0);
+            }
+        }
+    }
+
+    /// 30x20 visible at (2, 5) of the frame memory
+    fn lcd() -> RawLcd<Panel> {
+        RawLcd {
+            geometry: Geometry {
+                width: 30,
+                height: 20,
+                dx: 2,
+                dy: 5,
+            },
+            bus: Panel::new(),
+        }
+    }
+
+    fn raw(color: Rgb565) -> u16 {
+        RawU16::from(color).into_inner()
+    }
+
+    #[test]
+    fn sets_windows_at_the_offset() {
+        let mut lcd = lcd();
+        lcd.set_address_window(1, 2, 28, 19);
+        assert_eq!((lcd.bus.columns, lcd.bus.rows), ((3, 30), (7, 24)));
+        assert_eq!(lcd.bus.cmd, RASET);
+    }
+
+    #[test]
+    fn blits_rows() {
+        let mut lcd = lcd();
+        // 3x2, row by row
+        let data = [
+            0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x04, 0x00, 0x05, 0x00, 0x06,
+        ];
+        lcd.blit(Rect::new(4, 6, 6, 7), &data).unwrap();
+        let memory = &lcd.bus.memory;
+        assert_eq!(memory[11][6..9], [1, 2, 3]);
+        assert_eq!(memory[12][6..9], [4, 5, 6]);
+        assert_eq!(memory[11][5], 0);
+        assert_eq!(memory[13][6], 0);
+        assert!(!lcd.bus.unread);
+
+        assert_eq!(
+            lcd.blit(Rect::new(4, 6, 6, 7), &data[..10]),
+            Err(Error::Length)
+        );
+        assert_eq!(
+            lcd.blit(Rect::new(28, 0, 30, 1), &data),
+            Err(Error::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn fills_the_visible_screen() {
+        let mut lcd = lcd();
+        lcd.fill(Rgb565::RED);
+        let memory = &lcd.bus.memory;
+        for (y, row) in memory.iter().enumerate() {
+            for (x, &p) in row.iter().enumerate() {
+                let visible = (2..32).contains(&x) && (5..25).contains(&y);
+                assert_eq!(p == raw(Rgb565::RED), visible, "({}, {})", x, y);
+            }
+        }
+        assert!(!lcd.bus.unread);
+    }
+
+    #[test]
+    fn draws_pixels() {
+        let mut lcd = lcd();
+        Pixel(Point::new(0, 0), Rgb565::GREEN)
+            .draw(&mut lcd)
+            .unwrap();
+        Pixel(Point::new(29, 19), Rgb565::BLUE)
+            .draw(&mut lcd)
+            .unwrap();
+        assert!(!lcd.bus.unread);
+        // clipped
+        Pixel(Point::new(30, 0), Rgb565::WHITE)
+            .draw(&mut lcd)
+            .unwrap();
+        Pixel(Point::new(-1, 0), Rgb565::WHITE)
+            .draw(&mut lcd)
+            .unwrap();
+
+        let memory = &lcd.bus.memory;
+        assert_eq!(memory[5][2], raw(Rgb565::GREEN));
+        assert_eq!(memory[24][31], raw(Rgb565::BLUE));
+        let drawn = memory.iter().flatten().filter(|&&p| p != 0).count();
+        assert_eq!(drawn, 2);
+    }
+}
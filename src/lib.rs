@@ -1,12 +1,25 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 pub use gd32vf103xx_hal as hal;
 pub use hal::pac;
 
+// for `bitmap_font!`
+#[doc(hidden)]
+pub use embedded_graphics as __embedded_graphics;
+
 pub mod adc;
+pub mod font;
 pub mod lcd;
+pub mod logger;
+pub mod packet;
 pub mod stdout;
+pub mod trap;
+pub mod ui;
 pub mod esp_at;
+pub mod ring;
+pub mod shell;
+#[cfg(feature = "panic-handler")]
+mod panic;
 
 use core::fmt;
 use core::str;
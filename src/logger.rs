@@ -0,0 +1,127 @@
+//! `log` backend writing to stdout
+//!
+//! Every record becomes one line with a timestamp (seconds since reset, from
+//! `mcycle`), the level and the module path:
+//!
+//! ```text
+//! [   1.204711 DEBUG longan_nano_playground::adc] adc0 enabled
+//! ```
+//!
+//! Filtering happens at compile time with `log`'s `max_level_*` features,
+//! which the final binary enables on its own `log` dependency (only one of
+//! them may be on), and at runtime with `log::set_max_level` and per-module
+//! levels:
+//!
+//! ```ignore
+//! stdout::configure(dp.USART0, gpioa.pa9, gpioa.pa10, 115200.bps(), &mut afio, &mut rcu);
+//! logger::init(&rcu.clocks, LevelFilter::Debug);
+//! logger::set_module_level("longan_nano_playground::esp_at", LevelFilter::Warn);
+//!
+//! log::info!("booted");
+//! ```
+
+use core::fmt::Write;
+
+use gd32vf103xx_hal::rcu::Clocks;
+use log::{LevelFilter, Log, Metadata, Record};
+use riscv::interrupt;
+use riscv::register::mcycle;
+
+use crate::stdout::{self, Stdout};
+
+/// Max number of per-module levels
+const MODULE_FILTER_CAPACITY: usize = 8;
+
+static LOGGER: Logger = Logger;
+
+/// Core clock, to convert `mcycle` to seconds
+static mut CYCLES_PER_SECOND: u32 = 8_000_000;
+
+static mut MODULE_LEVELS: [Option<(&str, LevelFilter)>; MODULE_FILTER_CAPACITY] =
+    [None; MODULE_FILTER_CAPACITY];
+
+/// The logger, installed by `init`
+pub struct Logger;
+
+/// Installs the logger, records are written to the default stdout console
+pub fn init(clocks: &Clocks, level: LevelFilter) {
+    interrupt::free(|_| unsafe {
+        CYCLES_PER_SECOND = clocks.sysclk().0;
+    });
+    // only fails if a logger is set already
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+/// Sets the level of a module and its submodules, e.g. `"my_app::sensor"`.
+///
+/// The longest matching module path wins. Levels above `log::max_level()` are
+/// still filtered out. Returns false when the table is full.
+pub fn set_module_level(module: &'static str, level: LevelFilter) -> bool {
+    interrupt::free(|_| unsafe {
+        let slot = MODULE_LEVELS
+            .iter()
+            .position(|m| matches!(m, Some((path, _)) if *path == module))
+            .or_else(|| MODULE_LEVELS.iter().position(|m| m.is_none()));
+        match slot {
+            Some(i) => {
+                MODULE_LEVELS[i] = Some((module, level));
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Removes all per-module levels
+pub fn clear_module_levels() {
+    interrupt::free(|_| unsafe {
+        MODULE_LEVELS = [None; MODULE_FILTER_CAPACITY];
+    })
+}
+
+/// Level of the longest matching entry of `MODULE_LEVELS`
+fn module_level(target: &str) -> LevelFilter {
+    interrupt::free(|_| unsafe {
+        let mut best: Option<(&str, LevelFilter)> = None;
+        for (path, level) in MODULE_LEVELS.iter().flatten() {
+            let matches = target == *path
+                || (target.starts_with(path) && target[path.len()..].starts_with("::"));
+            if matches && best.map_or(true, |(p, _)| path.len() > p.len()) {
+                best = Some((*path, *level));
+            }
+        }
+        best.map_or(LevelFilter::Trace, |(_, level)| level)
+    })
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= module_level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let cycles = mcycle::read64();
+        let hz = interrupt::free(|_| unsafe { CYCLES_PER_SECOND }) as u64;
+        let secs = cycles / hz;
+        let micros = (cycles % hz) * 1_000_000 / hz;
+
+        let _ = writeln!(
+            Stdout,
+            "[{:4}.{:06} {:5} {}] {}",
+            secs,
+            micros,
+            record.level(),
+            record.module_path().unwrap_or_else(|| record.target()),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        stdout::flush();
+    }
+}
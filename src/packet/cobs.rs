@@ -0,0 +1,131 @@
+//! Consistent Overhead Byte Stuffing
+//!
+//! Removes all zero bytes from a frame, so 0x00 can delimit frames on the
+//! wire. Costs one byte per started 254 bytes.
+
+/// Encoded size of `len` bytes, without delimiter
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encodes `src` into `dst`, returns the encoded length, or `None` if `dst`
+/// is too small. No delimiter is added.
+pub fn encode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    if dst.len() < max_encoded_len(src.len()) {
+        return None;
+    }
+    let mut code_at = 0;
+    let mut out = 1;
+    let mut code = 1u8;
+    for &b in src {
+        if b != 0 {
+            dst[out] = b;
+            out += 1;
+            code += 1;
+        }
+        if b == 0 || code == 0xff {
+            dst[code_at] = code;
+            code_at = out;
+            out += 1;
+            code = 1;
+        }
+    }
+    dst[code_at] = code;
+    Some(out)
+}
+
+/// Decodes `src` (without delimiter) into `dst`, returns the decoded length,
+/// or `None` if `src` is malformed or `dst` too small.
+pub fn decode(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut i = 0;
+    let mut out = 0;
+    while i < src.len() {
+        let code = src[i] as usize;
+        if code == 0 || i + code > src.len() {
+            return None;
+        }
+        let block = &src[i + 1..i + code];
+        if block.contains(&0) {
+            return None;
+        }
+        dst.get_mut(out..out + block.len())?.copy_from_slice(block);
+        out += block.len();
+        i += code;
+        // a full block has no implied zero, neither has the end
+        if code < 0xff && i < src.len() {
+            *dst.get_mut(out)? = 0;
+            out += 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(src: &[u8]) -> usize {
+        let mut encoded = [0u8; 512];
+        let n = encode(src, &mut encoded).unwrap();
+        assert!(n <= max_encoded_len(src.len()));
+        assert!(!encoded[..n].contains(&0));
+        let mut decoded = [0u8; 512];
+        assert_eq!(decode(&encoded[..n], &mut decoded), Some(src.len()));
+        assert_eq!(&decoded[..src.len()], src);
+        n
+    }
+
+    #[test]
+    fn encodes() {
+        let mut dst = [0u8; 8];
+        let n = encode(&[0x11, 0x22, 0x00, 0x33], &mut dst).unwrap();
+        assert_eq!(&dst[..n], [0x03, 0x11, 0x22, 0x02, 0x33]);
+        let n = encode(&[0x00, 0x00], &mut dst).unwrap();
+        assert_eq!(&dst[..n], [0x01, 0x01, 0x01]);
+        let n = encode(&[], &mut dst).unwrap();
+        assert_eq!(&dst[..n], [0x01]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut src = [0u8; 260];
+        for len in 0..=src.len() {
+            // zeros every 7th byte
+            for (i, b) in src[..len].iter_mut().enumerate() {
+                *b = if i % 7 == 3 { 0 } else { i as u8 | 1 };
+            }
+            round_trip(&src[..len]);
+            round_trip(&[0u8; 260][..len]);
+            round_trip(&[0xffu8; 260][..len]);
+        }
+    }
+
+    #[test]
+    fn full_blocks() {
+        // 254 non-zero bytes fill one block, with no implied zero after it
+        let block = [0x42u8; 254];
+        let mut dst = [0u8; 300];
+        let n = encode(&block, &mut dst).unwrap();
+        assert_eq!(n, 256);
+        assert_eq!((dst[0], dst[255]), (0xff, 0x01));
+        assert_eq!(round_trip(&block), 256);
+
+        let mut src = [0x42u8; 255];
+        assert_eq!(round_trip(&src), 257);
+        src[254] = 0;
+        assert_eq!(round_trip(&src), 257);
+        assert_eq!(round_trip(&src[..253]), 254);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let mut dst = [0u8; 8];
+        assert_eq!(encode(&[1, 2, 3], &mut dst[..3]), None);
+        // code past the end, zero code, zero inside a block
+        assert_eq!(decode(&[0x05, 0x11], &mut dst), None);
+        assert_eq!(decode(&[0x00], &mut dst), None);
+        assert_eq!(decode(&[0x03, 0x00, 0x11], &mut dst), None);
+        assert_eq!(decode(&[0x03, 0x11, 0x22, 0x01], &mut dst[..2]), None);
+        assert_eq!(decode(&[], &mut dst), Some(0));
+    }
+}
@@ -0,0 +1,99 @@
+//! CRC-32/MPEG-2: polynomial 0x04c11db7, init 0xffffffff, not reflected,
+//! no final xor. This is what the GD32 CRC unit computes over 32-bit words,
+//! fed most significant byte first.
+
+/// Checksum of a whole frame
+pub trait Crc32 {
+    fn checksum(&mut self, data: &[u8]) -> u32;
+}
+
+const POLY: u32 = 0x04c1_1db7;
+const INIT: u32 = 0xffff_ffff;
+
+/// Continues a CRC over more bytes
+pub fn update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= (b as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Bitwise software CRC, for hosts and chips without a CRC unit
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SoftCrc;
+
+impl Crc32 for SoftCrc {
+    fn checksum(&mut self, data: &[u8]) -> u32 {
+        update(INIT, data)
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+pub use self::hw::HwCrc;
+
+#[cfg(target_arch = "riscv32")]
+mod hw {
+    use super::{update, Crc32};
+    use crate::hal::rcu::Rcu;
+    use crate::pac::{CRC, RCU};
+
+    /// RCU AHBEN bit of the CRC unit
+    const RCU_AHBEN_CRCEN: u32 = 1 << 6;
+    /// CRC CTL reset bit, reloads 0xffffffff
+    const CRC_CTL_RST: u32 = 1 << 0;
+
+    /// The GD32 CRC unit, whole words in hardware, the tail in software
+    pub struct HwCrc {
+        crc: CRC,
+    }
+
+    impl HwCrc {
+        pub fn new(crc: CRC, _rcu: &mut Rcu) -> Self {
+            let rcu = unsafe { &*RCU::ptr() };
+            rcu.ahben
+                .modify(|r, w| unsafe { w.bits(r.bits() | RCU_AHBEN_CRCEN) });
+            HwCrc { crc }
+        }
+
+        /// Gives back the peripheral
+        pub fn free(self) -> CRC {
+            self.crc
+        }
+    }
+
+    impl Crc32 for HwCrc {
+        fn checksum(&mut self, data: &[u8]) -> u32 {
+            self.crc.ctl.write(|w| unsafe { w.bits(CRC_CTL_RST) });
+            let mut words = data.chunks_exact(4);
+            for word in &mut words {
+                let word = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+                self.crc.data.write(|w| unsafe { w.bits(word) });
+            }
+            update(self.crc.data.read().bits(), words.remainder())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(SoftCrc.checksum(b"123456789"), 0x0376_e6e7);
+        assert_eq!(SoftCrc.checksum(b""), INIT);
+    }
+
+    #[test]
+    fn continues() {
+        let crc = update(INIT, b"1234");
+        assert_eq!(update(crc, b"56789"), 0x0376_e6e7);
+    }
+}
@@ -0,0 +1,292 @@
+//! Framed binary packets, for streaming samples and records to a PC
+//!
+//! Each packet is checksummed and COBS encoded, so frames are delimited by
+//! 0x00 on the wire:
+//!
+//! ```text
+//! frame = COBS(seq | kind | payload | crc) 0x00
+//! seq   = u8, incremented for every packet sent, to detect lost ones
+//! kind  = u8, what the payload is, up to the application
+//! crc   = u32 little endian, CRC-32/MPEG-2 of seq | kind | payload
+//! ```
+//!
+//! Send them on a console next to stdout (or on stdout with no text in
+//! between), and decode them on the PC with `tools/packet-dump`:
+//!
+//! ```ignore
+//! let mut encoder = Encoder::new(HwCrc::new(dp.CRC, &mut rcu));
+//! let sample = adc.read_channel(16);
+//! encoder.send(KIND_TEMPERATURE, &sample.to_le_bytes(), &mut |b| console.write_byte(b))?;
+//! ```
+//!
+//! `Decoder` does the reverse, on either side.
+
+pub mod cobs;
+pub mod crc;
+
+pub use self::crc::{Crc32, SoftCrc};
+
+#[cfg(target_arch = "riscv32")]
+pub use self::crc::HwCrc;
+
+/// Longest payload
+pub const MAX_PAYLOAD: usize = 240;
+/// seq and kind
+const HEADER_LEN: usize = 2;
+/// crc
+const TRAILER_LEN: usize = 4;
+/// Longest frame before encoding
+pub const MAX_FRAME: usize = HEADER_LEN + MAX_PAYLOAD + TRAILER_LEN;
+/// Longest encoded frame, with delimiter
+pub const MAX_ENCODED: usize = cobs::max_encoded_len(MAX_FRAME) + 1;
+
+/// Packet errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Payload longer than `MAX_PAYLOAD`, or received frame too long
+    TooLong,
+    /// Output buffer too small
+    Overflow,
+    /// Invalid COBS, or frame shorter than header and crc
+    Malformed,
+    /// Checksum mismatch
+    Crc,
+}
+
+/// A decoded packet
+#[derive(Debug, PartialEq)]
+pub struct Packet<'a> {
+    pub seq: u8,
+    pub kind: u8,
+    pub payload: &'a [u8],
+}
+
+/// Builds frames, numbering them
+pub struct Encoder<C> {
+    crc: C,
+    seq: u8,
+}
+
+impl<C: Crc32> Encoder<C> {
+    pub fn new(crc: C) -> Self {
+        Encoder { crc, seq: 0 }
+    }
+
+    /// Sequence number of the next packet
+    pub fn seq(&self) -> u8 {
+        self.seq
+    }
+
+    /// Encodes a packet into `dst`, delimiter included. Returns the length.
+    pub fn encode(&mut self, kind: u8, payload: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(Error::TooLong);
+        }
+        let mut frame = [0u8; MAX_FRAME];
+        let len = HEADER_LEN + payload.len();
+        frame[0] = self.seq;
+        frame[1] = kind;
+        frame[HEADER_LEN..len].copy_from_slice(payload);
+        let crc = self.crc.checksum(&frame[..len]);
+        frame[len..len + TRAILER_LEN].copy_from_slice(&crc.to_le_bytes());
+        let frame = &frame[..len + TRAILER_LEN];
+
+        let n = cobs::encode(frame, dst).ok_or(Error::Overflow)?;
+        *dst.get_mut(n).ok_or(Error::Overflow)? = 0;
+        self.seq = self.seq.wrapping_add(1);
+        Ok(n + 1)
+    }
+
+    /// Encodes a packet and writes it byte by byte
+    pub fn send(
+        &mut self,
+        kind: u8,
+        payload: &[u8],
+        write: &mut dyn FnMut(u8),
+    ) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_ENCODED];
+        let n = self.encode(kind, payload, &mut buf)?;
+        buf[..n].iter().for_each(|&b| write(b));
+        Ok(())
+    }
+}
+
+/// Splits a byte stream into packets
+pub struct Decoder<C> {
+    crc: C,
+    buf: [u8; MAX_ENCODED],
+    len: usize,
+    /// The frame being received did not fit
+    overflow: bool,
+    frame: [u8; MAX_FRAME],
+    next_seq: Option<u8>,
+    lost: u32,
+}
+
+impl<C: Crc32> Decoder<C> {
+    pub fn new(crc: C) -> Self {
+        Decoder {
+            crc,
+            buf: [0; MAX_ENCODED],
+            len: 0,
+            overflow: false,
+            frame: [0; MAX_FRAME],
+            next_seq: None,
+            lost: 0,
+        }
+    }
+
+    /// Number of packets missing from the sequence so far
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// Handles one received byte, returns a packet or an error at the end of
+    /// each frame. Bytes before the first delimiter are garbage, and are
+    /// reported as an error.
+    pub fn feed(&mut self, b: u8) -> Option<Result<Packet<'_>, Error>> {
+        if b != 0 {
+            if self.len < self.buf.len() {
+                self.buf[self.len] = b;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        let len = core::mem::replace(&mut self.len, 0);
+        if core::mem::replace(&mut self.overflow, false) {
+            return Some(Err(Error::TooLong));
+        }
+        if len == 0 {
+            return None;
+        }
+        Some(self.decode(len))
+    }
+
+    fn decode(&mut self, len: usize) -> Result<Packet<'_>, Error> {
+        let n = cobs::decode(&self.buf[..len], &mut self.frame).ok_or(Error::Malformed)?;
+        if n < HEADER_LEN + TRAILER_LEN {
+            return Err(Error::Malformed);
+        }
+        let body = n - TRAILER_LEN;
+        let mut crc = [0u8; TRAILER_LEN];
+        crc.copy_from_slice(&self.frame[body..n]);
+        if self.crc.checksum(&self.frame[..body]) != u32::from_le_bytes(crc) {
+            return Err(Error::Crc);
+        }
+
+        let seq = self.frame[0];
+        if let Some(expected) = self.next_seq {
+            self.lost += seq.wrapping_sub(expected) as u32;
+        }
+        self.next_seq = Some(seq.wrapping_add(1));
+        Ok(Packet {
+            seq,
+            kind: self.frame[1],
+            payload: &self.frame[HEADER_LEN..body],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `bytes`, expecting exactly one result at the end
+    fn feed_frame<'d>(
+        decoder: &'d mut Decoder<SoftCrc>,
+        bytes: &[u8],
+    ) -> Result<Packet<'d>, Error> {
+        let (last, rest) = bytes.split_last().unwrap();
+        for &b in rest {
+            assert!(decoder.feed(b).is_none());
+        }
+        decoder.feed(*last).unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut encoder = Encoder::new(SoftCrc);
+        let mut decoder = Decoder::new(SoftCrc);
+        let mut payload = [0u8; MAX_PAYLOAD];
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b = (i * 37) as u8;
+        }
+        let mut buf = [0u8; MAX_ENCODED];
+        for len in 0..=MAX_PAYLOAD {
+            let n = encoder
+                .encode(len as u8, &payload[..len], &mut buf)
+                .unwrap();
+            assert!(n <= MAX_ENCODED);
+            assert_eq!(buf[n - 1], 0);
+            assert!(!buf[..n - 1].contains(&0));
+            let packet = feed_frame(&mut decoder, &buf[..n]).unwrap();
+            assert_eq!(
+                (packet.seq, packet.kind, packet.payload),
+                (len as u8, len as u8, &payload[..len])
+            );
+        }
+        assert_eq!(decoder.lost(), 0);
+        assert_eq!(
+            encoder.encode(0, &[0; MAX_PAYLOAD + 1], &mut buf),
+            Err(Error::TooLong)
+        );
+        assert_eq!(
+            encoder.encode(0, &[1, 2], &mut buf[..8]),
+            Err(Error::Overflow)
+        );
+    }
+
+    #[test]
+    fn frame_check_value() {
+        let mut encoder = Encoder::new(SoftCrc);
+        let mut buf = [0u8; MAX_ENCODED];
+        let n = encoder.encode(b'2', b"3456789", &mut buf).unwrap();
+        // seq 0 is a zero byte, then "23456789"
+        let mut frame = [0u8; MAX_FRAME];
+        let len = cobs::decode(&buf[..n - 1], &mut frame).unwrap();
+        assert_eq!(&frame[..len - TRAILER_LEN], b"\x0023456789");
+        assert_eq!(
+            frame[len - TRAILER_LEN..len],
+            crc::update(0xffff_ffff, b"\x0023456789").to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut encoder = Encoder::new(SoftCrc);
+        let mut decoder = Decoder::new(SoftCrc);
+        let mut buf = [0u8; MAX_ENCODED];
+
+        // garbage before the first delimiter
+        assert_eq!(feed_frame(&mut decoder, b"junk\0"), Err(Error::Malformed));
+        assert!(decoder.feed(0).is_none());
+
+        let n = encoder.encode(1, &[0xaa, 0x00, 0xbb], &mut buf).unwrap();
+        buf[3] ^= 0x40;
+        assert_eq!(feed_frame(&mut decoder, &buf[..n]), Err(Error::Crc));
+
+        // too short for header and crc
+        assert_eq!(
+            feed_frame(&mut decoder, &[0x03, 1, 2, 0]),
+            Err(Error::Malformed)
+        );
+
+        // longer than any frame
+        for _ in 0..MAX_ENCODED + 10 {
+            assert!(decoder.feed(0x55).is_none());
+        }
+        assert_eq!(decoder.feed(0), Some(Err(Error::TooLong)));
+
+        // seq 0 was corrupted, 2 and 3 are not sent
+        let n = encoder.encode(1, &[], &mut buf).unwrap();
+        assert_eq!(feed_frame(&mut decoder, &buf[..n]).unwrap().seq, 1);
+        encoder.encode(1, &[], &mut buf).unwrap();
+        encoder.encode(1, &[], &mut buf).unwrap();
+        let n = encoder.encode(1, &[], &mut buf).unwrap();
+        assert_eq!(feed_frame(&mut decoder, &buf[..n]).unwrap().seq, 4);
+        assert_eq!(decoder.lost(), 2);
+    }
+}
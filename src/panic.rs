@@ -0,0 +1,146 @@
+//! Panic handler, enabled with the `panic-handler` feature
+//!
+//! On panic, interrupts are disabled and:
+//!
+//! - message and location are printed on the default stdout console
+//! - if `lcd::configure` was called, the LCD turns red and shows them too
+//! - the red LED (PC13) blinks three short flashes and a pause, forever
+//!
+//! Don't link `panic-halt` or another handler together with this feature. The
+//! examples only link it without the feature:
+//!
+//! ```ignore
+//! #[cfg(not(feature = "panic-handler"))]
+//! use panic_halt as _;
+//! ```
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+
+use embedded_graphics::fonts::{Font6x8, Text};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text_style;
+use riscv::interrupt;
+use riscv::register::mcycle;
+
+use crate::lcd::raw::RawLcd;
+use crate::pac::{GPIOC, RCU};
+use crate::stdout::{self, Stdout};
+use crate::ByteMutWriter;
+
+/// Font6x8 on 160x80
+const SCREEN_COLUMNS: usize = 160 / 6;
+const SCREEN_ROWS: usize = 80 / 8;
+
+/// RCU APB2EN bit of GPIOC
+const RCU_APB2EN_PCEN: u32 = 1 << 4;
+/// LED is PC13, active low
+const LED_PIN: u32 = 1 << 13;
+/// Blink unit, 1/8s at 108MHz, slower clocks just blink slower
+const BLINK_UNIT: u64 = 108_000_000 / 8;
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    unsafe { interrupt::disable() };
+
+    // interrupts are off, stdout falls back to polling
+    let _ = Stdout.write_str("\r\n!!! PANIC");
+    if let Some(location) = info.location() {
+        let _ = write!(Stdout, " at {}:{}", location.file(), location.line());
+    }
+    let _ = write!(Stdout, "\r\n{}\r\n", info.message());
+    stdout::flush();
+
+    if let Some(mut lcd) = unsafe { RawLcd::steal() } {
+        crash_screen(&mut lcd, info);
+    }
+
+    blink_forever()
+}
+
+fn crash_screen(lcd: &mut RawLcd, info: &PanicInfo) {
+    let mut buf = [0u8; SCREEN_COLUMNS * SCREEN_ROWS + SCREEN_ROWS];
+    let mut buf = ByteMutWriter::new(&mut buf[..]);
+    {
+        let mut text = Wrap {
+            inner: &mut buf,
+            column: 0,
+        };
+        let _ = write!(text, "PANIC");
+        if let Some(location) = info.location() {
+            let _ = write!(text, " {}:{}", location.file(), location.line());
+        }
+        let _ = write!(text, "\n{}", info.message());
+    }
+
+    lcd.fill(Rgb565::RED);
+    let style = text_style!(
+        font = Font6x8,
+        text_color = Rgb565::WHITE,
+        background_color = Rgb565::RED
+    );
+    let _ = Text::new(buf.as_str(), Point::new(0, 0))
+        .into_styled(style)
+        .draw(lcd);
+    lcd.wait_idle();
+}
+
+fn blink_forever() -> ! {
+    unsafe {
+        let rcu = &*RCU::ptr();
+        rcu.apb2en.modify(|r, w| w.bits(r.bits() | RCU_APB2EN_PCEN));
+        // PC13 push-pull output, 50MHz
+        let gpioc = &*GPIOC::ptr();
+        gpioc
+            .ctl1
+            .modify(|r, w| w.bits((r.bits() & !(0xf << 20)) | (0b0011 << 20)));
+    }
+
+    let gpioc = unsafe { &*GPIOC::ptr() };
+    let led = |on: bool| {
+        if on {
+            gpioc.bc.write(|w| unsafe { w.bits(LED_PIN) });
+        } else {
+            gpioc.bop.write(|w| unsafe { w.bits(LED_PIN) });
+        }
+    };
+
+    loop {
+        for _ in 0..3 {
+            led(true);
+            wait(1);
+            led(false);
+            wait(2);
+        }
+        wait(12);
+    }
+}
+
+fn wait(units: u64) {
+    let start = mcycle::read64();
+    while mcycle::read64().wrapping_sub(start) < units * BLINK_UNIT {}
+}
+
+/// Inserts line breaks every `SCREEN_COLUMNS` chars
+struct Wrap<'a, W: Write> {
+    inner: &'a mut W,
+    column: usize,
+}
+
+impl<W: Write> Write for Wrap<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if c == '\n' {
+                self.column = 0;
+            } else if self.column == SCREEN_COLUMNS {
+                self.inner.write_char('\n')?;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.inner.write_char(c)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Fixed-capacity byte ring buffer
+//!
+//! Usable from `static` items (`new` is a `const fn`), so it can be shared
+//! between an interrupt handler and the main loop inside `interrupt::free`.
+
+/// A FIFO of at most `N - 1` bytes.
+pub struct RingBuffer<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    tail: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    /// Creates an empty buffer
+    pub const fn new() -> Self {
+        RingBuffer {
+            buf: [0; N],
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// Maximum number of bytes the buffer can hold
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Number of bytes currently stored
+    pub fn len(&self) -> usize {
+        (self.head + N - self.tail) % N
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    pub fn is_full(&self) -> bool {
+        (self.head + 1) % N == self.tail
+    }
+
+    /// Appends a byte, returns `false` when the buffer is full
+    pub fn push(&mut self, b: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[self.head] = b;
+        self.head = (self.head + 1) % N;
+        true
+    }
+
+    /// Appends a byte, dropping the oldest one when the buffer is full
+    pub fn push_overwrite(&mut self, b: u8) {
+        if self.is_full() {
+            self.tail = (self.tail + 1) % N;
+        }
+        self.push(b);
+    }
+
+    /// Removes the oldest byte
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let b = self.buf[self.tail];
+        self.tail = (self.tail + 1) % N;
+        Some(b)
+    }
+
+    /// Returns the oldest byte without removing it
+    pub fn peek(&self) -> Option<u8> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.buf[self.tail])
+        }
+    }
+
+    /// Moves as many bytes as fit into `buf`, returns the count
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < buf.len() {
+            match self.pop() {
+                Some(b) => {
+                    buf[n] = b;
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        n
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.tail = 0;
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,227 @@
+//! Board commands, and a shell loop on stdout
+//!
+//! ```ignore
+//! stdout::configure(dp.USART0, gpioa.pa9, gpioa.pa10, 115200.bps(), &mut afio, &mut rcu);
+//!
+//! let mut board = Board::new();
+//! board.adc = Some(adc.enable());
+//! board.rtc = Some(Rtc::rtc(dp.RTC, &mut bak_dom));
+//!
+//! shell::board::run(&mut board, COMMANDS)
+//! ```
+//!
+//! `COMMANDS` can be extended by copying it into a longer table.
+
+use core::fmt::Write;
+
+use gd32vf103xx_hal::rtc::Rtc;
+use riscv::register::{marchid, mhartid, mimpid, misa, mvendorid};
+
+use super::{Args, Command, Error, Shell};
+use crate::adc::{self, Adc, Enabled};
+use crate::pac::{gpioa, ADC0, GPIOA, GPIOB, GPIOC, GPIOD, GPIOE};
+use crate::stdout::{self, Stdout};
+
+/// Timer unit of the core, MSFTRST resets the SoC when written with the key
+const TIMER_MSFTRST: usize = 0xd100_0ff0;
+const MSFTRST_KEY: u32 = 0x8000_0a5f;
+
+/// Peripherals used by the board commands, `None` ones are unavailable
+pub struct Board {
+    pub adc: Option<Adc<ADC0, Enabled>>,
+    pub rtc: Option<Rtc>,
+}
+
+impl Board {
+    pub const fn new() -> Self {
+        Board {
+            adc: None,
+            rtc: None,
+        }
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Built-in board commands
+pub const COMMANDS: &[Command<Board>] = &[
+    Command::new("peek", "<addr> [words]", "read memory words", peek),
+    Command::new("poke", "<addr> <value>", "write a memory word", poke),
+    Command::new("adc", "read <channel>", "convert an ADC0 channel", adc),
+    Command::new(
+        "gpio",
+        "get <pin> | set <pin> <0|1>",
+        "read or drive a pin, e.g. pc13",
+        gpio,
+    ),
+    Command::new("rtc", "get | set <seconds>", "RTC counter", rtc),
+    Command::new("cpuinfo", "", "machine information CSRs", cpuinfo),
+    Command::new("reset", "", "reset the board", reset),
+];
+
+/// Serves `commands` on the default stdout console, forever
+pub fn run<C>(ctx: &mut C, commands: &[Command<C>]) -> ! {
+    let mut shell = Shell::new(commands);
+    shell.prompt(&mut Stdout);
+    loop {
+        if let Some(b) = stdout::read_byte() {
+            shell.feed(ctx, b, &mut Stdout);
+        }
+    }
+}
+
+/// Word aligned address argument
+fn address(args: &mut Args) -> Result<usize, Error> {
+    let addr = args.number()?;
+    if addr % 4 != 0 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(addr as usize)
+}
+
+fn peek(_: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    let addr = address(args)?;
+    let words = args.number_or(1)? as usize;
+    args.finish()?;
+    for row in (0..words).step_by(4) {
+        write!(out, "{:08x}:", addr + row * 4)?;
+        for i in row..usize::min(row + 4, words) {
+            let value = unsafe { core::ptr::read_volatile((addr + i * 4) as *const u32) };
+            write!(out, " {:08x}", value)?;
+        }
+        writeln!(out, "\r")?;
+    }
+    Ok(())
+}
+
+fn poke(_: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    let addr = address(args)?;
+    let value = args.number()?;
+    args.finish()?;
+    unsafe { core::ptr::write_volatile(addr as *mut u32, value) };
+    writeln!(out, "{:08x} <- {:08x}\r", addr, value)?;
+    Ok(())
+}
+
+fn adc(board: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    if args.required()? != "read" {
+        return Err(Error::InvalidArgument);
+    }
+    let channel = args.number()?;
+    args.finish()?;
+    // 0-15 external, 16 temperature, 17 vrefint
+    if channel > 17 {
+        return Err(Error::InvalidArgument);
+    }
+    let adc = board.adc.as_mut().ok_or(Error::Unavailable)?;
+    let value = adc.read_channel(channel as u8);
+    writeln!(out, "{} ({} mV)\r", value, adc::to_millivolts(value))?;
+    Ok(())
+}
+
+/// `pa0`..`pe15`
+fn pin(name: &str) -> Result<(&'static gpioa::RegisterBlock, u32), Error> {
+    let name = name.as_bytes();
+    if name.len() < 3 || !name[0].eq_ignore_ascii_case(&b'p') {
+        return Err(Error::InvalidArgument);
+    }
+    let port = unsafe {
+        match name[1].to_ascii_lowercase() {
+            b'a' => &*GPIOA::ptr(),
+            b'b' => &*GPIOB::ptr(),
+            b'c' => &*GPIOC::ptr(),
+            b'd' => &*GPIOD::ptr(),
+            b'e' => &*GPIOE::ptr(),
+            _ => return Err(Error::InvalidArgument),
+        }
+    };
+    let pin = core::str::from_utf8(&name[2..])
+        .ok()
+        .and_then(super::parse_number)
+        .filter(|n| *n < 16)
+        .ok_or(Error::InvalidArgument)?;
+    Ok((port, pin))
+}
+
+fn gpio(_: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    match args.required()? {
+        "get" => {
+            let (port, pin) = pin(args.required()?)?;
+            args.finish()?;
+            let level = (port.istat.read().bits() >> pin) & 1;
+            writeln!(out, "{}\r", level)?;
+        }
+        "set" => {
+            let (port, pin) = pin(args.required()?)?;
+            let level = args.number()?;
+            args.finish()?;
+            // the pin must be an output already
+            match level {
+                0 => port.bc.write(|w| unsafe { w.bits(1 << pin) }),
+                1 => port.bop.write(|w| unsafe { w.bits(1 << pin) }),
+                _ => return Err(Error::InvalidArgument),
+            }
+        }
+        _ => return Err(Error::InvalidArgument),
+    }
+    Ok(())
+}
+
+fn rtc(board: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    let command = args.required()?;
+    let rtc = board.rtc.as_mut().ok_or(Error::Unavailable)?;
+    match command {
+        "get" => {
+            args.finish()?;
+            let t = rtc.current_time();
+            writeln!(
+                out,
+                "{} ({}:{:02}:{:02})\r",
+                t,
+                t / 3600,
+                t % 3600 / 60,
+                t % 60
+            )?;
+        }
+        "set" => {
+            let t = args.number()?;
+            args.finish()?;
+            rtc.set_time(t);
+        }
+        _ => return Err(Error::InvalidArgument),
+    }
+    Ok(())
+}
+
+fn cpuinfo(_: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    args.finish()?;
+    let misa = misa::read().map_or(0, |r| r.bits());
+    writeln!(out, "misa:      {:08x}\r", misa)?;
+    write!(out, "           RV32")?;
+    for (i, c) in (b'A'..=b'Z').enumerate() {
+        if misa & (1 << i) != 0 {
+            write!(out, "{}", c as char)?;
+        }
+    }
+    writeln!(out, "\r")?;
+    let mvendorid = mvendorid::read().map_or(0, |r| r.bits());
+    writeln!(out, "mvendorid: {:08x}\r", mvendorid)?;
+    let marchid = marchid::read().map_or(0, |r| r.bits());
+    writeln!(out, "marchid:   {:08x}\r", marchid)?;
+    let mimpid = mimpid::read().map_or(0, |r| r.bits());
+    writeln!(out, "mimpid:    {:08x}\r", mimpid)?;
+    writeln!(out, "mhartid:   {:08x}\r", mhartid::read())?;
+    Ok(())
+}
+
+fn reset(_: &mut Board, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+    args.finish()?;
+    writeln!(out, "resetting\r")?;
+    stdout::flush();
+    unsafe { core::ptr::write_volatile(TIMER_MSFTRST as *mut u32, MSFTRST_KEY) };
+    loop {}
+}
@@ -0,0 +1,411 @@
+//! Line editor with history, for VT100 compatible terminals
+//!
+//! Keys: Left/Right, Home/End (or Ctrl-A/Ctrl-E), Backspace, Delete,
+//! Ctrl-U clears the line, Ctrl-C discards it, Up/Down walk the history.
+
+use core::fmt::{self, Write};
+use core::str;
+
+/// Longest line, longer input is dropped
+pub const LINE_CAPACITY: usize = 96;
+/// Number of lines kept in the history
+pub const HISTORY_CAPACITY: usize = 8;
+
+const CTRL_A: u8 = 0x01;
+const CTRL_C: u8 = 0x03;
+const CTRL_E: u8 = 0x05;
+const CTRL_U: u8 = 0x15;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+const ESC: u8 = 0x1b;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    /// after ESC
+    Esc,
+    /// after `ESC [`, with the numeric parameter so far
+    Csi(u8),
+}
+
+#[derive(Clone, Copy)]
+struct Line {
+    buf: [u8; LINE_CAPACITY],
+    len: usize,
+}
+
+impl Line {
+    const EMPTY: Line = Line {
+        buf: [0; LINE_CAPACITY],
+        len: 0,
+    };
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Line editor, fed one received byte at a time
+pub struct Editor {
+    line: Line,
+    cursor: usize,
+    escape: Escape,
+    /// Swallows the '\n' of "\r\n"
+    last_cr: bool,
+    /// The line is complete, and is cleared on the next byte
+    done: bool,
+    /// Ring of previous lines, newest at `history_head - 1`
+    history: [Line; HISTORY_CAPACITY],
+    history_head: usize,
+    history_len: usize,
+    /// Entries back in history being shown, 0 is the line being edited
+    browsing: usize,
+    /// The line being edited while browsing
+    scratch: Line,
+}
+
+impl Editor {
+    pub const fn new() -> Self {
+        Editor {
+            line: Line::EMPTY,
+            cursor: 0,
+            escape: Escape::None,
+            last_cr: false,
+            done: false,
+            history: [Line::EMPTY; HISTORY_CAPACITY],
+            history_head: 0,
+            history_len: 0,
+            browsing: 0,
+            scratch: Line::EMPTY,
+        }
+    }
+
+    /// The line so far, or the complete line after `feed` returned true
+    pub fn line(&self) -> &str {
+        // only ASCII is inserted
+        str::from_utf8(self.line.as_bytes()).unwrap_or("")
+    }
+
+    /// Number of lines in the history
+    pub fn history_len(&self) -> usize {
+        self.history_len
+    }
+
+    /// A history entry, 0 is the newest
+    pub fn history(&self, back: usize) -> Option<&str> {
+        if back >= self.history_len {
+            return None;
+        }
+        let i = (self.history_head + HISTORY_CAPACITY - 1 - back) % HISTORY_CAPACITY;
+        str::from_utf8(self.history[i].as_bytes()).ok()
+    }
+
+    /// Handles one input byte, echoing to `out`. Returns true on Enter.
+    pub fn feed(&mut self, b: u8, out: &mut dyn Write) -> bool {
+        if self.done {
+            self.done = false;
+            self.line.len = 0;
+            self.cursor = 0;
+        }
+
+        match self.escape {
+            Escape::Esc => {
+                self.escape = if b == b'[' || b == b'O' {
+                    Escape::Csi(0)
+                } else {
+                    Escape::None
+                };
+                return false;
+            }
+            Escape::Csi(param) => {
+                if b.is_ascii_digit() {
+                    self.escape = Escape::Csi(param.saturating_mul(10).saturating_add(b - b'0'));
+                } else {
+                    self.escape = Escape::None;
+                    let _ = self.escape_key(b, param, out);
+                }
+                return false;
+            }
+            Escape::None => {}
+        }
+
+        let last_cr = core::mem::replace(&mut self.last_cr, false);
+        let _ = match b {
+            b'\n' if last_cr => return false,
+            b'\r' | b'\n' => {
+                self.last_cr = b == b'\r';
+                let _ = out.write_str("\r\n");
+                self.commit();
+                return true;
+            }
+            ESC => {
+                self.escape = Escape::Esc;
+                Ok(())
+            }
+            BACKSPACE | DELETE => self.backspace(out),
+            CTRL_A => self.move_to(0, out),
+            CTRL_E => self.move_to(self.line.len, out),
+            CTRL_C => {
+                self.line.len = 0;
+                self.cursor = 0;
+                self.browsing = 0;
+                let _ = out.write_str("^C\r\n");
+                self.done = true;
+                return true;
+            }
+            CTRL_U => self.replace(&[], out),
+            0x20..=0x7e => self.insert(b, out),
+            _ => Ok(()),
+        };
+        false
+    }
+
+    fn escape_key(&mut self, key: u8, param: u8, out: &mut dyn Write) -> fmt::Result {
+        match (key, param) {
+            (b'A', _) => self.history_back(out),
+            (b'B', _) => self.history_forward(out),
+            (b'C', _) if self.cursor < self.line.len => {
+                self.cursor += 1;
+                out.write_str("\x1b[C")
+            }
+            (b'D', _) if self.cursor > 0 => {
+                self.cursor -= 1;
+                out.write_char(BACKSPACE as char)
+            }
+            (b'H', _) | (b'~', 1) => self.move_to(0, out),
+            (b'F', _) | (b'~', 4) => self.move_to(self.line.len, out),
+            (b'~', 3) => self.delete(out),
+            _ => Ok(()),
+        }
+    }
+
+    fn insert(&mut self, b: u8, out: &mut dyn Write) -> fmt::Result {
+        if self.line.len == LINE_CAPACITY {
+            return Ok(());
+        }
+        self.line
+            .buf
+            .copy_within(self.cursor..self.line.len, self.cursor + 1);
+        self.line.buf[self.cursor] = b;
+        self.line.len += 1;
+        self.cursor += 1;
+        out.write_char(b as char)?;
+        self.redraw_tail(0, out)
+    }
+
+    fn backspace(&mut self, out: &mut dyn Write) -> fmt::Result {
+        if self.cursor == 0 {
+            return Ok(());
+        }
+        self.cursor -= 1;
+        out.write_char(BACKSPACE as char)?;
+        self.delete(out)
+    }
+
+    /// Deletes the char under the cursor
+    fn delete(&mut self, out: &mut dyn Write) -> fmt::Result {
+        if self.cursor == self.line.len {
+            return Ok(());
+        }
+        self.line
+            .buf
+            .copy_within(self.cursor + 1..self.line.len, self.cursor);
+        self.line.len -= 1;
+        self.redraw_tail(1, out)
+    }
+
+    /// Rewrites the line from the cursor, blanking `erase` chars after it,
+    /// then moves back to the cursor
+    fn redraw_tail(&self, erase: usize, out: &mut dyn Write) -> fmt::Result {
+        let tail = &self.line.buf[self.cursor..self.line.len];
+        for &b in tail {
+            out.write_char(b as char)?;
+        }
+        for _ in 0..erase {
+            out.write_char(' ')?;
+        }
+        for _ in 0..tail.len() + erase {
+            out.write_char(BACKSPACE as char)?;
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, pos: usize, out: &mut dyn Write) -> fmt::Result {
+        while self.cursor > pos {
+            self.cursor -= 1;
+            out.write_char(BACKSPACE as char)?;
+        }
+        while self.cursor < pos {
+            out.write_char(self.line.buf[self.cursor] as char)?;
+            self.cursor += 1;
+        }
+        Ok(())
+    }
+
+    /// Replaces the whole line, e.g. with a history entry
+    fn replace(&mut self, new: &[u8], out: &mut dyn Write) -> fmt::Result {
+        self.move_to(0, out)?;
+        let old_len = self.line.len;
+        self.line.buf[..new.len()].copy_from_slice(new);
+        self.line.len = new.len();
+        self.redraw_tail(old_len.saturating_sub(new.len()), out)?;
+        self.move_to(self.line.len, out)
+    }
+
+    fn history_back(&mut self, out: &mut dyn Write) -> fmt::Result {
+        if self.browsing == self.history_len {
+            return Ok(());
+        }
+        if self.browsing == 0 {
+            self.scratch = self.line;
+        }
+        self.browsing += 1;
+        self.show_history(out)
+    }
+
+    fn history_forward(&mut self, out: &mut dyn Write) -> fmt::Result {
+        if self.browsing == 0 {
+            return Ok(());
+        }
+        self.browsing -= 1;
+        self.show_history(out)
+    }
+
+    fn show_history(&mut self, out: &mut dyn Write) -> fmt::Result {
+        let entry = if self.browsing == 0 {
+            self.scratch
+        } else {
+            let i = (self.history_head + HISTORY_CAPACITY - self.browsing) % HISTORY_CAPACITY;
+            self.history[i]
+        };
+        self.replace(entry.as_bytes(), out)
+    }
+
+    /// Completes the line, and adds it to the history
+    fn commit(&mut self) {
+        self.done = true;
+        self.browsing = 0;
+        let line = self.line.as_bytes();
+        if line.iter().all(|b| *b == b' ') || self.history(0).map(str::as_bytes) == Some(line) {
+            return;
+        }
+        self.history[self.history_head] = self.line;
+        self.history_head = (self.history_head + 1) % HISTORY_CAPACITY;
+        self.history_len = usize::min(self.history_len + 1, HISTORY_CAPACITY);
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Discards the echo
+    struct Sink;
+
+    impl Write for Sink {
+        fn write_str(&mut self, _: &str) -> fmt::Result {
+            Ok(())
+        }
+    }
+
+    const UP: &[u8] = b"\x1b[A";
+    const DOWN: &[u8] = b"\x1b[B";
+    const LEFT: &[u8] = b"\x1b[D";
+
+    /// Feeds `input`, returning the last completed line
+    fn feed<'e>(editor: &'e mut Editor, input: &[u8]) -> Option<&'e str> {
+        let mut done = false;
+        for &b in input {
+            done = editor.feed(b, &mut Sink);
+        }
+        if done {
+            Some(editor.line())
+        } else {
+            None
+        }
+    }
+
+    fn enter(editor: &mut Editor, line: &str) {
+        feed(editor, line.as_bytes());
+        assert_eq!(feed(editor, b"\r"), Some(line));
+    }
+
+    #[test]
+    fn edits_lines() {
+        let mut editor = Editor::new();
+        assert_eq!(feed(&mut editor, b"pek"), None);
+        feed(&mut editor, LEFT);
+        assert_eq!(feed(&mut editor, b"e\r"), Some("peek"));
+
+        // the \n of \r\n does not complete another line
+        assert_eq!(feed(&mut editor, b"\n"), None);
+        assert_eq!(feed(&mut editor, b"ab\x08c\x1b[H\x1b[3~z\n"), Some("zc"));
+        assert_eq!(feed(&mut editor, b"abc\x15d\r"), Some("d"));
+        assert_eq!(feed(&mut editor, b"abc\x03"), Some(""));
+
+        let long = [b'a'; LINE_CAPACITY + 4];
+        feed(&mut editor, &long);
+        assert_eq!(editor.line().len(), LINE_CAPACITY);
+    }
+
+    #[test]
+    fn keeps_history() {
+        let mut editor = Editor::new();
+        enter(&mut editor, "one");
+        enter(&mut editor, "two");
+        // blank lines and repeats are not added
+        enter(&mut editor, "  ");
+        enter(&mut editor, "two");
+        assert_eq!(editor.history_len(), 2);
+        assert_eq!(editor.history(0), Some("two"));
+        assert_eq!(editor.history(1), Some("one"));
+        assert_eq!(editor.history(2), None);
+
+        // up past the oldest entry stays there
+        feed(&mut editor, b"th");
+        for _ in 0..3 {
+            feed(&mut editor, UP);
+        }
+        assert_eq!(editor.line(), "one");
+        feed(&mut editor, DOWN);
+        assert_eq!(editor.line(), "two");
+        // back down to the line being edited
+        feed(&mut editor, DOWN);
+        feed(&mut editor, DOWN);
+        assert_eq!(feed(&mut editor, b"ree\r"), Some("three"));
+
+        // a recalled line can be edited
+        feed(&mut editor, UP);
+        feed(&mut editor, UP);
+        assert_eq!(feed(&mut editor, b"!\r"), Some("two!"));
+        assert_eq!(editor.history(0), Some("two!"));
+        assert_eq!(editor.history(1), Some("three"));
+    }
+
+    #[test]
+    fn drops_oldest_entries() {
+        let mut editor = Editor::new();
+        let names = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"];
+        for name in &names {
+            enter(&mut editor, name);
+        }
+        assert_eq!(editor.history_len(), HISTORY_CAPACITY);
+        assert_eq!(editor.history(0), Some("j"));
+        assert_eq!(editor.history(HISTORY_CAPACITY - 1), Some("c"));
+
+        for _ in 0..HISTORY_CAPACITY + 1 {
+            feed(&mut editor, UP);
+        }
+        assert_eq!(editor.line(), "c");
+        // Ctrl-C stops browsing
+        feed(&mut editor, b"\x03");
+        feed(&mut editor, UP);
+        assert_eq!(editor.line(), "j");
+    }
+}
@@ -0,0 +1,485 @@
+//! Interactive command shell
+//!
+//! A `Shell` reads bytes from a terminal, edits the line with history, and
+//! dispatches it to a table of commands. Each command gets a context `C`
+//! holding whatever peripherals it needs:
+//!
+//! ```ignore
+//! fn hello(_: &mut (), args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+//!     let name = args.next().unwrap_or("world");
+//!     args.finish()?;
+//!     writeln!(out, "hello {}", name)?;
+//!     Ok(())
+//! }
+//!
+//! const COMMANDS: &[Command<()>] = &[Command::new("hello", "[name]", "say hello", hello)];
+//!
+//! let mut shell = Shell::new(COMMANDS);
+//! shell.execute(&mut (), "hello nano", &mut out);
+//! ```
+//!
+//! `help` is always available. The board commands (peek/poke, adc, gpio, rtc,
+//! cpuinfo, reset) are in the `board` module, `board::run` serves them on
+//! stdout. Everything else here is plain logic, usable on any target.
+
+use core::fmt::{self, Write};
+
+mod editor;
+
+#[cfg(target_arch = "riscv32")]
+pub mod board;
+
+pub use self::editor::{Editor, HISTORY_CAPACITY, LINE_CAPACITY};
+
+/// Printed before each line
+pub const PROMPT: &str = "> ";
+
+/// Command errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// No command with this name
+    UnknownCommand,
+    /// An argument is missing
+    MissingArgument,
+    /// An argument could not be parsed, or is out of range
+    InvalidArgument,
+    /// More arguments than the command takes
+    TooManyArguments,
+    /// The peripheral the command needs is not available
+    Unavailable,
+    /// Writing the output failed
+    Output,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Error::Output
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Error::UnknownCommand => "unknown command, try help",
+            Error::MissingArgument => "missing argument",
+            Error::InvalidArgument => "invalid argument",
+            Error::TooManyArguments => "too many arguments",
+            Error::Unavailable => "not available",
+            Error::Output => "output error",
+        })
+    }
+}
+
+/// Command handler
+pub type Handler<C> = fn(&mut C, &mut Args, &mut dyn Write) -> Result<(), Error>;
+
+/// Command table entry
+pub struct Command<C> {
+    pub name: &'static str,
+    /// Arguments, for help
+    pub usage: &'static str,
+    /// One line description, for help
+    pub help: &'static str,
+    pub handler: Handler<C>,
+}
+
+impl<C> Command<C> {
+    pub const fn new(
+        name: &'static str,
+        usage: &'static str,
+        help: &'static str,
+        handler: Handler<C>,
+    ) -> Self {
+        Command {
+            name,
+            usage,
+            help,
+            handler,
+        }
+    }
+}
+
+/// Whitespace separated arguments of a command line
+///
+/// An argument in double quotes may contain whitespace, `"a b"` is `a b`. An
+/// unterminated quote runs to the end of the line.
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(line: &'a str) -> Self {
+        Args { rest: line }
+    }
+
+    /// The next argument, which must be present
+    pub fn required(&mut self) -> Result<&'a str, Error> {
+        self.next().ok_or(Error::MissingArgument)
+    }
+
+    /// The next argument as a number, decimal, `0x` hex or `0b` binary
+    pub fn number(&mut self) -> Result<u32, Error> {
+        parse_number(self.required()?).ok_or(Error::InvalidArgument)
+    }
+
+    /// Optional number argument
+    pub fn number_or(&mut self, default: u32) -> Result<u32, Error> {
+        match self.next() {
+            Some(arg) => parse_number(arg).ok_or(Error::InvalidArgument),
+            None => Ok(default),
+        }
+    }
+
+    /// Everything not taken yet, trimmed
+    pub fn rest(&self) -> &'a str {
+        self.rest.trim()
+    }
+
+    /// Checks there are no arguments left
+    pub fn finish(&mut self) -> Result<(), Error> {
+        match self.next() {
+            Some(_) => Err(Error::TooManyArguments),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = &'a str;
+
+    /// The next argument
+    fn next(&mut self) -> Option<&'a str> {
+        let s = self.rest.trim_start();
+        if s.is_empty() {
+            self.rest = s;
+            return None;
+        }
+        if let Some(quoted) = s.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            self.rest = quoted.get(end + 1..).unwrap_or("");
+            return Some(&quoted[..end]);
+        }
+        let end = s.find(char::is_whitespace).unwrap_or(s.len());
+        self.rest = &s[end..];
+        Some(&s[..end])
+    }
+}
+
+/// Parses decimal, `0x` hex or `0b` binary, `_` separators allowed
+pub fn parse_number(s: &str) -> Option<u32> {
+    let (digits, radix) = match s.get(..2) {
+        Some("0x") | Some("0X") => (&s[2..], 16),
+        Some("0b") | Some("0B") => (&s[2..], 2),
+        _ => (s, 10),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut value: u32 = 0;
+    for c in digits.chars().filter(|c| *c != '_') {
+        let digit = c.to_digit(radix)?;
+        value = value.checked_mul(radix)?.checked_add(digit)?;
+    }
+    Some(value)
+}
+
+/// Command table with a line editor
+pub struct Shell<'t, C> {
+    commands: &'t [Command<C>],
+    editor: Editor,
+}
+
+impl<'t, C> Shell<'t, C> {
+    pub const fn new(commands: &'t [Command<C>]) -> Self {
+        Shell {
+            commands,
+            editor: Editor::new(),
+        }
+    }
+
+    pub fn commands(&self) -> &'t [Command<C>] {
+        self.commands
+    }
+
+    pub fn editor(&self) -> &Editor {
+        &self.editor
+    }
+
+    /// Prints the prompt
+    pub fn prompt(&self, out: &mut dyn Write) {
+        let _ = out.write_str(PROMPT);
+    }
+
+    /// Handles one input byte. On Enter the line is executed, errors are
+    /// printed, and the prompt is shown again.
+    pub fn feed(&mut self, ctx: &mut C, b: u8, out: &mut dyn Write) {
+        if !self.editor.feed(b, out) {
+            return;
+        }
+        if let Err(e) = self.execute(ctx, self.editor.line(), out) {
+            let _ = writeln!(out, "error: {}\r", e);
+        }
+        self.prompt(out);
+    }
+
+    /// Runs a command line
+    pub fn execute(&self, ctx: &mut C, line: &str, out: &mut dyn Write) -> Result<(), Error> {
+        let mut args = Args::new(line);
+        let name = match args.next() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        if name == "help" {
+            return self.help(&mut args, out);
+        }
+        let command = self
+            .commands
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or(Error::UnknownCommand)?;
+        (command.handler)(ctx, &mut args, out)
+    }
+
+    /// `help [command]`
+    fn help(&self, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+        let filter = args.next();
+        args.finish()?;
+        let mut found = false;
+        let selected = self.commands.iter().filter(|c| match filter {
+            Some(name) => name == c.name,
+            None => true,
+        });
+        for c in selected {
+            writeln!(out, "{} {}\r", c.name, c.usage)?;
+            writeln!(out, "    {}\r", c.help)?;
+            found = true;
+        }
+        match (filter, found) {
+            (None, _) => writeln!(out, "help [command]\r\n    show this\r")?,
+            (Some(_), false) => return Err(Error::UnknownCommand),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output collected in a fixed buffer, without alloc
+    struct Out {
+        buf: [u8; 512],
+        len: usize,
+    }
+
+    impl Out {
+        fn new() -> Self {
+            Out {
+                buf: [0; 512],
+                len: 0,
+            }
+        }
+
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.buf[..self.len]).unwrap()
+        }
+    }
+
+    impl Write for Out {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.buf
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    /// Adds its two arguments into the context
+    fn add(sum: &mut u32, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+        let a = args.number()?;
+        let b = args.number_or(1)?;
+        args.finish()?;
+        *sum = a.checked_add(b).ok_or(Error::InvalidArgument)?;
+        write!(out, "{}", sum)?;
+        Ok(())
+    }
+
+    fn echo(_: &mut u32, args: &mut Args, out: &mut dyn Write) -> Result<(), Error> {
+        let first = args.required()?;
+        write!(out, "[{}][{}]", first, args.rest())?;
+        Ok(())
+    }
+
+    const COMMANDS: &[Command<u32>] = &[
+        Command::new("add", "<a> [b]", "add numbers", add),
+        Command::new("echo", "<arg> [rest]", "print arguments", echo),
+    ];
+
+    fn args(line: &str) -> ([&str; 4], usize) {
+        let mut out = [""; 4];
+        let mut n = 0;
+        for arg in Args::new(line) {
+            out[n] = arg;
+            n += 1;
+        }
+        (out, n)
+    }
+
+    #[test]
+    fn tokenizes() {
+        assert_eq!(
+            args("  peek\t0x4001_0800   4 "),
+            (["peek", "0x4001_0800", "4", ""], 3)
+        );
+        assert_eq!(args(""), ([""; 4], 0));
+        assert_eq!(args(" \t "), ([""; 4], 0));
+
+        let mut args = Args::new("gpio set  a13 1");
+        assert_eq!(args.next(), Some("gpio"));
+        assert_eq!(args.rest(), "set  a13 1");
+        assert_eq!(args.required(), Ok("set"));
+        assert_eq!(args.finish(), Err(Error::TooManyArguments));
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(args(r#"echo "a b"  c"#), (["echo", "a b", "c", ""], 3));
+        assert_eq!(args(r#""" x"#), (["", "x", "", ""], 2));
+        assert_eq!(args(r#"x"y z"#), (["x\"y", "z", "", ""], 2));
+        // runs to the end of the line
+        assert_eq!(args(r#"echo "a  b "#), (["echo", "a  b ", "", ""], 2));
+        assert_eq!(args(r#"a "b"c"#), (["a", "b", "c", ""], 3));
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("0x4001_0800"), Some(0x4001_0800));
+        assert_eq!(parse_number("0XFF"), Some(255));
+        assert_eq!(parse_number("0b1010"), Some(10));
+        assert_eq!(parse_number("4294967295"), Some(u32::MAX));
+        assert_eq!(parse_number("4294967296"), None);
+        assert_eq!(parse_number("0x"), None);
+        assert_eq!(parse_number("-1"), None);
+        assert_eq!(parse_number("12a"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn dispatches() {
+        let shell = Shell::new(COMMANDS);
+        let mut sum = 0;
+        let mut out = Out::new();
+        assert_eq!(shell.execute(&mut sum, "add 2 0x10", &mut out), Ok(()));
+        assert_eq!((sum, out.as_str()), (18, "18"));
+
+        let mut out = Out::new();
+        assert_eq!(
+            shell.execute(&mut sum, r#"echo "one two" three  four"#, &mut out),
+            Ok(())
+        );
+        assert_eq!(out.as_str(), "[one two][three  four]");
+
+        // blank lines do nothing
+        let mut out = Out::new();
+        assert_eq!(shell.execute(&mut sum, "   ", &mut out), Ok(()));
+        assert_eq!(out.as_str(), "");
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let shell = Shell::new(COMMANDS);
+        let mut out = Out::new();
+        assert_eq!(
+            shell.execute(&mut 0, "sub 1 2", &mut out),
+            Err(Error::UnknownCommand)
+        );
+        // names are matched whole and case sensitive
+        assert_eq!(
+            shell.execute(&mut 0, "ad 1", &mut out),
+            Err(Error::UnknownCommand)
+        );
+        assert_eq!(
+            shell.execute(&mut 0, "ADD 1", &mut out),
+            Err(Error::UnknownCommand)
+        );
+        assert_eq!(
+            shell.execute(&mut 0, "help sub", &mut out),
+            Err(Error::UnknownCommand)
+        );
+        assert_eq!(out.as_str(), "");
+    }
+
+    #[test]
+    fn argument_errors() {
+        let shell = Shell::new(COMMANDS);
+        let mut sum = 7;
+        let mut out = Out::new();
+        assert_eq!(
+            shell.execute(&mut sum, "add", &mut out),
+            Err(Error::MissingArgument)
+        );
+        assert_eq!(
+            shell.execute(&mut sum, "add x", &mut out),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            shell.execute(&mut sum, "add 1 0b2", &mut out),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            shell.execute(&mut sum, "add 1 2 3", &mut out),
+            Err(Error::TooManyArguments)
+        );
+        assert_eq!(
+            shell.execute(&mut sum, "add 0xffffffff", &mut out),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            shell.execute(&mut sum, "echo", &mut out),
+            Err(Error::MissingArgument)
+        );
+        assert_eq!(
+            shell.execute(&mut sum, "help add x", &mut out),
+            Err(Error::TooManyArguments)
+        );
+        assert_eq!((sum, out.as_str()), (7, ""));
+    }
+
+    #[test]
+    fn help() {
+        let shell = Shell::new(COMMANDS);
+        let mut out = Out::new();
+        shell.execute(&mut 0, "help echo", &mut out).unwrap();
+        assert_eq!(out.as_str(), "echo <arg> [rest]\r\n    print arguments\r\n");
+
+        let mut out = Out::new();
+        shell.execute(&mut 0, "help", &mut out).unwrap();
+        assert!(out
+            .as_str()
+            .starts_with("add <a> [b]\r\n    add numbers\r\necho"));
+        assert!(out
+            .as_str()
+            .ends_with("help [command]\r\n    show this\r\n"));
+    }
+
+    #[test]
+    fn feeds_lines() {
+        let mut shell = Shell::new(COMMANDS);
+        let mut sum = 0;
+        let mut out = Out::new();
+        for &b in b"add 40 2\r\nnope\r" {
+            shell.feed(&mut sum, b, &mut out);
+        }
+        assert_eq!(sum, 42);
+        assert_eq!(
+            out.as_str(),
+            "add 40 2\r\n42> nope\r\nerror: unknown command, try help\r\n> "
+        );
+        assert_eq!(shell.editor().history(0), Some("nope"));
+    }
+}
@@ -1,47 +1,568 @@
 //! Stdout based on the UART hooked up to the debug connector
+//!
+//! Any of USART0 (PA9/PA10, or remapped PB6/PB7), USART1 (PA2/PA3) and USART2
+//! (PB10/PB11) can be opened as a `Console`. One of them is the default console
+//! `sprintln!` writes to, set by `configure` or `set_default`:
+//!
+//! ```ignore
+//! // debug connector, default console
+//! stdout::configure(dp.USART0, gpioa.pa9, gpioa.pa10, 115200.bps(), &mut afio, &mut rcu);
+//!
+//! // ESP module on USART2, next to it
+//! let mut esp = stdout::open(dp.USART2, (gpiob.pb10, gpiob.pb11), 115200.bps(), &mut afio, &mut rcu);
+//! writeln!(esp, "AT").unwrap();
+//! ```
+//!
+//! Output is queued in a transmit buffer and sent by the USART TBE interrupt,
+//! so `sprintln!` returns without waiting for the UART. Input is line-buffered:
+//! received bytes are stored by the USART interrupt. Call `handle_interrupt`
+//! of each console from its handler:
+//!
+//! ```ignore
+//! ECLIC::setup(Interrupt::USART0, TriggerType::Level, Level::L1, Priority::P1);
+//! unsafe { ECLIC::unmask(Interrupt::USART0) };
+//!
+//! #[no_mangle]
+//! fn USART0() {
+//!     stdout::handle_interrupt();
+//! }
+//! ```
+//!
+//! While the USART interrupt is not unmasked, or interrupts are disabled
+//! (e.g. in a trap handler), output falls back to busy-waiting on the UART.
+//!
+//! `Stdout` is a `fmt::Write` handle of the default console, for `write!`
+//! outside of the `sprint!` macros, like the `logger` backend and the panic
+//! handler.
 
-use core::fmt::{self, Write};
+use core::fmt;
+use core::marker::PhantomData;
 use riscv::interrupt;
+use riscv::register::mstatus;
 use gd32vf103xx_hal::{
-    serial::{Serial, Tx, self},
-    gpio::{Active, gpioa::{PA10, PA9}},
+    serial::{Serial, self},
+    gpio::{Active, gpioa::{PA10, PA2, PA3, PA9}, gpiob::{PB10, PB11, PB6, PB7}},
     time::Bps,
     rcu::Rcu,
     afio::Afio,
-    pac::USART0,
+    eclic::EclicExt,
+    pac::{usart0, ECLIC, Interrupt, USART0, USART1, USART2},
 };
 
+use crate::ring::RingBuffer;
 
-static mut STDOUT: Option<Tx<USART0>> = None;
+/// Size of the receive buffer of each console
+const STDIN_CAPACITY: usize = 128;
+/// Size of the transmit buffer of each console
+const STDOUT_CAPACITY: usize = 512;
 
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
 
-/// Configures stdout
-pub fn configure<X, Y>(
-    uart: USART0, tx: PA9<X>, rx: PA10<Y>,
+/// The console `sprintln!` writes to
+static mut DEFAULT: Option<&'static dyn SerialConsole> = None;
+
+/// Also gets everything written to stdout, like an `LcdConsole`
+static mut MIRROR: Option<&'static mut dyn fmt::Write> = None;
+/// Set by `set_mirror`, so `write_str` does not put back a mirror that was
+/// replaced or removed while it was writing
+static mut MIRROR_SET: bool = false;
+
+
+/// What to do when the transmit buffer is full
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FullPolicy {
+    /// Wait for the interrupt to make room
+    Block,
+    /// Discard the byte being written
+    DropNewest,
+    /// Discard the oldest queued byte
+    OverwriteOldest,
+}
+
+
+/// Buffers and settings of one console
+pub struct State {
+    tx: RingBuffer<STDOUT_CAPACITY>,
+    rx: RingBuffer<STDIN_CAPACITY>,
+    policy: FullPolicy,
+    echo: bool,
+    /// Last line ended with '\r', so a following '\n' is part of it
+    last_cr: bool,
+    open: bool,
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            tx: RingBuffer::new(),
+            rx: RingBuffer::new(),
+            policy: FullPolicy::Block,
+            echo: false,
+            last_cr: false,
+            open: false,
+        }
+    }
+}
+
+
+/// A USART instance usable as console
+pub trait Usart {
+    /// Interrupt line of the instance
+    const INTERRUPT: Interrupt;
+
+    /// Register block of the instance
+    fn regs() -> &'static usart0::RegisterBlock;
+
+    /// Console state of the instance, must be used inside `interrupt::free`
+    unsafe fn state() -> &'static mut State;
+}
+
+macro_rules! usart {
+    ($($USART:ident: $STATE:ident,)+) => {
+        $(
+            static mut $STATE: State = State::new();
+
+            impl Usart for $USART {
+                const INTERRUPT: Interrupt = Interrupt::$USART;
+
+                fn regs() -> &'static usart0::RegisterBlock {
+                    unsafe { &*$USART::ptr() }
+                }
+
+                unsafe fn state() -> &'static mut State {
+                    &mut $STATE
+                }
+            }
+        )+
+    };
+}
+
+usart! {
+    USART0: USART0_STATE,
+    USART1: USART1_STATE,
+    USART2: USART2_STATE,
+}
+
+
+/// A (tx, rx) pin pair of a USART instance
+pub trait ConsolePins<USART> {
+    /// Switches the pins to alternate function, and sets up the USART
+    fn setup(self, usart: USART, config: serial::Config, afio: &mut Afio, rcu: &mut Rcu);
+}
+
+macro_rules! console_pins {
+    ($($USART:ident: ($TX:ident, $RX:ident),)+) => {
+        $(
+            impl<X: Active, Y: Active> ConsolePins<$USART> for ($TX<X>, $RX<Y>) {
+                fn setup(self, usart: $USART, config: serial::Config, afio: &mut Afio, rcu: &mut Rcu) {
+                    let tx = self.0.into_alternate_push_pull();
+                    let rx = self.1.into_floating_input();
+                    // remap is selected by the HAL from the pin types.
+                    // Tx/Rx halves are not kept, the console uses the registers.
+                    let _ = Serial::new(usart, (tx, rx), config, afio, rcu);
+                }
+            }
+        )+
+    };
+}
+
+console_pins! {
+    USART0: (PA9, PA10),
+    USART0: (PB6, PB7),
+    USART1: (PA2, PA3),
+    USART2: (PB10, PB11),
+}
+
+
+/// Console operations, object safe so any console can be the default one
+pub trait SerialConsole: Sync {
+    /// Writes a raw byte
+    fn write_byte(&self, b: u8);
+    /// Blocks until everything written so far has left the UART
+    fn flush(&self);
+    /// Takes a received byte, without blocking
+    fn read_byte(&self) -> Option<u8>;
+    /// Number of received bytes waiting to be read
+    fn available(&self) -> usize;
+    /// Echo received characters back in `read_line`
+    fn set_echo(&self, echo: bool);
+    /// Sets the transmit buffer full policy, default is `FullPolicy::Block`
+    fn set_full_policy(&self, policy: FullPolicy);
+    /// Handles the USART interrupt
+    fn handle_interrupt(&self);
+
+    /// Writes a string
+    fn write_str(&self, s: &str) {
+        for &b in s.as_bytes() {
+            self.write_byte(b);
+        }
+    }
+
+    /// Reads a line into `buf`, blocking until '\r' or '\n'.
+    ///
+    /// Handles backspace, and echoes if enabled by `set_echo`. The line ending
+    /// is not stored, characters beyond `buf` are dropped. Returns the length.
+    fn read_line(&self, buf: &mut [u8]) -> usize;
+}
+
+
+/// Serial console on one USART instance
+pub struct Console<USART> {
+    _usart: PhantomData<USART>,
+}
+
+// zero-sized handle, the state lives in statics
+unsafe impl<USART> Sync for Console<USART> {}
+
+impl<USART> Clone for Console<USART> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<USART> Copy for Console<USART> {}
+
+impl<USART: Usart + 'static> Console<USART> {
+    const INSTANCE: Self = Console { _usart: PhantomData };
+
+    /// Handle of a console opened before with `open`
+    pub const fn new() -> Self {
+        Console { _usart: PhantomData }
+    }
+
+    /// Whether the TBE interrupt can drain the transmit buffer
+    fn buffered() -> bool {
+        mstatus::read().mie() && ECLIC::is_enabled(USART::INTERRUPT)
+    }
+
+    /// Sends everything queued, busy-waiting on the UART.
+    /// Must be called inside `interrupt::free`.
+    fn drain(state: &mut State) {
+        let regs = USART::regs();
+        while let Some(b) = state.tx.pop() {
+            write_data(regs, b);
+        }
+    }
+}
+
+impl<USART: Usart + 'static> SerialConsole for Console<USART> {
+    fn write_byte(&self, b: u8) {
+        if !Self::buffered() {
+            interrupt::free(|_| unsafe {
+                let state = USART::state();
+                if state.open {
+                    // keep the order of what is still queued
+                    Self::drain(state);
+                    write_data(USART::regs(), b);
+                }
+            });
+            return;
+        }
+
+        loop {
+            let queued = interrupt::free(|_| unsafe {
+                let state = USART::state();
+                if !state.open {
+                    return true;
+                }
+                let queued = match state.policy {
+                    FullPolicy::Block => state.tx.push(b),
+                    FullPolicy::DropNewest => {
+                        state.tx.push(b);
+                        true
+                    }
+                    FullPolicy::OverwriteOldest => {
+                        state.tx.push_overwrite(b);
+                        true
+                    }
+                };
+                // TBE interrupt
+                USART::regs().ctl0.modify(|_, w| w.tbeie().set_bit());
+                queued
+            });
+            if queued {
+                return;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if !interrupt::free(|_| unsafe { USART::state().open }) {
+            return;
+        }
+        if Self::buffered() {
+            while interrupt::free(|_| unsafe { !USART::state().tx.is_empty() }) {}
+        } else {
+            interrupt::free(|_| unsafe { Self::drain(USART::state()) });
+        }
+        while USART::regs().stat.read().tc().bit_is_clear() {}
+    }
+
+    fn read_byte(&self) -> Option<u8> {
+        interrupt::free(|_| unsafe { USART::state().rx.pop() })
+    }
+
+    fn available(&self) -> usize {
+        interrupt::free(|_| unsafe { USART::state().rx.len() })
+    }
+
+    fn set_echo(&self, echo: bool) {
+        interrupt::free(|_| unsafe { USART::state().echo = echo })
+    }
+
+    fn set_full_policy(&self, policy: FullPolicy) {
+        interrupt::free(|_| unsafe { USART::state().policy = policy })
+    }
+
+    fn handle_interrupt(&self) {
+        interrupt::free(|_| unsafe {
+            let regs = USART::regs();
+            let state = USART::state();
+            if !state.open {
+                return;
+            }
+
+            if regs.ctl0.read().tbeie().bit_is_set() {
+                while regs.stat.read().tbe().bit_is_set() {
+                    match state.tx.pop() {
+                        Some(b) => regs.data.write(|w| w.data().bits(b as u16)),
+                        None => break,
+                    }
+                }
+                if state.tx.is_empty() {
+                    regs.ctl0.modify(|_, w| w.tbeie().clear_bit());
+                }
+            }
+
+            loop {
+                let stat = regs.stat.read();
+                let error = stat.perr().bit_is_set()
+                    || stat.ferr().bit_is_set()
+                    || stat.nerr().bit_is_set()
+                    || stat.orerr().bit_is_set();
+                if stat.rbne().bit_is_clear() && !error {
+                    break;
+                }
+                // reading STAT then DATA clears the error flags
+                let b = regs.data.read().data().bits() as u8;
+                if !error {
+                    // dropped when full
+                    state.rx.push(b);
+                }
+            }
+        })
+    }
+
+    fn read_line(&self, buf: &mut [u8]) -> usize {
+        let echo = interrupt::free(|_| unsafe { USART::state().echo });
+        let mut len = 0;
+        loop {
+            let b = match self.read_byte() {
+                Some(b) => b,
+                None => continue,
+            };
+            let last_cr = interrupt::free(|_| unsafe {
+                core::mem::replace(&mut USART::state().last_cr, b == b'\r')
+            });
+            match b {
+                b'\n' if last_cr && len == 0 => {}
+                b'\r' | b'\n' => {
+                    if echo {
+                        self.write_str("\r\n");
+                    }
+                    return len;
+                }
+                BACKSPACE | DELETE => {
+                    if len > 0 {
+                        len -= 1;
+                        if echo {
+                            self.write_str("\x08 \x08");
+                        }
+                    }
+                }
+                _ => {
+                    if len < buf.len() {
+                        buf[len] = b;
+                        len += 1;
+                        if echo {
+                            self.write_byte(b);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<USART: Usart + 'static> fmt::Write for Console<USART> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        SerialConsole::write_str(self, s);
+        Ok(())
+    }
+}
+
+
+/// Busy-waits for TBE, then writes `b`
+fn write_data(regs: &usart0::RegisterBlock, b: u8) {
+    while regs.stat.read().tbe().bit_is_clear() {}
+    regs.data.write(|w| unsafe { w.data().bits(b as u16) });
+}
+
+
+/// Opens a console on any USART instance and pin pair, with the receive
+/// interrupt enabled
+pub fn open<USART, PINS>(
+    uart: USART, pins: PINS,
     baud_rate: Bps, afio: &mut Afio, rcu: &mut Rcu
-) where X: Active, Y: Active
+) -> Console<USART>
+where USART: Usart + 'static, PINS: ConsolePins<USART>
 {
-    let tx = tx.into_alternate_push_pull();
-    let rx = rx.into_floating_input();
     let config = serial::Config::default().baudrate(baud_rate);
-    let serial = Serial::new(uart, (tx, rx), config, afio, rcu);
-    let (tx, _) = serial.split();
+    pins.setup(uart, config, afio, rcu);
 
     interrupt::free(|_| {
         unsafe {
-            STDOUT.replace(tx);
+            let state = USART::state();
+            state.tx.clear();
+            state.rx.clear();
+            state.open = true;
+            // RBNE interrupt
+            USART::regs().ctl0.modify(|_, w| w.rbneie().set_bit());
         }
+    });
+    Console::new()
+}
+
+
+/// Configures stdout on USART0 (PA9/PA10), and makes it the default console
+pub fn configure<X, Y>(
+    uart: USART0, tx: PA9<X>, rx: PA10<Y>,
+    baud_rate: Bps, afio: &mut Afio, rcu: &mut Rcu
+) where X: Active, Y: Active
+{
+    let console = open(uart, (tx, rx), baud_rate, afio, rcu);
+    set_default(console);
+}
+
+
+/// Makes `console` the one `sprintln!` and the free functions here use
+pub fn set_default<USART: Usart + 'static>(_console: Console<USART>) {
+    interrupt::free(|_| unsafe { DEFAULT = Some(&Console::<USART>::INSTANCE) })
+}
+
+
+fn default() -> Option<&'static dyn SerialConsole> {
+    interrupt::free(|_| unsafe { DEFAULT })
+}
+
+
+/// Writes a raw byte to stdout
+pub fn write_byte(b: u8) {
+    if let Some(console) = default() {
+        console.write_byte(b);
+    }
+}
+
+
+/// Writes a string to stdout, and to the mirror if any
+pub fn write_str(s: &str) {
+    if let Some(console) = default() {
+        console.write_str(s);
+    }
+    // taken out while writing, so output from interrupts meanwhile skips it
+    let mirror = interrupt::free(|_| unsafe {
+        MIRROR_SET = false;
+        MIRROR.take()
+    });
+    if let Some(mirror) = mirror {
+        let _ = mirror.write_str(s);
+        interrupt::free(move |_| unsafe {
+            if !MIRROR_SET {
+                MIRROR = Some(mirror);
+            }
+        });
+    }
+}
+
+
+/// Copies stdout text to `mirror` as well, e.g. an `LcdConsole` in a static.
+/// Raw bytes from `write_byte` are not mirrored.
+pub fn set_mirror(mirror: Option<&'static mut dyn fmt::Write>) {
+    interrupt::free(|_| unsafe {
+        MIRROR = mirror;
+        MIRROR_SET = true;
     })
 }
 
 
+/// `fmt::Write` handle of stdout, for `write!`
+pub struct Stdout;
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s);
+        Ok(())
+    }
+}
+
+
 /// Writes formatted string to stdout
 pub fn write_fmt(args: fmt::Arguments) {
-    interrupt::free(|_| unsafe {
-        if let Some(stdout) = STDOUT.as_mut() {
-            let _ = stdout.write_fmt(args);
-        }
-    })
+    let _ = fmt::Write::write_fmt(&mut Stdout, args);
+}
+
+
+/// Blocks until everything written so far has left the UART
+pub fn flush() {
+    if let Some(console) = default() {
+        console.flush();
+    }
+}
+
+
+/// Sets the transmit buffer full policy of stdout
+pub fn set_full_policy(policy: FullPolicy) {
+    if let Some(console) = default() {
+        console.set_full_policy(policy);
+    }
+}
+
+
+/// Interrupt handler of the default console
+pub fn handle_interrupt() {
+    if let Some(console) = default() {
+        console.handle_interrupt();
+    }
+}
+
+
+/// Echo received characters back in `read_line`
+pub fn set_echo(echo: bool) {
+    if let Some(console) = default() {
+        console.set_echo(echo);
+    }
+}
+
+
+/// Number of received bytes waiting to be read
+pub fn available() -> usize {
+    default().map(|c| c.available()).unwrap_or(0)
+}
+
+
+/// Takes a received byte, without blocking
+pub fn read_byte() -> Option<u8> {
+    default().and_then(|c| c.read_byte())
+}
+
+
+/// Reads a line from stdin into `buf`, see `SerialConsole::read_line`
+pub fn read_line(buf: &mut [u8]) -> usize {
+    match default() {
+        Some(console) => console.read_line(buf),
+        None => 0,
+    }
 }
 
 
@@ -65,4 +586,4 @@ macro_rules! sprintln {
     ($s:expr, $($tt:tt)*) => {
         $crate::stdout::write_fmt(format_args!(concat!($s, "\n"), $($tt)*))
     };
-}
\ No newline at end of file
+}
@@ -0,0 +1,260 @@
+//! Trap decoding and default trap handlers
+//!
+//! With the `trap-handler` feature this module provides `ExceptionHandler`
+//! and `DefaultHandler` for riscv-rt, replacing the silent `loop {}`. Every
+//! unexpected trap is dumped on stdout:
+//!
+//! ```text
+//! !!! TRAP Exception 2: Illegal instruction
+//!   mcause=0x38000002 mepc=0x08000a3c mtval=0x00000000
+//!   ra=0x08000a1e t0=0x00000000 t1=0x20000010 t2=0x00000001
+//!   ...
+//! ```
+//!
+//! A hook can be set to log traps elsewhere or recover from them:
+//!
+//! ```ignore
+//! fn on_trap(info: &TrapInfo) -> Action {
+//!     match info.cause() {
+//!         Cause::Exception(3) => Action::Skip, // ebreak
+//!         _ => Action::Halt,
+//!     }
+//! }
+//!
+//! trap::set_hook(on_trap);
+//! ```
+//!
+//! Binaries with their own `DefaultHandler` keep it for builds without the
+//! feature, like the `button` and `usbhid` examples:
+//!
+//! ```ignore
+//! #[cfg(not(feature = "trap-handler"))]
+//! #[no_mangle]
+//! fn DefaultHandler() { ... }
+//! ```
+
+use core::fmt;
+
+use riscv::interrupt;
+use riscv::register::{mcause, mepc, mtval};
+
+/// Exception code / ECLIC interrupt id bits of `mcause`
+const MCAUSE_CODE: usize = 0xfff;
+const MCAUSE_INTERRUPT: usize = 1 << 31;
+
+/// Caller-saved registers, as pushed by riscv-rt's trap entry
+#[repr(C)]
+#[derive(Debug)]
+pub struct TrapFrame {
+    pub ra: usize,
+    pub t0: usize,
+    pub t1: usize,
+    pub t2: usize,
+    pub t3: usize,
+    pub t4: usize,
+    pub t5: usize,
+    pub t6: usize,
+    pub a0: usize,
+    pub a1: usize,
+    pub a2: usize,
+    pub a3: usize,
+    pub a4: usize,
+    pub a5: usize,
+    pub a6: usize,
+    pub a7: usize,
+}
+
+impl TrapFrame {
+    /// Registers with their ABI names, in frame order
+    pub fn registers(&self) -> [(&'static str, usize); 16] {
+        [
+            ("ra", self.ra),
+            ("t0", self.t0),
+            ("t1", self.t1),
+            ("t2", self.t2),
+            ("t3", self.t3),
+            ("t4", self.t4),
+            ("t5", self.t5),
+            ("t6", self.t6),
+            ("a0", self.a0),
+            ("a1", self.a1),
+            ("a2", self.a2),
+            ("a3", self.a3),
+            ("a4", self.a4),
+            ("a5", self.a5),
+            ("a6", self.a6),
+            ("a7", self.a7),
+        ]
+    }
+}
+
+/// Decoded `mcause`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cause {
+    /// Exception code
+    Exception(u16),
+    /// ECLIC interrupt id
+    Interrupt(u16),
+}
+
+impl Cause {
+    pub fn from_mcause(bits: usize) -> Self {
+        let code = (bits & MCAUSE_CODE) as u16;
+        if bits & MCAUSE_INTERRUPT != 0 {
+            Cause::Interrupt(code)
+        } else {
+            Cause::Exception(code)
+        }
+    }
+
+    pub fn is_interrupt(&self) -> bool {
+        matches!(self, Cause::Interrupt(_))
+    }
+
+    /// Human readable name
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Cause::Exception(code) => match code {
+                0 => "Instruction address misaligned",
+                1 => "Instruction access fault",
+                2 => "Illegal instruction",
+                3 => "Breakpoint",
+                4 => "Load address misaligned",
+                5 => "Load access fault",
+                6 => "Store/AMO address misaligned",
+                7 => "Store/AMO access fault",
+                8 => "Environment call from U-mode",
+                11 => "Environment call from M-mode",
+                0xfff => "NMI",
+                _ => "Reserved",
+            },
+            Cause::Interrupt(id) => match id {
+                3 => "Software interrupt",
+                7 => "Timer interrupt",
+                17 => "Bus error interrupt",
+                18 => "Performance monitor interrupt",
+                _ if id >= 19 => "External interrupt",
+                _ => "Reserved",
+            },
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cause::Exception(code) => write!(f, "Exception {}: {}", code, self.name()),
+            Cause::Interrupt(id) => write!(f, "Interrupt {}: {}", id, self.name()),
+        }
+    }
+}
+
+/// What to do after the hook returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Dump the trap on stdout and stop
+    Halt,
+    /// Return to `mepc`, e.g. for a spurious interrupt
+    Return,
+    /// Return after the trapping instruction, e.g. for `ebreak`
+    Skip,
+}
+
+/// Trap hook, called before the default handling
+pub type Hook = fn(&TrapInfo) -> Action;
+
+static mut HOOK: Option<Hook> = None;
+
+/// State of the hart when the trap was taken
+pub struct TrapInfo<'a> {
+    pub mcause: usize,
+    pub mepc: usize,
+    pub mtval: usize,
+    /// Saved registers, only available for exceptions
+    pub frame: Option<&'a TrapFrame>,
+}
+
+impl<'a> TrapInfo<'a> {
+    /// Reads the trap CSRs
+    pub fn read(frame: Option<&'a TrapFrame>) -> Self {
+        TrapInfo {
+            mcause: mcause::read().bits(),
+            mepc: mepc::read(),
+            mtval: mtval::read(),
+            frame,
+        }
+    }
+
+    pub fn cause(&self) -> Cause {
+        Cause::from_mcause(self.mcause)
+    }
+}
+
+impl fmt::Display for TrapInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "!!! TRAP {}", self.cause())?;
+        writeln!(
+            f,
+            "  mcause={:#010x} mepc={:#010x} mtval={:#010x}",
+            self.mcause, self.mepc, self.mtval
+        )?;
+        if let Some(frame) = self.frame {
+            for row in frame.registers().chunks(4) {
+                write!(f, " ")?;
+                for (name, value) in row {
+                    write!(f, " {}={:#010x}", name, value)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sets the trap hook
+pub fn set_hook(hook: Hook) {
+    interrupt::free(|_| unsafe { HOOK = Some(hook) })
+}
+
+/// Removes the trap hook, all traps halt again
+pub fn clear_hook() {
+    interrupt::free(|_| unsafe { HOOK = None })
+}
+
+/// Runs the hook and carries out its action, what the default handlers do
+pub fn handle(info: &TrapInfo) {
+    let hook = interrupt::free(|_| unsafe { HOOK });
+    match hook.map_or(Action::Halt, |hook| hook(info)) {
+        Action::Return => {}
+        Action::Skip => mepc::write(info.mepc + instruction_len(info.mepc)),
+        Action::Halt => {
+            crate::sprint!("\r\n{}", info);
+            crate::stdout::flush();
+            loop {}
+        }
+    }
+}
+
+/// Length of the instruction at `pc`, 2 for compressed instructions
+fn instruction_len(pc: usize) -> usize {
+    let low = unsafe { core::ptr::read_volatile(pc as *const u16) };
+    if low & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
+
+#[cfg(feature = "trap-handler")]
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn ExceptionHandler(frame: &TrapFrame) {
+    handle(&TrapInfo::read(Some(frame)));
+}
+
+#[cfg(feature = "trap-handler")]
+#[allow(non_snake_case)]
+#[no_mangle]
+pub fn DefaultHandler() {
+    handle(&TrapInfo::read(None));
+}
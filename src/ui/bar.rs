@@ -0,0 +1,114 @@
+//! Horizontal progress bar
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitive_style;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{fill, layout, rect_size, Style, Widget};
+
+/// Outlined bar filled from the left, in proportion to `value / max`
+pub struct ProgressBar {
+    bounds: Rectangle,
+    value: u32,
+    max: u32,
+    style: Style,
+    /// Filled width on screen, `None` before the first draw
+    drawn: Option<u32>,
+}
+
+impl ProgressBar {
+    pub fn new(bounds: Rectangle, max: u32) -> Self {
+        ProgressBar {
+            bounds,
+            value: 0,
+            max: max.max(1),
+            style: Style::default(),
+            drawn: None,
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Sets the value, clamped to `max`
+    pub fn set(&mut self, value: u32) {
+        self.value = value.min(self.max);
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        if style != self.style {
+            self.style = style;
+            self.drawn = None;
+        }
+    }
+
+    /// Inside of the outline
+    fn inner(&self) -> Rectangle {
+        layout::inset(self.bounds, 1)
+    }
+
+    /// Filled width for the current value
+    fn filled(&self) -> u32 {
+        let width = rect_size(&self.inner()).width as u64;
+        (self.value as u64 * width / self.max as u64) as u32
+    }
+
+    /// Columns `from..to` of the inside
+    fn columns(&self, from: u32, to: u32) -> Rectangle {
+        let inner = self.inner();
+        Rectangle::new(
+            Point::new(inner.top_left.x + from as i32, inner.top_left.y),
+            Point::new(inner.top_left.x + to as i32 - 1, inner.bottom_right.y),
+        )
+    }
+}
+
+impl<D: DrawTarget<Rgb565>> Widget<D> for ProgressBar {
+    fn bounds(&self) -> Rectangle {
+        self.bounds
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.drawn != Some(self.filled())
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        let filled = self.filled();
+        let width = rect_size(&self.inner()).width;
+        match self.drawn {
+            None => {
+                self.bounds
+                    .into_styled(primitive_style!(
+                        stroke_color = self.style.fg,
+                        stroke_width = 1,
+                        fill_color = self.style.bg
+                    ))
+                    .draw(target)?;
+                fill(target, self.columns(0, filled), self.style.accent)?;
+            }
+            // only the columns that change
+            Some(drawn) if filled > drawn => {
+                fill(target, self.columns(drawn, filled), self.style.accent)?
+            }
+            Some(drawn) => fill(
+                target,
+                self.columns(filled, drawn.min(width)),
+                self.style.bg,
+            )?,
+        }
+        self.drawn = Some(filled);
+        Ok(())
+    }
+}
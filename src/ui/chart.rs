@@ -0,0 +1,426 @@
+//! Strip chart
+//!
+//! Sweeps left to right like an oscilloscope: each sample is one column, and
+//! the newest column is the only one redrawn, with a blank column ahead of
+//! it. Once the chart is full, new columns overwrite the oldest ones.
+//!
+//! ```ignore
+//! let mut chart = Chart::new(area, 2).scale(Scale::Auto).grid(20, 4);
+//! loop {
+//!     let temp = adc::to_decicelsius(adc.read_idata0());
+//!     let vref = adc::to_millivolts(adc.read_idata1());
+//!     chart.push(&[temp, vref]);
+//!     ui::update(&mut lcd, &mut [&mut chart])?;
+//! }
+//! ```
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{rect_size, Style, Widget};
+
+/// Most traces in one chart
+pub const MAX_SERIES: usize = 4;
+/// Most samples kept per trace, the width of the screen
+pub const MAX_POINTS: usize = 160;
+
+/// Default trace colours
+const COLORS: [Rgb565; MAX_SERIES] = [Rgb565::GREEN, Rgb565::YELLOW, Rgb565::CYAN, Rgb565::MAGENTA];
+
+/// Vertical range of a chart
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scale {
+    /// Fits the samples on screen, rounded so it changes rarely
+    Auto,
+    /// From the first value at the bottom to the second at the top
+    Fixed(i32, i32),
+}
+
+/// 1 to 4 traces over a ring of samples
+pub struct Chart {
+    bounds: Rectangle,
+    /// Columns in use, at most `MAX_POINTS`
+    width: usize,
+    series: usize,
+    colors: [Rgb565; MAX_SERIES],
+    samples: [[i32; MAX_POINTS]; MAX_SERIES],
+    /// Column of the next sample
+    head: usize,
+    /// Samples stored, up to `width`
+    len: usize,
+    /// Samples pushed since the last draw
+    pending: usize,
+    scale: Scale,
+    /// Vertical grid line every that many columns, 0 for none
+    grid_x: u32,
+    /// Horizontal grid lines dividing the height, 0 for none
+    grid_y: u32,
+    grid_color: Rgb565,
+    style: Style,
+    /// Range on screen, `None` before the first draw
+    drawn: Option<(i32, i32)>,
+}
+
+impl Chart {
+    /// Chart of `series` traces, one column per sample
+    pub fn new(bounds: Rectangle, series: usize) -> Self {
+        Chart {
+            bounds,
+            width: (rect_size(&bounds).width as usize).clamp(1, MAX_POINTS),
+            series: series.clamp(1, MAX_SERIES),
+            colors: COLORS,
+            samples: [[0; MAX_POINTS]; MAX_SERIES],
+            head: 0,
+            len: 0,
+            pending: 0,
+            scale: Scale::Auto,
+            grid_x: 0,
+            grid_y: 0,
+            grid_color: Rgb565::new(6, 12, 6),
+            style: Style::default(),
+            drawn: None,
+        }
+    }
+
+    pub fn scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Grid lines every `x_step` columns, and `y_divisions` bands high
+    pub fn grid(mut self, x_step: u32, y_divisions: u32) -> Self {
+        self.grid_x = x_step;
+        self.grid_y = y_divisions;
+        self
+    }
+
+    pub fn grid_color(mut self, color: Rgb565) -> Self {
+        self.grid_color = color;
+        self
+    }
+
+    /// Colour of one trace
+    pub fn color(mut self, series: usize, color: Rgb565) -> Self {
+        if series < MAX_SERIES {
+            self.colors[series] = color;
+        }
+        self
+    }
+
+    /// Background comes from `style.bg`
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    /// Adds one sample per trace, missing ones repeat the previous value
+    pub fn push(&mut self, values: &[i32]) {
+        let prev = (self.head + self.width - 1) % self.width;
+        for s in 0..self.series {
+            let value = match values.get(s) {
+                Some(&v) => v,
+                None if self.len > 0 => self.samples[s][prev],
+                None => 0,
+            };
+            self.samples[s][self.head] = value;
+        }
+        self.head = (self.head + 1) % self.width;
+        self.len = (self.len + 1).min(self.width);
+        self.pending = (self.pending + 1).min(self.width);
+    }
+
+    /// Forgets every sample
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.pending = 0;
+        self.drawn = None;
+    }
+
+    /// Newest sample of a trace
+    pub fn last(&self, series: usize) -> Option<i32> {
+        if self.len == 0 || series >= self.series {
+            return None;
+        }
+        Some(self.samples[series][(self.head + self.width - 1) % self.width])
+    }
+
+    /// Range to draw with, bottom then top
+    fn range(&self) -> (i32, i32) {
+        match self.scale {
+            Scale::Fixed(lo, hi) if hi > lo => (lo, hi),
+            Scale::Fixed(lo, _) => (lo, lo + 1),
+            Scale::Auto => {
+                let mut values = self.samples[..self.series]
+                    .iter()
+                    .flat_map(|s| s[..self.len].iter().copied());
+                let first = match values.next() {
+                    Some(v) => v,
+                    None => return (0, 1),
+                };
+                let (lo, hi) = values.fold((first, first), |(lo, hi), v| (lo.min(v), hi.max(v)));
+                nice_range(lo, hi)
+            }
+        }
+    }
+
+    /// Blank column ahead of the newest sample, once the chart wrapped
+    fn gap(&self) -> Option<usize> {
+        if self.len == self.width && self.width > 1 {
+            Some(self.head)
+        } else {
+            None
+        }
+    }
+
+    /// Screen y of `value`
+    fn y(&self, value: i32, (lo, hi): (i32, i32)) -> i32 {
+        let height = rect_size(&self.bounds).height as i64 - 1;
+        let value = value.max(lo).min(hi);
+        let offset = (value - lo) as i64 * height / (hi - lo) as i64;
+        self.bounds.bottom_right.y - offset as i32
+    }
+
+    fn is_grid_row(&self, y: i32) -> bool {
+        if self.grid_y == 0 {
+            return false;
+        }
+        let height = rect_size(&self.bounds).height as i32 - 1;
+        let dy = self.bounds.bottom_right.y - y;
+        (0..=self.grid_y as i32).any(|i| height * i / self.grid_y as i32 == dy)
+    }
+
+    /// Pixels of one whole column: background, grid, then traces on top
+    fn column(&self, x: usize, range: (i32, i32)) -> impl Iterator<Item = Pixel<Rgb565>> + '_ {
+        let mut spans = [(i32::MAX, i32::MIN); MAX_SERIES];
+        let filled = x < self.len && Some(x) != self.gap();
+        if filled {
+            for (s, span) in spans.iter_mut().enumerate().take(self.series) {
+                let y = self.y(self.samples[s][x], range);
+                // joined to the previous sample, unless this is the oldest
+                let prev = (x + self.width - 1) % self.width;
+                let prev_y = if (x > 0 || self.len == self.width) && Some(prev) != self.gap() {
+                    self.y(self.samples[s][prev], range)
+                } else {
+                    y
+                };
+                *span = (y.min(prev_y), y.max(prev_y));
+            }
+        }
+
+        let vertical_grid = self.grid_x > 0 && (x as u32).is_multiple_of(self.grid_x);
+        let screen_x = self.bounds.top_left.x + x as i32;
+        (self.bounds.top_left.y..=self.bounds.bottom_right.y).map(move |y| {
+            let trace = (0..self.series)
+                .rev()
+                .find(|&s| spans[s].0 <= y && y <= spans[s].1);
+            let color = match trace {
+                Some(s) => self.colors[s],
+                None if vertical_grid || self.is_grid_row(y) => self.grid_color,
+                None => self.style.bg,
+            };
+            Pixel(Point::new(screen_x, y), color)
+        })
+    }
+}
+
+/// Widens (lo, hi) to multiples of a round step, 1, 2 or 5 times a power of
+/// ten, so small changes of the samples keep the same range. Near the ends of
+/// `i32` the range stops at them.
+fn nice_range(lo: i32, hi: i32) -> (i32, i32) {
+    let span = (hi as i64 - lo as i64).max(1);
+    let mut decade: i64 = 1;
+    let step = loop {
+        if let Some(m) = [1, 2, 5].iter().find(|&&m| span / (m * decade) <= 8) {
+            break m * decade;
+        }
+        decade *= 10;
+    };
+    let lo = (lo as i64).div_euclid(step) * step;
+    let hi = ((hi as i64).div_euclid(step) + 1) * step;
+    (
+        lo.max(i32::MIN as i64) as i32,
+        hi.min(i32::MAX as i64) as i32,
+    )
+}
+
+impl<D: DrawTarget<Rgb565>> Widget<D> for Chart {
+    fn bounds(&self) -> Rectangle {
+        self.bounds
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.pending > 0 || self.drawn.is_none()
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        let range = self.range();
+        let pending = core::mem::replace(&mut self.pending, 0);
+        if self.drawn != Some(range) {
+            // new range, everything moves
+            self.drawn = Some(range);
+            for x in 0..self.width {
+                target.draw_iter(self.column(x, range))?;
+            }
+            return Ok(());
+        }
+
+        // newest columns, the gap ahead of them, and the oldest column that
+        // is no longer joined to the one before
+        for i in (1..=pending).rev() {
+            let x = (self.head + self.width - i) % self.width;
+            target.draw_iter(self.column(x, range))?;
+        }
+        if let Some(gap) = self.gap() {
+            target.draw_iter(self.column(gap, range))?;
+            let oldest = (gap + 1) % self.width;
+            if pending < self.width - 1 {
+                target.draw_iter(self.column(oldest, range))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::geometry::Size;
+
+    use super::*;
+    use crate::ui::rect;
+    use crate::ui::tests::Canvas;
+
+    /// `width` columns of 20 pixels at (10, 10)
+    fn chart(width: u32, scale: Scale) -> Chart {
+        Chart::new(rect(Point::new(10, 10), Size::new(width, 20)), 2)
+            .scale(scale)
+            .grid(4, 2)
+    }
+
+    fn draw(chart: &mut Chart, canvas: &mut Canvas) -> usize {
+        let before = canvas.writes;
+        chart.draw(canvas).unwrap();
+        canvas.writes - before
+    }
+
+    /// What a full redraw of `chart` looks like
+    fn redrawn(chart: &mut Chart) -> Canvas {
+        let mut canvas = Canvas::new();
+        Widget::<Canvas>::invalidate(chart);
+        chart.draw(&mut canvas).unwrap();
+        canvas
+    }
+
+    #[test]
+    fn nice_ranges() {
+        assert_eq!(nice_range(0, 0), (0, 1));
+        assert_eq!(nice_range(5, 5), (5, 6));
+        assert_eq!(nice_range(3, 47), (0, 50));
+        assert_eq!(nice_range(-13, 7), (-15, 10));
+        assert_eq!(nice_range(0, 10), (0, 12));
+        assert_eq!(nice_range(215, 231), (214, 232));
+        assert_eq!(nice_range(1200, 3300), (1000, 3500));
+        // no overflow at the ends
+        assert_eq!(nice_range(i32::MIN, i32::MAX), (i32::MIN, i32::MAX));
+        assert_eq!(nice_range(i32::MAX - 1, i32::MAX).1, i32::MAX);
+    }
+
+    #[test]
+    fn auto_scale() {
+        let mut chart = chart(10, Scale::Auto);
+        assert_eq!(chart.range(), (0, 1));
+        chart.push(&[3, 47]);
+        assert_eq!(chart.range(), (0, 50));
+        chart.push(&[-13]);
+        assert_eq!(chart.last(1), Some(47));
+        assert_eq!(chart.range(), (-20, 50));
+        assert_eq!(chart.last(2), None);
+    }
+
+    #[test]
+    fn gap_once_wrapped() {
+        let mut ring = chart(4, Scale::Fixed(0, 100));
+        for i in 0..3 {
+            ring.push(&[i]);
+            assert_eq!(ring.gap(), None);
+        }
+        ring.push(&[3]);
+        assert_eq!(ring.gap(), Some(0));
+        ring.push(&[4]);
+        assert_eq!(ring.gap(), Some(1));
+        ring.clear();
+        assert_eq!((ring.gap(), ring.last(0)), (None, None));
+
+        // a chart one column wide has no room for a gap
+        let mut narrow = chart(1, Scale::Fixed(0, 100));
+        narrow.push(&[1]);
+        narrow.push(&[2]);
+        assert_eq!(narrow.gap(), None);
+    }
+
+    #[test]
+    fn draws_only_new_columns() {
+        let mut canvas = Canvas::new();
+        let mut chart = chart(10, Scale::Fixed(0, 100));
+        // everything on the first draw
+        assert_eq!(draw(&mut chart, &mut canvas), 10 * 20);
+        assert!(!Widget::<Canvas>::is_dirty(&chart));
+        assert_eq!(draw(&mut chart, &mut canvas), 0);
+
+        chart.push(&[10, 90]);
+        assert_eq!(draw(&mut chart, &mut canvas), 20);
+        chart.push(&[20, 80]);
+        chart.push(&[30, 70]);
+        assert_eq!(draw(&mut chart, &mut canvas), 2 * 20);
+
+        // once full: newest, gap and oldest
+        for i in 0..7 {
+            chart.push(&[i * 10, 100 - i * 10]);
+        }
+        draw(&mut chart, &mut canvas);
+        chart.push(&[50, 50]);
+        assert_eq!(draw(&mut chart, &mut canvas), 3 * 20);
+    }
+
+    #[test]
+    fn partial_draws_match_full_ones() {
+        let mut canvas = Canvas::new();
+        let mut chart = chart(10, Scale::Fixed(-50, 50));
+        chart.draw(&mut canvas).unwrap();
+        // one and several samples per draw, around the ring more than once
+        let mut value = 0;
+        for batch in [1, 1, 3, 1, 5, 9, 1, 12, 2].iter() {
+            for _ in 0..*batch {
+                value = (value * 7 + 13) % 100 - 50;
+                chart.push(&[value, -value]);
+            }
+            chart.draw(&mut canvas).unwrap();
+            assert!(!canvas.drawn_outside(chart.bounds));
+            assert_eq!(canvas.pixels[..], redrawn(&mut chart).pixels[..]);
+        }
+    }
+
+    #[test]
+    fn redraws_all_on_new_range() {
+        let mut canvas = Canvas::new();
+        let mut chart = chart(10, Scale::Auto);
+        chart.push(&[1, 2]);
+        chart.draw(&mut canvas).unwrap();
+        chart.push(&[1, 2]);
+        assert_eq!(draw(&mut chart, &mut canvas), 20);
+        chart.push(&[1000, 2]);
+        assert_eq!(draw(&mut chart, &mut canvas), 10 * 20);
+        assert_eq!(canvas.pixels[..], redrawn(&mut chart).pixels[..]);
+    }
+}
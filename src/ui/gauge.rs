@@ -0,0 +1,150 @@
+//! Arc gauge
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{rect_size, Style, Widget};
+
+/// Where the arc starts, in degrees clockwise from 3 o'clock
+const START: i32 = 135;
+/// Length of the arc, the opening is at the bottom
+const SWEEP: i32 = 270;
+
+/// 270° ring filled clockwise in proportion to `value / max`
+pub struct Gauge {
+    center: Point,
+    radius: i32,
+    thickness: i32,
+    value: u32,
+    max: u32,
+    style: Style,
+    track: Rgb565,
+    /// Filled degrees on screen, `None` before the first draw
+    drawn: Option<i32>,
+}
+
+impl Gauge {
+    /// The largest ring centered in `bounds`
+    pub fn new(bounds: Rectangle, max: u32) -> Self {
+        let size = rect_size(&bounds);
+        let radius = (size.width.min(size.height) / 2) as i32;
+        Gauge {
+            center: bounds.top_left + Point::new(size.width as i32 / 2, size.height as i32 / 2),
+            radius,
+            thickness: (radius / 4).max(2),
+            value: 0,
+            max: max.max(1),
+            style: Style::default(),
+            track: Rgb565::new(8, 16, 8),
+            drawn: None,
+        }
+    }
+
+    /// Width of the ring
+    pub fn thickness(mut self, thickness: u32) -> Self {
+        self.thickness = (thickness as i32).min(self.radius).max(1);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Colour of the empty part of the ring
+    pub fn track(mut self, track: Rgb565) -> Self {
+        self.track = track;
+        self
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Sets the value, clamped to `max`
+    pub fn set(&mut self, value: u32) {
+        self.value = value.min(self.max);
+    }
+
+    fn filled(&self) -> i32 {
+        (self.value as u64 * SWEEP as u64 / self.max as u64) as i32
+    }
+
+    /// Ring pixels with an arc position in `from..to` degrees, and their colour
+    fn pixels(&self, from: i32, to: i32, filled: i32) -> impl Iterator<Item = Pixel<Rgb565>> + '_ {
+        let outer = self.radius * self.radius;
+        let inner = (self.radius - self.thickness) * (self.radius - self.thickness);
+        let r = self.radius;
+        (-r..=r)
+            .flat_map(move |dy| (-r..=r).map(move |dx| (dx, dy)))
+            .filter_map(move |(dx, dy)| {
+                let d2 = dx * dx + dy * dy;
+                if d2 > outer || d2 <= inner {
+                    return None;
+                }
+                let at = (angle(dx, dy) - START).rem_euclid(360);
+                if at >= SWEEP || at < from || at >= to {
+                    return None;
+                }
+                let color = if at < filled {
+                    self.style.accent
+                } else {
+                    self.track
+                };
+                Some(Pixel(self.center + Point::new(dx, dy), color))
+            })
+    }
+}
+
+/// Angle of (dx, dy) in degrees clockwise from 3 o'clock, y pointing down.
+/// Within a degree, no trigonometry needed.
+fn angle(dx: i32, dy: i32) -> i32 {
+    let (ax, ay) = (dx.abs() as f32, dy.abs() as f32);
+    if ax == 0.0 && ay == 0.0 {
+        return 0;
+    }
+    // atan(z) ~ 45z + 15.66z(1 - z) degrees for 0 <= z <= 1
+    let atan = |z: f32| 45.0 * z + 15.66 * z * (1.0 - z);
+    let a = if ax >= ay {
+        atan(ay / ax)
+    } else {
+        90.0 - atan(ax / ay)
+    };
+    let a = match (dx < 0, dy < 0) {
+        (false, false) => a,
+        (true, false) => 180.0 - a,
+        (true, true) => 180.0 + a,
+        (false, true) => 360.0 - a,
+    };
+    (a + 0.5) as i32 % 360
+}
+
+impl<D: DrawTarget<Rgb565>> Widget<D> for Gauge {
+    fn bounds(&self) -> Rectangle {
+        let r = Point::new(self.radius, self.radius);
+        Rectangle::new(self.center - r, self.center + r)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.drawn != Some(self.filled())
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        let filled = self.filled();
+        // only the part of the arc between the old and new value
+        let (from, to) = match self.drawn {
+            None => (0, SWEEP),
+            Some(drawn) => (drawn.min(filled), drawn.max(filled)),
+        };
+        target.draw_iter(self.pixels(from, to, filled))?;
+        self.drawn = Some(filled);
+        Ok(())
+    }
+}
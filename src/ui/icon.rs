@@ -0,0 +1,101 @@
+//! 1bpp icons
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{fill, rect, Style, Widget};
+
+/// Monochrome bitmap, rows padded to whole bytes, most significant bit first
+pub struct Icon {
+    top_left: Point,
+    size: Size,
+    bitmap: &'static [u8],
+    style: Style,
+    visible: bool,
+    dirty: bool,
+}
+
+impl Icon {
+    pub fn new(top_left: Point, width: u32, height: u32, bitmap: &'static [u8]) -> Self {
+        Icon {
+            top_left,
+            size: Size::new(width, height),
+            bitmap,
+            style: Style::default(),
+            visible: true,
+            dirty: true,
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        if style != self.style {
+            self.style = style;
+            self.dirty = true;
+        }
+    }
+
+    /// Swaps the bitmap, same size
+    pub fn set_bitmap(&mut self, bitmap: &'static [u8]) {
+        if bitmap.as_ptr() != self.bitmap.as_ptr() {
+            self.bitmap = bitmap;
+            self.dirty = true;
+        }
+    }
+
+    /// Hidden icons are erased with the background
+    pub fn set_visible(&mut self, visible: bool) {
+        if visible != self.visible {
+            self.visible = visible;
+            self.dirty = true;
+        }
+    }
+
+    fn bit(&self, x: u32, y: u32) -> bool {
+        let stride = (self.size.width as usize).div_ceil(8);
+        let i = y as usize * stride + x as usize / 8;
+        self.bitmap
+            .get(i)
+            .is_some_and(|b| b & (0x80 >> (x % 8)) != 0)
+    }
+}
+
+impl<D: DrawTarget<Rgb565>> Widget<D> for Icon {
+    fn bounds(&self) -> Rectangle {
+        rect(self.top_left, self.size)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        self.dirty = false;
+        if !self.visible {
+            return fill(target, Widget::<D>::bounds(self), self.style.bg);
+        }
+        let (w, h) = (self.size.width, self.size.height);
+        let pixels = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let color = if self.bit(x, y) {
+                    self.style.fg
+                } else {
+                    self.style.bg
+                };
+                Pixel(self.top_left + Point::new(x as i32, y as i32), color)
+            });
+        target.draw_iter(pixels)
+    }
+}
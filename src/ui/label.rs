@@ -0,0 +1,294 @@
+//! Text widgets
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::fonts::Font;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{draw_text, Align, Style, TextBuf, Widget};
+
+/// One line of text
+pub struct Label<F> {
+    bounds: Rectangle,
+    font: F,
+    text: TextBuf,
+    align: Align,
+    style: Style,
+    dirty: bool,
+}
+
+impl<F: Font + Clone + Copy> Label<F> {
+    pub fn new(bounds: Rectangle, font: F, text: &str) -> Self {
+        Label {
+            bounds,
+            font,
+            text: TextBuf::from(text),
+            align: Align::Left,
+            style: Style::default(),
+            dirty: true,
+        }
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.replace(TextBuf::from(text));
+    }
+
+    /// Sets the text from `format_args!`
+    pub fn set_fmt(&mut self, args: fmt::Arguments) {
+        self.replace(TextBuf::from_fmt(args));
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        if style != self.style {
+            self.style = style;
+            self.dirty = true;
+        }
+    }
+
+    fn replace(&mut self, text: TextBuf) {
+        if text != self.text {
+            self.text = text;
+            self.dirty = true;
+        }
+    }
+}
+
+impl<D: DrawTarget<Rgb565>, F: Font + Clone + Copy> Widget<D> for Label<F> {
+    fn bounds(&self) -> Rectangle {
+        self.bounds
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        self.dirty = false;
+        draw_text(
+            target,
+            self.bounds,
+            self.text.as_str(),
+            self.font,
+            self.align,
+            self.style,
+        )
+    }
+}
+
+/// A fixed point number and its unit, right aligned, like `23.5C`
+pub struct Readout<F> {
+    label: Label<F>,
+    value: Option<i32>,
+    decimals: u8,
+    unit: &'static str,
+}
+
+impl<F: Font + Clone + Copy> Readout<F> {
+    /// Starts empty, shown as `--`
+    pub fn new(bounds: Rectangle, font: F, unit: &'static str) -> Self {
+        let mut readout = Readout {
+            label: Label::new(bounds, font, "").align(Align::Right),
+            value: None,
+            decimals: 0,
+            unit,
+        };
+        readout.format();
+        readout
+    }
+
+    /// Digits after the point: `set(235)` shows `23.5` with 1
+    pub fn decimals(mut self, decimals: u8) -> Self {
+        self.decimals = decimals;
+        self.format();
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.label = self.label.align(align);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.label = self.label.style(style);
+        self
+    }
+
+    pub fn value(&self) -> Option<i32> {
+        self.value
+    }
+
+    /// Sets the value, in units of 10^-decimals
+    pub fn set(&mut self, value: i32) {
+        if self.value != Some(value) {
+            self.value = Some(value);
+            self.format();
+        }
+    }
+
+    /// Back to `--`, e.g. when a sensor is gone
+    pub fn clear(&mut self) {
+        if self.value.is_some() {
+            self.value = None;
+            self.format();
+        }
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.label.set_style(style);
+    }
+
+    fn format(&mut self) {
+        let mut text = TextBuf::new();
+        let _ = match self.value {
+            None => write!(text, "--{}", self.unit),
+            Some(value) if self.decimals == 0 => write!(text, "{}{}", value, self.unit),
+            Some(value) => {
+                let scale = 10u32.pow(self.decimals as u32);
+                let abs = value.unsigned_abs();
+                let sign = if value < 0 { "-" } else { "" };
+                write!(
+                    text,
+                    "{}{}.{:0width$}{}",
+                    sign,
+                    abs / scale,
+                    abs % scale,
+                    self.unit,
+                    width = self.decimals as usize
+                )
+            }
+        };
+        self.label.replace(text);
+    }
+}
+
+impl<D: DrawTarget<Rgb565>, F: Font + Clone + Copy> Widget<D> for Readout<F> {
+    fn bounds(&self) -> Rectangle {
+        Widget::<D>::bounds(&self.label)
+    }
+
+    fn is_dirty(&self) -> bool {
+        Widget::<D>::is_dirty(&self.label)
+    }
+
+    fn invalidate(&mut self) {
+        Widget::<D>::invalidate(&mut self.label)
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        self.label.draw(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::fonts::Font6x8;
+    use embedded_graphics::geometry::{Point, Size};
+
+    use super::*;
+    use crate::ui::tests::Canvas;
+    use crate::ui::{rect, update};
+
+    fn bounds() -> Rectangle {
+        rect(Point::new(0, 0), Size::new(80, 10))
+    }
+
+    fn readout(decimals: u8, unit: &'static str) -> Readout<Font6x8> {
+        Readout::new(bounds(), Font6x8, unit).decimals(decimals)
+    }
+
+    fn shown(readout: &Readout<Font6x8>) -> &str {
+        readout.label.text()
+    }
+
+    #[test]
+    fn formats_readouts() {
+        let mut temp = readout(1, "C");
+        assert_eq!(shown(&temp), "--C");
+        temp.set(235);
+        assert_eq!(shown(&temp), "23.5C");
+        temp.set(-235);
+        assert_eq!(shown(&temp), "-23.5C");
+        // the sign is kept when the whole part is zero
+        temp.set(-5);
+        assert_eq!(shown(&temp), "-0.5C");
+        temp.set(0);
+        assert_eq!(shown(&temp), "0.0C");
+        temp.clear();
+        assert_eq!((shown(&temp), temp.value()), ("--C", None));
+
+        let mut volts = readout(2, "V");
+        volts.set(5);
+        assert_eq!(shown(&volts), "0.05V");
+        volts.set(i32::MIN);
+        assert_eq!(shown(&volts), "-21474836.48V");
+
+        let mut count = readout(0, "");
+        count.set(-7);
+        assert_eq!(shown(&count), "-7");
+        count.set(i32::MAX);
+        assert_eq!(shown(&count), "2147483647");
+    }
+
+    #[test]
+    fn formats_labels() {
+        let mut label = Label::new(bounds(), Font6x8, "ready");
+        assert_eq!(label.text(), "ready");
+        label.set_fmt(format_args!("{:>4}%", 42));
+        assert_eq!(label.text(), "  42%");
+        label.set_text("0123456789012345678901234567890123456789");
+        assert_eq!(label.text(), "01234567890123456789012345678901");
+    }
+
+    #[test]
+    fn redraws_only_when_changed() {
+        let mut canvas = Canvas::new();
+        let mut label = Label::new(bounds(), Font6x8, "idle");
+        let mut temp = Readout::new(rect(Point::new(0, 20), Size::new(80, 10)), Font6x8, "C");
+        temp.set(215);
+
+        update(&mut canvas, &mut [&mut label, &mut temp]).unwrap();
+        let first = canvas.writes;
+        // each widget fills its bounds once, then the text
+        assert!(first >= 2 * 80 * 10);
+        update(&mut canvas, &mut [&mut label, &mut temp]).unwrap();
+        assert_eq!(canvas.writes, first);
+
+        // same text, value and style
+        label.set_text("idle");
+        label.set_style(Style::default());
+        temp.set(215);
+        update(&mut canvas, &mut [&mut label, &mut temp]).unwrap();
+        assert_eq!(canvas.writes, first);
+
+        temp.set(216);
+        assert!(!Widget::<Canvas>::is_dirty(&label));
+        assert!(Widget::<Canvas>::is_dirty(&temp));
+        update(&mut canvas, &mut [&mut label, &mut temp]).unwrap();
+        let second = canvas.writes;
+        assert!(second > first);
+
+        Widget::<Canvas>::invalidate(&mut label);
+        update(&mut canvas, &mut [&mut label, &mut temp]).unwrap();
+        assert!(canvas.writes > second);
+    }
+}
@@ -0,0 +1,217 @@
+//! Splitting the screen into widget bounds
+//!
+//! All rectangles are inclusive, like `Rectangle` in embedded-graphics 0.6.
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::primitives::Rectangle;
+
+use super::rect_size;
+
+/// The whole screen
+pub fn screen(width: u32, height: u32) -> Rectangle {
+    Rectangle::new(
+        Point::zero(),
+        Point::new(width as i32 - 1, height as i32 - 1),
+    )
+}
+
+/// Shrinks `area` by `margin` on every side
+pub fn inset(area: Rectangle, margin: i32) -> Rectangle {
+    Rectangle::new(
+        area.top_left + Point::new(margin, margin),
+        area.bottom_right - Point::new(margin, margin),
+    )
+}
+
+/// Equal cells in rows and columns
+#[derive(Clone, Copy, Debug)]
+pub struct Grid {
+    area: Rectangle,
+    cols: u32,
+    rows: u32,
+    gap: u32,
+}
+
+impl Grid {
+    pub fn new(area: Rectangle, cols: u32, rows: u32) -> Self {
+        Grid {
+            area,
+            cols: cols.max(1),
+            rows: rows.max(1),
+            gap: 0,
+        }
+    }
+
+    /// Space between cells
+    pub fn gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// One cell
+    pub fn cell(&self, col: u32, row: u32) -> Rectangle {
+        self.span(col, row, 1, 1)
+    }
+
+    /// `cols` by `rows` cells from (`col`, `row`), gaps included
+    pub fn span(&self, col: u32, row: u32, cols: u32, rows: u32) -> Rectangle {
+        let (x0, x1) = Self::split(
+            self.area.top_left.x,
+            rect_size(&self.area).width,
+            self.cols,
+            self.gap,
+            col,
+            cols,
+        );
+        let (y0, y1) = Self::split(
+            self.area.top_left.y,
+            rect_size(&self.area).height,
+            self.rows,
+            self.gap,
+            row,
+            rows,
+        );
+        Rectangle::new(Point::new(x0, y0), Point::new(x1, y1))
+    }
+
+    /// Start and end of cells `first..first + n` of `count` along one axis.
+    /// Rounding goes to the last cells, so the total is exact.
+    fn split(origin: i32, len: u32, count: u32, gap: u32, first: u32, n: u32) -> (i32, i32) {
+        let room = len.saturating_sub(gap * (count - 1));
+        let edge = |i: u32| origin + (room * i / count + gap * i) as i32;
+        let last = (first + n).min(count);
+        (edge(first), edge(last) - gap as i32 - 1)
+    }
+}
+
+/// Direction of a `Stack`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+/// Cuts slices off an area one after the other, top to bottom or left to right
+#[derive(Clone, Copy, Debug)]
+pub struct Stack {
+    area: Rectangle,
+    direction: Direction,
+    gap: u32,
+}
+
+impl Stack {
+    pub fn vertical(area: Rectangle) -> Self {
+        Stack {
+            area,
+            direction: Direction::Vertical,
+            gap: 0,
+        }
+    }
+
+    pub fn horizontal(area: Rectangle) -> Self {
+        Stack {
+            area,
+            direction: Direction::Horizontal,
+            gap: 0,
+        }
+    }
+
+    /// Space between slices
+    pub fn gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Next slice, `len` pixels high or wide, less if the area runs out
+    pub fn take(&mut self, len: u32) -> Rectangle {
+        let Rectangle {
+            top_left,
+            bottom_right,
+        } = self.area;
+        let len = len as i32;
+        let gap = self.gap as i32;
+        match self.direction {
+            Direction::Vertical => {
+                let end = (top_left.y + len - 1).min(bottom_right.y);
+                self.area.top_left.y = end + 1 + gap;
+                Rectangle::new(top_left, Point::new(bottom_right.x, end))
+            }
+            Direction::Horizontal => {
+                let end = (top_left.x + len - 1).min(bottom_right.x);
+                self.area.top_left.x = end + 1 + gap;
+                Rectangle::new(top_left, Point::new(end, bottom_right.y))
+            }
+        }
+    }
+
+    /// What is left
+    pub fn rest(&mut self) -> Rectangle {
+        let size = rect_size(&self.area);
+        match self.direction {
+            Direction::Vertical => self.take(size.height),
+            Direction::Horizontal => self.take(size.width),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corners(r: Rectangle) -> (i32, i32, i32, i32) {
+        (
+            r.top_left.x,
+            r.top_left.y,
+            r.bottom_right.x,
+            r.bottom_right.y,
+        )
+    }
+
+    #[test]
+    fn grid_cells_meet() {
+        let grid = Grid::new(screen(160, 80), 2, 2).gap(2);
+        assert_eq!(corners(grid.cell(0, 0)), (0, 0, 78, 38));
+        assert_eq!(corners(grid.cell(1, 0)), (81, 0, 159, 38));
+        assert_eq!(corners(grid.cell(1, 1)), (81, 41, 159, 79));
+        assert_eq!(corners(grid.span(0, 1, 2, 1)), (0, 41, 159, 79));
+        // spans are cut at the last cell
+        assert_eq!(corners(grid.span(1, 0, 5, 1)), (81, 0, 159, 38));
+
+        // 100 does not split in 3: no pixel is lost or shared
+        for &gap in &[0, 1, 3] {
+            let area = Rectangle::new(Point::new(5, 7), Point::new(104, 7));
+            let grid = Grid::new(area, 3, 1).gap(gap);
+            assert_eq!(grid.cell(0, 0).top_left.x, 5);
+            assert_eq!(grid.cell(2, 0).bottom_right.x, 104);
+            for col in 0..2 {
+                let (a, b) = (grid.cell(col, 0), grid.cell(col + 1, 0));
+                assert_eq!(a.bottom_right.x + 1 + gap as i32, b.top_left.x);
+            }
+            let widths: u32 = (0..3).map(|col| rect_size(&grid.cell(col, 0)).width).sum();
+            assert_eq!(widths + 2 * gap, 100);
+        }
+    }
+
+    #[test]
+    fn grid_without_cells() {
+        // zero rows or columns count as one
+        let grid = Grid::new(screen(160, 80), 0, 0);
+        assert_eq!(corners(grid.cell(0, 0)), (0, 0, 159, 79));
+    }
+
+    #[test]
+    fn stacks_slices() {
+        let mut rows = Stack::vertical(screen(160, 80)).gap(1);
+        assert_eq!(corners(rows.take(10)), (0, 0, 159, 9));
+        assert_eq!(corners(rows.take(20)), (0, 11, 159, 30));
+        assert_eq!(corners(rows.rest()), (0, 32, 159, 79));
+        // nothing left
+        assert_eq!(rect_size(&rows.take(5)).height, 0);
+
+        let mut cols = Stack::horizontal(inset(screen(160, 80), 2));
+        assert_eq!(corners(cols.take(50)), (2, 2, 51, 77));
+        // less than asked for when the area runs out
+        assert_eq!(corners(cols.take(200)), (52, 2, 157, 77));
+        assert_eq!(rect_size(&cols.rest()).width, 0);
+    }
+}
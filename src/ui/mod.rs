@@ -0,0 +1,452 @@
+//! Retained-mode widgets for the 160x80 screen
+//!
+//! Widgets own their place and value, and only redraw when the value changes.
+//! Place them with `layout`, update them, then draw them all every loop:
+//!
+//! ```ignore
+//! let screen = layout::screen(160, 80);
+//! let mut rows = Stack::vertical(screen);
+//! let mut status = StatusBar::new(rows.take(10));
+//! let grid = Grid::new(rows.rest(), 2, 2).gap(2);
+//! let mut temp = Readout::new(grid.cell(0, 0), Font8x16, "C").decimals(1);
+//! let mut vref = Readout::new(grid.cell(1, 0), Font8x16, "V").decimals(2);
+//! let mut bar = ProgressBar::new(grid.span(0, 1, 2, 1), 4095);
+//!
+//! loop {
+//!     temp.set(read_temperature_decicelsius());
+//!     bar.set(adc.read_channel(0) as u32);
+//!     ui::update(&mut lcd, &mut [&mut status, &mut temp, &mut vref, &mut bar])?;
+//! }
+//! ```
+//!
+//! Everything is generic over `DrawTarget<Rgb565>`, so the rendering runs on
+//! the host against an in-memory target as well as on `lcd::Lcd`.
+
+use core::fmt;
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::fonts::{Font, Text};
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::{primitive_style, text_style};
+
+pub mod bar;
+pub mod chart;
+pub mod gauge;
+pub mod icon;
+pub mod label;
+pub mod layout;
+pub mod status;
+
+pub use self::bar::ProgressBar;
+pub use self::chart::{Chart, Scale};
+pub use self::gauge::Gauge;
+pub use self::icon::Icon;
+pub use self::label::{Label, Readout};
+pub use self::layout::{Grid, Stack};
+pub use self::status::StatusBar;
+
+/// Something placed on the screen that draws itself when changed
+pub trait Widget<D: DrawTarget<Rgb565>> {
+    /// Area covered, inclusive
+    fn bounds(&self) -> Rectangle;
+
+    /// Whether the next `draw` has something to do
+    fn is_dirty(&self) -> bool;
+
+    /// Forces a full redraw, e.g. after the screen was cleared
+    fn invalidate(&mut self);
+
+    /// Redraws what changed since the last call
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error>;
+}
+
+/// Draws every widget that changed
+pub fn update<D: DrawTarget<Rgb565>>(
+    target: &mut D,
+    widgets: &mut [&mut dyn Widget<D>],
+) -> Result<(), D::Error> {
+    for widget in widgets.iter_mut() {
+        if widget.is_dirty() {
+            widget.draw(target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Colours of a widget
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    /// Text and outlines
+    pub fg: Rgb565,
+    /// Background, also used to erase
+    pub bg: Rgb565,
+    /// Filled part of bars and gauges
+    pub accent: Rgb565,
+}
+
+impl Style {
+    pub const fn new(fg: Rgb565, bg: Rgb565, accent: Rgb565) -> Self {
+        Style { fg, bg, accent }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style::new(Rgb565::WHITE, Rgb565::BLACK, Rgb565::GREEN)
+    }
+}
+
+/// Horizontal placement of text in its bounds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Longest text of labels and status bars, in bytes
+pub const TEXT_CAPACITY: usize = 32;
+
+/// Fixed size string, `fmt::Write` truncates instead of failing
+#[derive(Clone, Copy)]
+pub struct TextBuf {
+    buf: [u8; TEXT_CAPACITY],
+    len: usize,
+}
+
+impl TextBuf {
+    pub const fn new() -> Self {
+        TextBuf {
+            buf: [0; TEXT_CAPACITY],
+            len: 0,
+        }
+    }
+
+    pub fn from_fmt(args: fmt::Arguments) -> Self {
+        let mut text = TextBuf::new();
+        let _ = fmt::Write::write_fmt(&mut text, args);
+        text
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only whole chars are stored
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl From<&str> for TextBuf {
+    fn from(s: &str) -> Self {
+        let mut text = TextBuf::new();
+        let _ = fmt::Write::write_str(&mut text, s);
+        text
+    }
+}
+
+impl Default for TextBuf {
+    fn default() -> Self {
+        TextBuf::new()
+    }
+}
+
+impl PartialEq for TextBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl fmt::Write for TextBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.chars() {
+            let n = ch.len_utf8();
+            if self.len + n > TEXT_CAPACITY {
+                break;
+            }
+            ch.encode_utf8(&mut self.buf[self.len..]);
+            self.len += n;
+        }
+        Ok(())
+    }
+}
+
+/// Inclusive rectangle of `size` at `top_left`
+pub fn rect(top_left: Point, size: Size) -> Rectangle {
+    Rectangle::new(
+        top_left,
+        top_left + Point::new(size.width as i32 - 1, size.height as i32 - 1),
+    )
+}
+
+/// Size of an inclusive rectangle
+pub fn rect_size(r: &Rectangle) -> Size {
+    Size::new(
+        (r.bottom_right.x - r.top_left.x + 1).max(0) as u32,
+        (r.bottom_right.y - r.top_left.y + 1).max(0) as u32,
+    )
+}
+
+/// Fills an inclusive rectangle
+pub(crate) fn fill<D: DrawTarget<Rgb565>>(
+    target: &mut D,
+    area: Rectangle,
+    color: Rgb565,
+) -> Result<(), D::Error> {
+    if area.bottom_right.x < area.top_left.x || area.bottom_right.y < area.top_left.y {
+        return Ok(());
+    }
+    area.into_styled(primitive_style!(fill_color = color))
+        .draw(target)
+}
+
+/// Erases `bounds` and draws one line of text in it, vertically centered.
+/// Characters that do not fit are left out, and a font taller than `bounds`
+/// is cut at its edges.
+pub(crate) fn draw_text<D, F>(
+    target: &mut D,
+    bounds: Rectangle,
+    text: &str,
+    font: F,
+    align: Align,
+    style: Style,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Rgb565>,
+    F: Font + Clone + Copy,
+{
+    fill(target, bounds, style.bg)?;
+
+    let size = rect_size(&bounds);
+    let char_width = F::CHARACTER_SIZE.width + F::CHARACTER_SPACING;
+    let max_chars = (size.width / char_width.max(1)) as usize;
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| text.len());
+    let text = &text[..end];
+
+    let width = text.chars().count() as u32 * char_width;
+    let x = match align {
+        Align::Left => 0,
+        Align::Center => (size.width - width) / 2,
+        Align::Right => size.width - width,
+    };
+    let y = size.height.saturating_sub(F::CHARACTER_SIZE.height) / 2;
+    let style = text_style!(
+        font = font,
+        text_color = style.fg,
+        background_color = style.bg
+    );
+    let mut clipped = Clipped {
+        target,
+        area: bounds,
+    };
+    Text::new(text, bounds.top_left + Point::new(x as i32, y as i32))
+        .into_styled(style)
+        .draw(&mut clipped)
+}
+
+/// Drops the pixels outside an inclusive rectangle
+struct Clipped<'a, D> {
+    target: &'a mut D,
+    area: Rectangle,
+}
+
+impl<D: DrawTarget<Rgb565>> DrawTarget<Rgb565> for Clipped<'_, D> {
+    type Error = D::Error;
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), D::Error> {
+        let Pixel(p, _) = pixel;
+        let Rectangle {
+            top_left,
+            bottom_right,
+        } = self.area;
+        if p.x < top_left.x || p.x > bottom_right.x || p.y < top_left.y || p.y > bottom_right.y {
+            return Ok(());
+        }
+        self.target.draw_pixel(pixel)
+    }
+
+    fn size(&self) -> Size {
+        self.target.size()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use core::convert::Infallible;
+
+    use embedded_graphics::fonts::Font6x8;
+
+    use super::*;
+
+    /// Colour of pixels nothing has drawn yet
+    pub(crate) const BLANK: Rgb565 = Rgb565::new(1, 2, 3);
+
+    /// 160x80 screen in memory, counting pixel writes
+    pub(crate) struct Canvas {
+        pub pixels: [[Rgb565; 160]; 80],
+        pub writes: usize,
+    }
+
+    impl Canvas {
+        pub fn new() -> Self {
+            Canvas {
+                pixels: [[BLANK; 160]; 80],
+                writes: 0,
+            }
+        }
+
+        pub fn pixel(&self, x: i32, y: i32) -> Rgb565 {
+            self.pixels[y as usize][x as usize]
+        }
+
+        /// Bounding box of the pixels in `color`
+        pub fn find(&self, color: Rgb565) -> Option<Rectangle> {
+            let mut found: Option<Rectangle> = None;
+            for (y, row) in self.pixels.iter().enumerate() {
+                for (x, &c) in row.iter().enumerate() {
+                    if c != color {
+                        continue;
+                    }
+                    let p = Point::new(x as i32, y as i32);
+                    found = Some(match found {
+                        None => Rectangle::new(p, p),
+                        Some(r) => Rectangle::new(
+                            Point::new(r.top_left.x.min(p.x), r.top_left.y.min(p.y)),
+                            Point::new(r.bottom_right.x.max(p.x), r.bottom_right.y.max(p.y)),
+                        ),
+                    });
+                }
+            }
+            found
+        }
+
+        /// Whether anything outside `area` was drawn
+        pub fn drawn_outside(&self, area: Rectangle) -> bool {
+            let inside = |x: i32, y: i32| {
+                x >= area.top_left.x
+                    && x <= area.bottom_right.x
+                    && y >= area.top_left.y
+                    && y <= area.bottom_right.y
+            };
+            (0..80).any(|y| (0..160).any(|x| !inside(x, y) && self.pixel(x, y) != BLANK))
+        }
+    }
+
+    impl DrawTarget<Rgb565> for Canvas {
+        type Error = Infallible;
+
+        fn draw_pixel(&mut self, Pixel(p, color): Pixel<Rgb565>) -> Result<(), Infallible> {
+            self.writes += 1;
+            if (0..160).contains(&p.x) && (0..80).contains(&p.y) {
+                self.pixels[p.y as usize][p.x as usize] = color;
+            }
+            Ok(())
+        }
+
+        fn size(&self) -> Size {
+            Size::new(160, 80)
+        }
+    }
+
+    /// 60x12 at (10, 20)
+    fn bounds() -> Rectangle {
+        rect(Point::new(10, 20), Size::new(60, 12))
+    }
+
+    fn text(s: &str, bounds: Rectangle, align: Align) -> Canvas {
+        let mut canvas = Canvas::new();
+        draw_text(&mut canvas, bounds, s, Font6x8, align, Style::default()).unwrap();
+        canvas
+    }
+
+    /// Whether `b` is `a` moved `dx` to the right, within `bounds`
+    fn shifted(a: &Canvas, b: &Canvas, dx: i32, bounds: Rectangle) -> bool {
+        let (x0, x1) = (bounds.top_left.x, bounds.bottom_right.x);
+        (bounds.top_left.y..=bounds.bottom_right.y).all(|y| {
+            (x0..=x1).all(|x| {
+                let moved = if x - dx < x0 {
+                    Style::default().bg
+                } else {
+                    a.pixel(x - dx, y)
+                };
+                b.pixel(x, y) == moved
+            })
+        })
+    }
+
+    #[test]
+    fn erases_and_centers_vertically() {
+        let canvas = text("Hi", bounds(), Align::Left);
+        assert!(!canvas.drawn_outside(bounds()));
+        // every pixel of the bounds is erased or drawn
+        assert_eq!(canvas.find(BLANK).map(|r| r.top_left.y), Some(0));
+        let ink = canvas.find(Rgb565::WHITE).unwrap();
+        assert_eq!(ink.top_left.x, 10);
+        // 8 pixel font in 12 pixels: 2 above
+        assert!(ink.top_left.y >= 22 && ink.bottom_right.y <= 29);
+    }
+
+    #[test]
+    fn aligns_text() {
+        let left = text("abc", bounds(), Align::Left);
+        // 3 chars of 6 pixels in 60
+        assert!(shifted(
+            &left,
+            &text("abc", bounds(), Align::Right),
+            42,
+            bounds()
+        ));
+        assert!(shifted(
+            &left,
+            &text("abc", bounds(), Align::Center),
+            21,
+            bounds()
+        ));
+        let right = text("abc", bounds(), Align::Right);
+        assert!(right.find(Rgb565::WHITE).unwrap().bottom_right.x <= 69);
+    }
+
+    #[test]
+    fn cuts_text_at_the_right_edge() {
+        let cut = text("0123456789abcdef", bounds(), Align::Left);
+        assert!(!cut.drawn_outside(bounds()));
+        // 10 chars fit, whatever the alignment
+        let fits = text("0123456789", bounds(), Align::Left);
+        assert_eq!(cut.pixels[..], fits.pixels[..]);
+        let right = text("0123456789abcdef", bounds(), Align::Right);
+        assert_eq!(right.pixels[..], fits.pixels[..]);
+        let center = text("0123456789abcdef", bounds(), Align::Center);
+        assert_eq!(center.pixels[..], fits.pixels[..]);
+    }
+
+    #[test]
+    fn clips_to_short_bounds() {
+        // lower than the font
+        let short = rect(Point::new(10, 20), Size::new(60, 5));
+        let canvas = text("Ag", short, Align::Center);
+        assert!(!canvas.drawn_outside(short));
+        assert!(canvas.find(Rgb565::WHITE).is_some());
+
+        // narrower than one char
+        let narrow = rect(Point::new(10, 20), Size::new(4, 12));
+        let canvas = text("W", narrow, Align::Right);
+        assert!(!canvas.drawn_outside(narrow));
+    }
+
+    #[test]
+    fn truncates_text_buffers() {
+        let long = TextBuf::from("0123456789012345678901234567890123456789");
+        assert_eq!(long.as_str().len(), TEXT_CAPACITY);
+        // a char that does not fit whole is left out
+        let wide = TextBuf::from_fmt(format_args!("{:31}温", ""));
+        assert_eq!(wide.as_str().len(), 31);
+    }
+}
@@ -0,0 +1,119 @@
+//! Status bar
+
+use core::fmt;
+
+use embedded_graphics::fonts::{Font, Font6x8};
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{draw_text, fill, rect_size, Align, Style, TextBuf, Widget};
+
+/// Strip with text on the left, like a title, and on the right, like a
+/// clock. The right text wins when both don't fit.
+pub struct StatusBar {
+    bounds: Rectangle,
+    left: TextBuf,
+    right: TextBuf,
+    style: Style,
+    dirty: bool,
+}
+
+impl StatusBar {
+    /// Usually the top 8 to 10 lines of the screen
+    pub fn new(bounds: Rectangle) -> Self {
+        StatusBar {
+            bounds,
+            left: TextBuf::new(),
+            right: TextBuf::new(),
+            style: Style::new(Rgb565::BLACK, Rgb565::new(21, 42, 21), Rgb565::BLUE),
+            dirty: true,
+        }
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn set_left(&mut self, text: &str) {
+        Self::replace(&mut self.left, TextBuf::from(text), &mut self.dirty);
+    }
+
+    pub fn set_left_fmt(&mut self, args: fmt::Arguments) {
+        Self::replace(&mut self.left, TextBuf::from_fmt(args), &mut self.dirty);
+    }
+
+    pub fn set_right(&mut self, text: &str) {
+        Self::replace(&mut self.right, TextBuf::from(text), &mut self.dirty);
+    }
+
+    pub fn set_right_fmt(&mut self, args: fmt::Arguments) {
+        Self::replace(&mut self.right, TextBuf::from_fmt(args), &mut self.dirty);
+    }
+
+    fn replace(old: &mut TextBuf, new: TextBuf, dirty: &mut bool) {
+        if new != *old {
+            *old = new;
+            *dirty = true;
+        }
+    }
+}
+
+impl<D: DrawTarget<Rgb565>> Widget<D> for StatusBar {
+    fn bounds(&self) -> Rectangle {
+        self.bounds
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    fn draw(&mut self, target: &mut D) -> Result<(), D::Error> {
+        self.dirty = false;
+        fill(target, self.bounds, self.style.bg)?;
+
+        // one pixel of padding on both ends
+        let Rectangle {
+            top_left,
+            bottom_right,
+        } = self.bounds;
+        let width = rect_size(&self.bounds).width as i32 - 2;
+        let char_width = Font6x8::CHARACTER_SIZE.width as i32;
+        let right_width = (self.right.as_str().chars().count() as i32 * char_width).min(width);
+        let left_end = top_left.x + width - right_width - char_width;
+
+        let right = Rectangle::new(
+            Point::new(bottom_right.x - right_width, top_left.y),
+            Point::new(bottom_right.x - 1, bottom_right.y),
+        );
+        draw_text(
+            target,
+            right,
+            self.right.as_str(),
+            Font6x8,
+            Align::Right,
+            self.style,
+        )?;
+        if left_end > top_left.x {
+            let left = Rectangle::new(
+                Point::new(top_left.x + 1, top_left.y),
+                Point::new(left_end, bottom_right.y),
+            );
+            draw_text(
+                target,
+                left,
+                self.left.as_str(),
+                Font6x8,
+                Align::Left,
+                self.style,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! Glyph Bitmap Distribution Format, the text format of X11 fonts
+//!
+//! Only what compiling needs is read: the ascent and descent, and each
+//! glyph's encoding, advance, bounding box and bitmap. Glyphs without a
+//! Unicode encoding (`ENCODING -1`) are skipped.
+
+use std::collections::BTreeMap;
+
+use crate::{Error, SourceFont, SourceGlyph};
+
+/// Glyph being read
+#[derive(Default)]
+struct Char {
+    encoding: Option<i64>,
+    advance: Option<i32>,
+    bbx: Option<(u32, u32, i32, i32)>,
+    bitmap: Vec<u8>,
+    /// Bitmap rows still to read
+    rows: Option<u32>,
+}
+
+pub fn parse(text: &str) -> Result<SourceFont, Error> {
+    let mut bbox = None;
+    let mut ascent = None;
+    let mut descent = None;
+    let mut glyphs = BTreeMap::new();
+    let mut current: Option<Char> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |msg: &str| Error::Bdf {
+            line: line_no,
+            msg: msg.to_string(),
+        };
+        let line = line.trim();
+
+        if let Some(c) = current.as_mut() {
+            if let Some(rows) = c.rows.filter(|&rows| rows > 0) {
+                if line == "ENDCHAR" {
+                    return Err(err("bitmap too short"));
+                }
+                let (width, _, _, _) = c.bbx.ok_or_else(|| err("BITMAP before BBX"))?;
+                let stride = (width as usize).div_ceil(8);
+                c.bitmap
+                    .extend(hex_row(line, stride).ok_or_else(|| err("bad bitmap row"))?);
+                c.rows = Some(rows - 1);
+                continue;
+            }
+        }
+
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        let int = |n: usize| -> Result<i64, Error> {
+            args.get(n)
+                .and_then(|a| a.parse().ok())
+                .ok_or_else(|| err(&format!("{} needs {} numbers", keyword, n + 1)))
+        };
+
+        match (keyword, current.as_mut()) {
+            ("FONTBOUNDINGBOX", None) => {
+                bbox = Some((
+                    int(0)? as u32,
+                    int(1)? as u32,
+                    int(2)? as i32,
+                    int(3)? as i32,
+                ))
+            }
+            ("FONT_ASCENT", None) => ascent = Some(int(0)? as i32),
+            ("FONT_DESCENT", None) => descent = Some(int(0)? as i32),
+            ("STARTCHAR", None) => current = Some(Char::default()),
+            ("STARTCHAR", Some(_)) => return Err(err("STARTCHAR before ENDCHAR")),
+            ("ENCODING", Some(c)) => c.encoding = Some(int(0)?),
+            ("DWIDTH", Some(c)) => c.advance = Some(int(0)? as i32),
+            ("BBX", Some(c)) => {
+                let (w, h) = (int(0)?, int(1)?);
+                if w < 0 || h < 0 {
+                    return Err(err("negative BBX"));
+                }
+                c.bbx = Some((w as u32, h as u32, int(2)? as i32, int(3)? as i32));
+            }
+            ("BITMAP", Some(c)) => {
+                let (_, height, _, _) = c.bbx.or(bbox).ok_or_else(|| err("BITMAP before BBX"))?;
+                c.bbx = c.bbx.or(bbox);
+                c.rows = Some(height);
+            }
+            ("ENDCHAR", Some(_)) => {
+                let c = current.take().unwrap_or_default();
+                let encoding = match c.encoding {
+                    Some(e) if e >= 0 => e as u32,
+                    _ => continue,
+                };
+                let (width, height, x_offset, y_offset) =
+                    c.bbx.or(bbox).ok_or_else(|| err("glyph without BBX"))?;
+                let advance = c.advance.unwrap_or(width as i32 + x_offset);
+                glyphs.insert(
+                    encoding,
+                    SourceGlyph::from_bits(
+                        advance,
+                        (width, height),
+                        (x_offset, y_offset),
+                        &c.bitmap,
+                    ),
+                );
+            }
+            ("ENDCHAR", None) => return Err(err("ENDCHAR before STARTCHAR")),
+            _ => {}
+        }
+    }
+
+    if current.is_some() {
+        return Err(Error::Bdf {
+            line: text.lines().count(),
+            msg: "missing ENDCHAR".to_string(),
+        });
+    }
+
+    // the properties, or else the bounding box of the font
+    let (ascent, descent) = match (ascent, descent, bbox) {
+        (Some(a), Some(d), _) => (a, d),
+        (a, d, Some((_, h, _, y))) => (a.unwrap_or(h as i32 + y), d.unwrap_or(-y)),
+        _ => {
+            return Err(Error::Bdf {
+                line: 0,
+                msg: "no FONT_ASCENT, FONT_DESCENT or FONTBOUNDINGBOX".to_string(),
+            })
+        }
+    };
+    Ok(SourceFont {
+        ascent,
+        descent,
+        glyphs,
+    })
+}
+
+/// First `stride` bytes of a row of hex digits, zero padded
+fn hex_row(line: &str, stride: usize) -> Option<Vec<u8>> {
+    if line.len() % 2 == 1 || !line.is_ascii() {
+        return None;
+    }
+    let mut row = (0..line.len() / 2)
+        .map(|i| u8::from_str_radix(&line[i * 2..i * 2 + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    row.resize(stride, 0);
+    Some(row)
+}
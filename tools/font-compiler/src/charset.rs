@@ -0,0 +1,97 @@
+//! Characters to compile
+//!
+//! A set of codepoints built from strings, text files, such as the strings of
+//! the firmware, and Unicode ranges like `U+4E00-U+9FFF`. Line breaks, tabs
+//! and other control characters are never added.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::Error;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Charset {
+    codepoints: BTreeSet<u32>,
+}
+
+impl Charset {
+    pub fn new() -> Self {
+        Charset::default()
+    }
+
+    /// Adds every character of `text`
+    pub fn add_str(&mut self, text: &str) {
+        self.codepoints
+            .extend(text.chars().filter(|c| !c.is_control()).map(|c| c as u32));
+    }
+
+    /// Adds every character of a UTF-8 text file
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Charset(format!("{}: {}", path.display(), e)))?;
+        self.add_str(&text);
+        Ok(())
+    }
+
+    /// Adds the codepoints from `first` to `last`, inclusive
+    pub fn add_range(&mut self, first: u32, last: u32) {
+        self.codepoints.extend(
+            (first..=last).filter(|&c| std::char::from_u32(c).is_some_and(|c| !c.is_control())),
+        );
+    }
+
+    /// Adds a range written `U+XXXX-U+YYYY`, or one codepoint `U+XXXX`. The
+    /// `U+` is optional, the digits are hexadecimal.
+    pub fn add_range_str(&mut self, range: &str) -> Result<(), Error> {
+        let (first, last) = parse_range(range)?;
+        self.add_range(first, last);
+        Ok(())
+    }
+
+    pub fn contains(&self, codepoint: u32) -> bool {
+        self.codepoints.contains(&codepoint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.codepoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codepoints.is_empty()
+    }
+
+    /// Codepoints in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.codepoints.iter().copied()
+    }
+}
+
+/// (first, last) of `U+XXXX-U+YYYY` or `U+XXXX`
+pub fn parse_range(range: &str) -> Result<(u32, u32), Error> {
+    let bad = || Error::Charset(format!("bad range {:?}, expected U+XXXX-U+YYYY", range));
+    let codepoint = |s: &str| {
+        let s = s.trim();
+        let digits = s
+            .strip_prefix("U+")
+            .or_else(|| s.strip_prefix("u+"))
+            .or_else(|| s.strip_prefix("0x"))
+            .unwrap_or(s);
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .filter(|&c| c <= 0x10ffff)
+            .ok_or_else(bad)
+    };
+    let (first, last) = match range.find('-') {
+        Some(i) => (codepoint(&range[..i])?, codepoint(&range[i + 1..])?),
+        None => {
+            let c = codepoint(range)?;
+            (c, c)
+        }
+    };
+    if first > last {
+        return Err(bad());
+    }
+    Ok((first, last))
+}
@@ -0,0 +1,170 @@
+//! Rasterizing glyphs into cells and writing the font blob
+
+use std::fmt::Write;
+
+use crate::format::{Header, Record, HEADER_LEN, NO_REPLACEMENT, RECORD_LEN};
+use crate::{Charset, Error, SourceFont, SourceGlyph};
+
+/// A compiled font
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compiled {
+    /// The font blob
+    pub data: Vec<u8>,
+    /// Glyphs in the blob
+    pub count: usize,
+    /// Characters asked for but not in the source font
+    pub missing: Vec<u32>,
+}
+
+/// Compiles the glyphs of `charset`, or all of them, plus the replacement
+/// glyph if the font has it, at 1, 2 or 4 bits per pixel
+pub fn compile(
+    font: &SourceFont,
+    charset: Option<&Charset>,
+    replacement: Option<char>,
+    bpp: u8,
+) -> Result<Compiled, Error> {
+    if ![1, 2, 4].contains(&bpp) {
+        return Err(Error::Depth(bpp));
+    }
+    let height = font.height();
+    if height <= 0 || height > 255 {
+        return Err(Error::Size(format!(
+            "font is {} pixels high, not 1 to 255",
+            height
+        )));
+    }
+
+    let mut missing = Vec::new();
+    let mut wanted: Vec<u32> = match charset {
+        Some(charset) => charset
+            .iter()
+            .filter(|&c| {
+                let found = font.glyphs.contains_key(&c);
+                if !found {
+                    missing.push(c);
+                }
+                found
+            })
+            .collect(),
+        None => font.glyphs.keys().copied().collect(),
+    };
+    let replacement = match replacement.map(|c| c as u32) {
+        Some(c) if font.glyphs.contains_key(&c) => {
+            if let Err(i) = wanted.binary_search(&c) {
+                wanted.insert(i, c);
+            }
+            c
+        }
+        Some(c) => {
+            if let Err(i) = missing.binary_search(&c) {
+                missing.insert(i, c);
+            }
+            NO_REPLACEMENT
+        }
+        None => NO_REPLACEMENT,
+    };
+
+    let mut records = Vec::with_capacity(wanted.len());
+    let mut bitmaps = Vec::new();
+    for &codepoint in &wanted {
+        let glyph = &font.glyphs[&codepoint];
+        let width = glyph.advance.max(0);
+        if width > 255 {
+            return Err(Error::Size(format!(
+                "U+{:04X} is {} pixels wide",
+                codepoint, width
+            )));
+        }
+        if bitmaps.len() >= 1 << 24 {
+            return Err(Error::Size("bitmaps over 16 MiB".to_string()));
+        }
+        records.push(Record {
+            codepoint,
+            width: width as u8,
+            offset: bitmaps.len() as u32,
+        });
+        let cell = (width as u32, height as u32);
+        rasterize(glyph, font.ascent, cell, bpp, &mut bitmaps);
+    }
+
+    let header = Header {
+        count: records.len() as u32,
+        height: height as u8,
+        bpp,
+        baseline: font.ascent.max(0).min(height) as u8,
+        max_width: records.iter().map(|r| r.width).max().unwrap_or(0),
+        replacement,
+    };
+    let records_end = header
+        .bitmaps_offset()
+        .expect("at most one record per codepoint");
+    let mut data = vec![0; records_end];
+    header.write(&mut data);
+    for (i, record) in records.iter().enumerate() {
+        let at = HEADER_LEN + i * RECORD_LEN;
+        record.write(&mut data[at..at + RECORD_LEN]);
+    }
+    data.extend_from_slice(&bitmaps);
+
+    Ok(Compiled {
+        data,
+        count: records.len(),
+        missing,
+    })
+}
+
+/// Appends the glyph drawn into a `width` x `height` cell with the baseline
+/// `ascent` rows down, clipping what sticks out. Coverage is rounded to the
+/// nearest of the `2^bpp` levels.
+fn rasterize(
+    glyph: &SourceGlyph,
+    ascent: i32,
+    (width, height): (u32, u32),
+    bpp: u8,
+    out: &mut Vec<u8>,
+) {
+    let bpp = bpp as usize;
+    let max = (1u32 << bpp) - 1;
+    let stride = (width as usize * bpp).div_ceil(8);
+    // cell row of the top of the bounding box
+    let top = ascent - glyph.y_offset - glyph.height as i32;
+    for y in 0..height as i32 {
+        let mut row = vec![0u8; stride];
+        for x in 0..width as i32 {
+            let coverage = glyph.coverage(x - glyph.x_offset, y - top) as u32;
+            let level = (coverage * max + 127) / 255;
+            let bit = x as usize * bpp;
+            row[bit / 8] |= (level << (8 - bpp - bit % 8)) as u8;
+        }
+        out.extend_from_slice(&row);
+    }
+}
+
+/// A Rust file with the blob as `pub const <name>: &[u8]`, for
+/// `bitmap_font!(pub Font = font16::FONT16)`
+pub fn to_rust(compiled: &Compiled, name: &str, source: &str) -> String {
+    let header = Header::parse(&compiled.data).ok();
+    let mut out = String::new();
+    // writing to a String cannot fail
+    let _ = writeln!(
+        out,
+        "// Generated by tools/font-compiler from {}, do not edit",
+        source
+    );
+    let _ = writeln!(out);
+    if let Some(h) = header {
+        let _ = writeln!(out, "/// {} glyphs, {} pixels high", h.count, h.height);
+    }
+    let _ = writeln!(out, "#[rustfmt::skip]");
+    let _ = writeln!(out, "pub const {}: &[u8] = &[", name);
+    for line in compiled.data.chunks(16) {
+        out.push_str("   ");
+        for b in line {
+            let _ = write!(out, " 0x{:02x},", b);
+        }
+        out.push('\n');
+    }
+    out.push_str("];\n");
+    out
+}
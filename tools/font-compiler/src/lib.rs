@@ -0,0 +1,181 @@
+//! Compiles fonts into the format of `longan_nano_playground::font`
+//!
+//! Reads BDF or PCF bitmap fonts, like GNU Unifont or Unibit, or rasterizes
+//! TrueType and OpenType fonts at a fixed size, keeps the characters asked
+//! for and writes a font blob, or a Rust file with the blob as a const:
+//!
+//! ```text
+//! $ font-compiler unibit.bdf --chars-file strings.txt --range U+20-U+7E -o font.bin
+//! $ font-compiler unibit.pcf --chars "气温湿度°" --rs src/font16.rs --name FONT16
+//! $ font-compiler DejaVuSans.ttf --size 14 --bpp 4 --range U+20-U+7E -o sans14.bin
+//! ```
+//!
+//! Every glyph is one cell of the font's ascent plus descent high, as wide as
+//! its advance, with the baseline at the ascent. Outline fonts keep their
+//! coverage at 2 or 4 bits per pixel for anti-aliased text; at 1 bit, pixels
+//! at least half covered are set. The output only depends on the font and
+//! the characters, in any order, so it can be checked in.
+//!
+//! The repo's `.cargo/config` targets the board, so build for the host
+//! explicitly:
+//!
+//! ```text
+//! $ cargo run --target x86_64-unknown-linux-gnu -- --help
+//! ```
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub mod bdf;
+pub mod charset;
+pub mod emit;
+pub mod outline;
+pub mod pcf;
+
+// the format is shared with the firmware
+#[path = "../../../src/font/format.rs"]
+pub mod format;
+
+pub use charset::Charset;
+pub use emit::{compile, to_rust, Compiled};
+
+/// Why a font could not be read or compiled
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Malformed BDF, at a line
+    Bdf {
+        line: usize,
+        msg: String,
+    },
+    /// Malformed PCF
+    Pcf(String),
+    /// Malformed outline font
+    Outline(String),
+    /// Neither BDF, PCF nor an outline font
+    Format,
+    /// Bad character set argument
+    Charset(String),
+    /// Font too tall or glyph too wide for the format
+    Size(String),
+    /// Bits per pixel other than 1, 2 or 4
+    Depth(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Bdf { line, msg } => write!(f, "line {}: {}", line, msg),
+            Error::Pcf(msg) => write!(f, "PCF: {}", msg),
+            Error::Outline(msg) => write!(f, "outline font: {}", msg),
+            Error::Format => write!(f, "not a BDF, PCF or outline font"),
+            Error::Charset(msg) => write!(f, "{}", msg),
+            Error::Size(msg) => write!(f, "{}", msg),
+            Error::Depth(bpp) => write!(f, "{} bits per pixel, not 1, 2 or 4", bpp),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// One glyph as stored in the source font
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceGlyph {
+    /// Distance to the next glyph's origin
+    pub advance: i32,
+    /// Bounding box size
+    pub width: u32,
+    pub height: u32,
+    /// Left edge of the bounding box, from the origin
+    pub x_offset: i32,
+    /// Bottom edge of the bounding box, up from the baseline
+    pub y_offset: i32,
+    /// `height` rows of `width` coverage bytes, 0 to 255
+    pub coverage: Vec<u8>,
+}
+
+impl SourceGlyph {
+    /// Glyph of a 1bpp bitmap, `height` rows of `ceil(width / 8)` bytes, most
+    /// significant bit first, as in BDF and PCF fonts
+    pub fn from_bits(
+        advance: i32,
+        (width, height): (u32, u32),
+        (x_offset, y_offset): (i32, i32),
+        bits: &[u8],
+    ) -> Self {
+        let stride = (width as usize).div_ceil(8);
+        let coverage = (0..height as usize)
+            .flat_map(|y| (0..width as usize).map(move |x| (x, y)))
+            .map(|(x, y)| match bits.get(y * stride + x / 8) {
+                Some(b) if b & (0x80 >> (x % 8)) != 0 => 0xff,
+                _ => 0,
+            })
+            .collect();
+        SourceGlyph {
+            advance,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            coverage,
+        }
+    }
+
+    /// Coverage of a pixel of the bounding box, 0 outside it
+    pub fn coverage(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return 0;
+        }
+        self.coverage[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Whether a pixel is at least half covered, `false` outside it
+    pub fn pixel(&self, x: i32, y: i32) -> bool {
+        self.coverage(x, y) >= 0x80
+    }
+}
+
+/// A parsed BDF, PCF or outline font
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceFont {
+    /// Pixels above the baseline
+    pub ascent: i32,
+    /// Pixels below the baseline
+    pub descent: i32,
+    /// Glyphs by Unicode codepoint
+    pub glyphs: BTreeMap<u32, SourceGlyph>,
+}
+
+impl SourceFont {
+    /// Parses a BDF or PCF font, told apart by their first bytes. Outline
+    /// fonts are read with `outline::parse`.
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.starts_with(pcf::MAGIC) {
+            pcf::parse(data)
+        } else if data.starts_with(b"STARTFONT") {
+            bdf::parse(&String::from_utf8_lossy(data))
+        } else {
+            Err(Error::Format)
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        SourceFont::parse(&fs::read(path)?)
+    }
+
+    /// Height of every compiled glyph
+    pub fn height(&self) -> i32 {
+        self.ascent + self.descent
+    }
+}
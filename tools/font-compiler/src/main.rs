@@ -0,0 +1,135 @@
+//! Compiles a BDF, PCF or outline font for `longan_nano_playground::font`
+//!
+//! ```text
+//! $ font-compiler unibit.bdf --chars "气温湿度" --range U+20-U+7E -o font.bin
+//! font.bin: 99 glyphs, 16 pixels high, 3214 bytes
+//! $ font-compiler DejaVuSans.ttf --size 14 --bpp 4 --range U+20-U+7E -o sans14.bin
+//! ```
+//!
+//! See the library docs for the options.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use font_compiler::{compile, outline, to_rust, Charset, SourceFont};
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: font-compiler <font.bdf | font.pcf | font.ttf | font.otf> [options]
+
+characters, all glyphs if none given, repeatable:
+    --chars <text>          every character of text
+    --chars-file <path>     every character of a UTF-8 file
+    --range <U+XXXX-U+YYYY> a range of codepoints, inclusive
+
+    --replacement <char>    glyph drawn for missing characters, default '?'
+    --no-replacement
+
+    --size <px>             size of outline fonts, ascender to descender
+    --bpp <1 | 2 | 4>       bits per pixel, default 1, anti-aliased above
+
+output, one of:
+    -o <font.bin>           the font blob
+    --rs <font.rs>          a Rust file with the blob as a const
+    --name <NAME>           name of the const, default FONT"
+    );
+    process::exit(2);
+}
+
+fn fail(msg: &dyn std::fmt::Display) -> ! {
+    eprintln!("font-compiler: {}", msg);
+    process::exit(1);
+}
+
+fn main() {
+    let mut input = None;
+    let mut charset: Option<Charset> = None;
+    let mut replacement = Some('?');
+    let mut bin = None;
+    let mut rs = None;
+    let mut name = "FONT".to_string();
+    let mut size = None;
+    let mut bpp = 1;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--chars" => charset.get_or_insert_with(Charset::new).add_str(&value()),
+            "--chars-file" => {
+                let path = value();
+                if let Err(e) = charset.get_or_insert_with(Charset::new).add_file(&path) {
+                    fail(&e);
+                }
+            }
+            "--range" => {
+                let range = value();
+                if let Err(e) = charset
+                    .get_or_insert_with(Charset::new)
+                    .add_range_str(&range)
+                {
+                    fail(&e);
+                }
+            }
+            "--replacement" => {
+                let c = value();
+                let mut chars = c.chars();
+                replacement = match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => usage(),
+                };
+            }
+            "--no-replacement" => replacement = None,
+            "--size" => size = Some(value().parse::<f32>().unwrap_or_else(|_| usage())),
+            "--bpp" => bpp = value().parse().unwrap_or_else(|_| usage()),
+            "-o" => bin = Some(value()),
+            "--rs" => rs = Some(value()),
+            "--name" => name = value(),
+            "-h" | "--help" => usage(),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => usage(),
+        }
+    }
+    let input = input.unwrap_or_else(|| usage());
+    if bin.is_some() == rs.is_some() {
+        usage();
+    }
+
+    let data = fs::read(&input).unwrap_or_else(|e| fail(&format!("{}: {}", input, e)));
+    let font = match (outline::is_outline(&data), size) {
+        (true, Some(size)) => outline::parse(&data, size, charset.as_ref()),
+        (true, None) => fail(&"outline fonts need --size"),
+        (false, _) => SourceFont::parse(&data),
+    };
+    let font = font.unwrap_or_else(|e| fail(&format!("{}: {}", input, e)));
+    let compiled = compile(&font, charset.as_ref(), replacement, bpp).unwrap_or_else(|e| fail(&e));
+    for &c in &compiled.missing {
+        match std::char::from_u32(c) {
+            Some(ch) => eprintln!("missing U+{:04X} {}", c, ch),
+            None => eprintln!("missing U+{:04X}", c),
+        }
+    }
+
+    let (path, data) = match (bin, rs) {
+        (Some(path), _) => (path, compiled.data.clone()),
+        (_, Some(path)) => {
+            let source = Path::new(&input)
+                .file_name()
+                .map_or(input.clone(), |n| n.to_string_lossy().into_owned());
+            (path, to_rust(&compiled, &name, &source).into_bytes())
+        }
+        _ => unreachable!(),
+    };
+    if let Err(e) = fs::write(&path, &data) {
+        fail(&format!("{}: {}", path, e));
+    }
+    eprintln!(
+        "{}: {} glyphs, {} pixels high, {} bytes",
+        path,
+        compiled.count,
+        font.height(),
+        compiled.data.len()
+    );
+}
@@ -0,0 +1,88 @@
+//! TrueType and OpenType fonts, rasterized at a fixed size
+//!
+//! The size is in pixels from the ascender to the descender, the same as the
+//! height of the compiled cells, up to rounding. Glyphs keep the coverage of
+//! each pixel; hinting is not applied, so small sizes look softer than in a
+//! desktop renderer.
+
+use std::collections::BTreeMap;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+
+use crate::{Charset, Error, SourceFont, SourceGlyph};
+
+/// Whether `data` looks like a TrueType, OpenType or collection file
+pub fn is_outline(data: &[u8]) -> bool {
+    [&[0, 1, 0, 0][..], b"OTTO", b"true", b"ttcf"]
+        .iter()
+        .any(|magic| data.starts_with(magic))
+}
+
+/// Rasterizes the glyphs of `charset`, or every mapped character, at `size`
+/// pixels. Characters the font lacks are left out.
+pub fn parse(data: &[u8], size: f32, charset: Option<&Charset>) -> Result<SourceFont, Error> {
+    if !(size > 0.0 && size < 256.0) {
+        return Err(Error::Size(format!("size {} is not 1 to 255 pixels", size)));
+    }
+    let font = FontRef::try_from_slice(data).map_err(|e| Error::Outline(e.to_string()))?;
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+
+    let chars: Vec<char> = match charset {
+        Some(charset) => charset.iter().filter_map(std::char::from_u32).collect(),
+        None => font
+            .codepoint_ids()
+            .map(|(_, c)| c)
+            .filter(|c| !c.is_control())
+            .collect(),
+    };
+
+    let mut glyphs = BTreeMap::new();
+    for c in chars {
+        let id = font.glyph_id(c);
+        if id.0 == 0 {
+            // .notdef
+            continue;
+        }
+        let advance = scaled.h_advance(id).round() as i32;
+        let glyph = id.with_scale_and_position(scale, point(0.0, 0.0));
+        let source = match font.outline_glyph(glyph) {
+            Some(outline) => {
+                // y grows downwards from the baseline
+                let bounds = outline.px_bounds();
+                let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+                let mut coverage = vec![0; width as usize * height as usize];
+                outline.draw(|x, y, c| {
+                    if x < width && y < height {
+                        let c = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+                        coverage[(y * width + x) as usize] = c;
+                    }
+                });
+                SourceGlyph {
+                    advance,
+                    width,
+                    height,
+                    x_offset: bounds.min.x as i32,
+                    y_offset: -bounds.max.y as i32,
+                    coverage,
+                }
+            }
+            // blank, like a space
+            None => SourceGlyph {
+                advance,
+                width: 0,
+                height: 0,
+                x_offset: 0,
+                y_offset: 0,
+                coverage: Vec::new(),
+            },
+        };
+        glyphs.insert(c as u32, source);
+    }
+
+    Ok(SourceFont {
+        ascent: scaled.ascent().round() as i32,
+        descent: (-scaled.descent()).round() as i32,
+        glyphs,
+    })
+}
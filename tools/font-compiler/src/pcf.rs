@@ -0,0 +1,281 @@
+//! Portable Compiled Format, the binary format of X11 fonts
+//!
+//! A table of contents points at tables, each starting with its own format
+//! word, which says the byte order of the table, and for bitmaps the bit
+//! order, the row padding and the scan unit. Read here: metrics, bitmaps,
+//! encodings, and the accelerators for the ascent and descent.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+use crate::{Error, SourceFont, SourceGlyph};
+
+pub const MAGIC: &[u8] = b"\x01fcp";
+
+// table types
+pub const ACCELERATORS: u32 = 1 << 1;
+pub const METRICS: u32 = 1 << 2;
+pub const BITMAPS: u32 = 1 << 3;
+pub const BDF_ENCODINGS: u32 = 1 << 5;
+pub const BDF_ACCELERATORS: u32 = 1 << 8;
+
+// format word
+pub const COMPRESSED_METRICS: u32 = 0x100;
+/// Most significant byte first
+pub const BYTE_MASK: u32 = 1 << 2;
+/// Most significant bit first
+pub const BIT_MASK: u32 = 1 << 3;
+
+/// Glyph metrics, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Metrics {
+    left: i32,
+    right: i32,
+    advance: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+/// Reader of one table
+struct Table<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: u32,
+}
+
+impl<'a> Table<'a> {
+    /// The table of `kind`, positioned after its format word
+    fn find(
+        font: &'a [u8],
+        toc: &[(u32, u32, usize, usize)],
+        kind: u32,
+    ) -> Result<Option<Self>, Error> {
+        let (_, _, size, offset) = match toc.iter().find(|t| t.0 == kind) {
+            Some(&t) => t,
+            None => return Ok(None),
+        };
+        let data = offset
+            .checked_add(size)
+            .and_then(|end| font.get(offset..end))
+            .ok_or_else(|| Error::Pcf(format!("table {:#x} out of the file", kind)))?;
+        let mut table = Table {
+            data,
+            pos: 0,
+            format: 0,
+        };
+        // always little endian
+        table.format = u32::from_le_bytes(table.bytes(4)?.try_into().unwrap());
+        Ok(Some(table))
+    }
+
+    fn big_endian(&self) -> bool {
+        self.format & BYTE_MASK != 0
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let data = self.data;
+        let bytes = data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| Error::Pcf("truncated table".to_string()))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let b = self.bytes(2)?.try_into().unwrap();
+        Ok(if self.big_endian() {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn i16(&mut self) -> Result<i32, Error> {
+        Ok(self.u16()? as i16 as i32)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let b = self.bytes(4)?.try_into().unwrap();
+        Ok(if self.big_endian() {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<SourceFont, Error> {
+    if !data.starts_with(MAGIC) {
+        return Err(Error::Format);
+    }
+    let le32 = |at: usize| -> Result<u32, Error> {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| Error::Pcf("truncated table of contents".to_string()))
+    };
+    let count = le32(4)? as usize;
+    let mut toc = Vec::new();
+    for i in 0..count.min(data.len() / 16) {
+        let at = 8 + i * 16;
+        toc.push((
+            le32(at)?,
+            le32(at + 4)?,
+            le32(at + 8)? as usize,
+            le32(at + 12)? as usize,
+        ));
+    }
+    let table = |kind| Table::find(data, &toc, kind);
+    let missing = |name: &str| Error::Pcf(format!("no {} table", name));
+
+    let metrics = read_metrics(&mut table(METRICS)?.ok_or_else(|| missing("metrics"))?)?;
+    let bitmaps = read_bitmaps(
+        &mut table(BITMAPS)?.ok_or_else(|| missing("bitmaps"))?,
+        &metrics,
+    )?;
+    let encodings =
+        read_encodings(&mut table(BDF_ENCODINGS)?.ok_or_else(|| missing("encodings"))?)?;
+    let accelerators = match table(BDF_ACCELERATORS)? {
+        Some(t) => Some(t),
+        None => table(ACCELERATORS)?,
+    };
+    let (ascent, descent) = match accelerators {
+        Some(mut t) => read_accelerators(&mut t)?,
+        // tallest glyph
+        None => metrics
+            .iter()
+            .fold((0, 0), |(a, d), m| (m.ascent.max(a), m.descent.max(d))),
+    };
+
+    let mut glyphs = BTreeMap::new();
+    for (codepoint, index) in encodings {
+        let (m, bitmap) = match (metrics.get(index), bitmaps.get(index)) {
+            (Some(m), Some(b)) => (m, b),
+            _ => return Err(Error::Pcf(format!("glyph {} out of range", index))),
+        };
+        let size = (
+            (m.right - m.left).max(0) as u32,
+            (m.ascent + m.descent).max(0) as u32,
+        );
+        glyphs.insert(
+            codepoint,
+            SourceGlyph::from_bits(m.advance, size, (m.left, -m.descent), bitmap),
+        );
+    }
+    Ok(SourceFont {
+        ascent,
+        descent,
+        glyphs,
+    })
+}
+
+fn read_metrics(t: &mut Table) -> Result<Vec<Metrics>, Error> {
+    if t.format & COMPRESSED_METRICS != 0 {
+        let count = t.u16()? as usize;
+        (0..count)
+            .map(|_| {
+                let mut f = || t.u8().map(|b| b as i32 - 0x80);
+                Ok(Metrics {
+                    left: f()?,
+                    right: f()?,
+                    advance: f()?,
+                    ascent: f()?,
+                    descent: f()?,
+                })
+            })
+            .collect()
+    } else {
+        let count = t.u32()? as usize;
+        (0..count)
+            .map(|_| {
+                let m = Metrics {
+                    left: t.i16()?,
+                    right: t.i16()?,
+                    advance: t.i16()?,
+                    ascent: t.i16()?,
+                    descent: t.i16()?,
+                };
+                // attributes
+                t.u16()?;
+                Ok(m)
+            })
+            .collect()
+    }
+}
+
+/// Bitmaps repacked to rows of `ceil(width / 8)` bytes, most significant bit
+/// first
+fn read_bitmaps(t: &mut Table, metrics: &[Metrics]) -> Result<Vec<Vec<u8>>, Error> {
+    let count = t.u32()? as usize;
+    let offsets = (0..count).map(|_| t.u32()).collect::<Result<Vec<_>, _>>()?;
+    // sizes for each padding
+    let sizes = (0..4).map(|_| t.u32()).collect::<Result<Vec<_>, _>>()?;
+    let pad = 1usize << (t.format & 3);
+    let unit = 1usize << (t.format >> 4 & 3);
+    let data = t.bytes(sizes[(t.format & 3) as usize] as usize)?;
+    let msb_byte = t.format & BYTE_MASK != 0;
+    let msb_bit = t.format & BIT_MASK != 0;
+
+    offsets
+        .iter()
+        .zip(metrics)
+        .map(|(&offset, m)| {
+            let width = (m.right - m.left).max(0) as usize;
+            let height = (m.ascent + m.descent).max(0) as usize;
+            let stride = width.div_ceil(8);
+            let padded = stride.div_ceil(pad) * pad;
+            let mut bitmap = Vec::with_capacity(stride * height);
+            for y in 0..height {
+                let start = offset as usize + y * padded;
+                let row = data
+                    .get(start..start + padded)
+                    .ok_or_else(|| Error::Pcf("bitmap out of the table".to_string()))?;
+                let mut row = row.to_vec();
+                // bytes within a scan unit follow the bit order
+                if msb_byte != msb_bit && unit > 1 {
+                    for chunk in row.chunks_mut(unit) {
+                        chunk.reverse();
+                    }
+                }
+                if !msb_bit {
+                    for b in row.iter_mut() {
+                        *b = b.reverse_bits();
+                    }
+                }
+                bitmap.extend_from_slice(&row[..stride]);
+            }
+            Ok(bitmap)
+        })
+        .collect()
+}
+
+/// (codepoint, glyph index) of every encoded glyph
+fn read_encodings(t: &mut Table) -> Result<Vec<(u32, usize)>, Error> {
+    let min_byte2 = t.u16()? as u32;
+    let max_byte2 = t.u16()? as u32;
+    let min_byte1 = t.u16()? as u32;
+    let max_byte1 = t.u16()? as u32;
+    // default char
+    t.u16()?;
+    let mut encodings = Vec::new();
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            let index = t.u16()?;
+            if index != 0xffff {
+                encodings.push((byte1 << 8 | byte2, index as usize));
+            }
+        }
+    }
+    Ok(encodings)
+}
+
+fn read_accelerators(t: &mut Table) -> Result<(i32, i32), Error> {
+    // flags and padding
+    t.bytes(8)?;
+    let ascent = t.u32()? as i32;
+    let descent = t.u32()? as i32;
+    Ok((ascent, descent))
+}
@@ -0,0 +1,430 @@
+use font_compiler::format::{BitmapFont, NO_REPLACEMENT};
+use font_compiler::{charset, compile, pcf, to_rust, Charset, SourceFont};
+
+/// One glyph of the test font, rows with the leftmost pixel as the top bit
+struct Fixture {
+    encoding: i32,
+    advance: i32,
+    /// width, height, x offset, y offset
+    bbx: (u32, u32, i32, i32),
+    rows: Vec<u32>,
+}
+
+const ASCENT: i32 = 14;
+const DESCENT: i32 = 2;
+const HAN: u32 = 0x6e29; // 温
+
+fn fixtures() -> Vec<Fixture> {
+    vec![
+        Fixture {
+            encoding: 'A' as i32,
+            advance: 8,
+            bbx: (6, 7, 1, 0),
+            rows: vec![
+                0b001100, 0b010010, 0b100001, 0b111111, 0b100001, 0b100001, 0b100001,
+            ],
+        },
+        Fixture {
+            encoding: 'g' as i32,
+            advance: 8,
+            bbx: (5, 6, 1, -2),
+            rows: vec![0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+        },
+        Fixture {
+            encoding: '?' as i32,
+            advance: 8,
+            bbx: (4, 4, 2, 6),
+            rows: vec![0b0110, 0b1001, 0b0010, 0b0100],
+        },
+        Fixture {
+            encoding: HAN as i32,
+            advance: 16,
+            bbx: (16, 16, 0, -2),
+            rows: (0..16).map(|i| 0x8000 >> i | 1 << i).collect(),
+        },
+        Fixture {
+            encoding: -1,
+            advance: 8,
+            bbx: (1, 1, 0, 0),
+            rows: vec![1],
+        },
+    ]
+}
+
+/// Bytes of a row, most significant bit first
+fn row_bytes(row: u32, width: u32) -> Vec<u8> {
+    let stride = width.div_ceil(8);
+    let value = row << (stride * 8 - width);
+    (0..stride)
+        .rev()
+        .map(|i| (value >> (i * 8)) as u8)
+        .collect()
+}
+
+fn bdf() -> String {
+    let glyphs = fixtures();
+    let mut out = String::from(
+        "STARTFONT 2.1\nFONT -test-fixed-medium-r-normal--16-160-75-75-c-80-iso10646-1\n\
+         SIZE 16 75 75\nFONTBOUNDINGBOX 16 16 0 -2\nSTARTPROPERTIES 2\n",
+    );
+    out += &format!(
+        "FONT_ASCENT {}\nFONT_DESCENT {}\nENDPROPERTIES\n",
+        ASCENT, DESCENT
+    );
+    out += &format!("CHARS {}\n", glyphs.len());
+    for g in &glyphs {
+        let (w, h, x, y) = g.bbx;
+        out += &format!(
+            "STARTCHAR U+{:04X}\nENCODING {}\nSWIDTH 500 0\nDWIDTH {} 0\nBBX {} {} {} {}\nBITMAP\n",
+            g.encoding, g.encoding, g.advance, w, h, x, y
+        );
+        for &row in &g.rows {
+            for b in row_bytes(row, w) {
+                out += &format!("{:02X}", b);
+            }
+            out += "\n";
+        }
+        out += "ENDCHAR\n";
+    }
+    out + "ENDFONT\n"
+}
+
+/// Layout of the PCF tables
+#[derive(Clone, Copy)]
+struct PcfLayout {
+    big_endian: bool,
+    msb_bit: bool,
+    /// log2 of the row padding and of the scan unit, in bytes
+    pad: u32,
+    unit: u32,
+    compressed: bool,
+}
+
+fn pcf(layout: PcfLayout) -> Vec<u8> {
+    let glyphs: Vec<Fixture> = fixtures().into_iter().filter(|g| g.encoding >= 0).collect();
+    let format = (layout.big_endian as u32) << 2
+        | (layout.msb_bit as u32) << 3
+        | layout.pad
+        | layout.unit << 4;
+    let put16 = |out: &mut Vec<u8>, v: u16| {
+        out.extend_from_slice(&if layout.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        })
+    };
+    let put32 = |out: &mut Vec<u8>, v: u32| {
+        out.extend_from_slice(&if layout.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        })
+    };
+
+    let mut metrics = Vec::new();
+    let metrics_format = format
+        | if layout.compressed {
+            pcf::COMPRESSED_METRICS
+        } else {
+            0
+        };
+    metrics.extend_from_slice(&metrics_format.to_le_bytes());
+    if layout.compressed {
+        put16(&mut metrics, glyphs.len() as u16);
+    } else {
+        put32(&mut metrics, glyphs.len() as u32);
+    }
+    for g in &glyphs {
+        let (w, h, x, y) = g.bbx;
+        let values = [x, x + w as i32, g.advance, h as i32 + y, -y];
+        for &v in &values {
+            if layout.compressed {
+                metrics.push((v + 0x80) as u8);
+            } else {
+                put16(&mut metrics, v as u16);
+            }
+        }
+        if !layout.compressed {
+            put16(&mut metrics, 0);
+        }
+    }
+
+    let pad = 1usize << layout.pad;
+    let unit = 1usize << layout.unit;
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for g in &glyphs {
+        offsets.push(data.len() as u32);
+        for &row in &g.rows {
+            let mut bytes = row_bytes(row, g.bbx.0);
+            bytes.resize(bytes.len().div_ceil(pad) * pad, 0);
+            if !layout.msb_bit {
+                for b in bytes.iter_mut() {
+                    *b = b.reverse_bits();
+                }
+            }
+            if layout.big_endian != layout.msb_bit && unit > 1 {
+                for chunk in bytes.chunks_mut(unit) {
+                    chunk.reverse();
+                }
+            }
+            data.extend(bytes);
+        }
+    }
+    let mut bitmaps = Vec::new();
+    bitmaps.extend_from_slice(&format.to_le_bytes());
+    put32(&mut bitmaps, glyphs.len() as u32);
+    for &o in &offsets {
+        put32(&mut bitmaps, o);
+    }
+    for p in 0..4 {
+        put32(
+            &mut bitmaps,
+            if p == layout.pad {
+                data.len() as u32
+            } else {
+                0
+            },
+        );
+    }
+    bitmaps.extend(data);
+
+    let codes: Vec<u32> = glyphs.iter().map(|g| g.encoding as u32).collect();
+    let (min1, max1) = (
+        codes.iter().map(|c| c >> 8).min().unwrap(),
+        codes.iter().map(|c| c >> 8).max().unwrap(),
+    );
+    let (min2, max2) = (
+        codes.iter().map(|c| c & 0xff).min().unwrap(),
+        codes.iter().map(|c| c & 0xff).max().unwrap(),
+    );
+    let mut encodings = Vec::new();
+    encodings.extend_from_slice(&format.to_le_bytes());
+    for &v in &[min2, max2, min1, max1, 0] {
+        put16(&mut encodings, v as u16);
+    }
+    for b1 in min1..=max1 {
+        for b2 in min2..=max2 {
+            let index = codes.iter().position(|&c| c == b1 << 8 | b2);
+            put16(&mut encodings, index.map_or(0xffff, |i| i as u16));
+        }
+    }
+
+    let mut accelerators = Vec::new();
+    accelerators.extend_from_slice(&format.to_le_bytes());
+    accelerators.extend_from_slice(&[0; 8]);
+    put32(&mut accelerators, ASCENT as u32);
+    put32(&mut accelerators, DESCENT as u32);
+    put32(&mut accelerators, 0);
+
+    let tables = [
+        (pcf::BDF_ACCELERATORS, accelerators),
+        (pcf::METRICS, metrics),
+        (pcf::BITMAPS, bitmaps),
+        (pcf::BDF_ENCODINGS, encodings),
+    ];
+    let mut out = pcf::MAGIC.to_vec();
+    out.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+    let mut offset = 8 + tables.len() * 16;
+    for (kind, table) in &tables {
+        let table_format = u32::from_le_bytes([table[0], table[1], table[2], table[3]]);
+        for &v in &[*kind, table_format, table.len() as u32, offset as u32] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        offset += table.len();
+    }
+    for (_, table) in &tables {
+        out.extend_from_slice(table);
+    }
+    out
+}
+
+fn chars(text: &str) -> Charset {
+    let mut charset = Charset::new();
+    charset.add_str(text);
+    charset
+}
+
+/// Rows of a compiled glyph, '#' for set pixels
+fn render(font: &BitmapFont, c: char) -> Vec<String> {
+    let glyph = font.glyph(c).unwrap();
+    (0..glyph.height as u32)
+        .map(|y| {
+            (0..glyph.width as u32)
+                .map(|x| if glyph.pixel(x, y) { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn parses_bdf() {
+    let font = SourceFont::parse(bdf().as_bytes()).unwrap();
+    assert_eq!((font.ascent, font.descent), (ASCENT, DESCENT));
+    // ENCODING -1 is skipped
+    let codepoints: Vec<u32> = font.glyphs.keys().copied().collect();
+    assert_eq!(codepoints, vec!['?' as u32, 'A' as u32, 'g' as u32, HAN]);
+    let a = &font.glyphs[&('A' as u32)];
+    assert_eq!(
+        (a.advance, a.width, a.height, a.x_offset, a.y_offset),
+        (8, 6, 7, 1, 0)
+    );
+    assert!(a.pixel(2, 0) && !a.pixel(0, 0));
+}
+
+#[test]
+fn places_glyphs_on_the_baseline() {
+    let font = SourceFont::parse(bdf().as_bytes()).unwrap();
+    let compiled = compile(&font, Some(&chars("Ag")), None, 1).unwrap();
+    let font = BitmapFont::new(&compiled.data).unwrap();
+    assert_eq!(font.height(), 16);
+    assert_eq!(font.header().baseline, 14);
+    assert_eq!(font.header().replacement, NO_REPLACEMENT);
+
+    let a = render(&font, 'A');
+    assert_eq!(a.len(), 16);
+    assert!(a[..7].iter().all(|row| row == "........"));
+    assert_eq!(a[7], "...##...");
+    assert_eq!(a[10], ".######.");
+    assert_eq!(a[13], ".#....#.");
+    assert!(a[14..].iter().all(|row| row == "........"));
+
+    // descender below the baseline
+    let g = render(&font, 'g');
+    assert_eq!(g[9], "........");
+    assert_eq!(g[10], "..####..");
+    assert_eq!(g[15], "..###...");
+}
+
+#[test]
+fn keeps_the_charset_and_replacement() {
+    let font = SourceFont::parse(bdf().as_bytes()).unwrap();
+    let compiled = compile(&font, Some(&chars("温A\nZ")), Some('?'), 1).unwrap();
+    assert_eq!(compiled.count, 3);
+    assert_eq!(compiled.missing, vec!['Z' as u32]);
+
+    let font = BitmapFont::new(&compiled.data).unwrap();
+    assert_eq!(font.len(), 3);
+    assert_eq!(font.header().max_width, 16);
+    assert!(font.glyph('g').is_none());
+    assert_eq!(font.char_width('温'), 16);
+    assert_eq!(font.text_width("A温Z"), 8 + 16 + 8);
+
+    let han = render(&font, '温');
+    for (y, row) in han.iter().enumerate() {
+        let expected: String = (0..16)
+            .map(|x| if x == y || x == 15 - y { '#' } else { '.' })
+            .collect();
+        assert_eq!(row, &expected);
+    }
+}
+
+#[test]
+fn reports_a_missing_replacement() {
+    let font = SourceFont::parse(bdf().as_bytes()).unwrap();
+    let compiled = compile(&font, Some(&chars("A")), Some('\u{fffd}'), 1).unwrap();
+    assert_eq!(compiled.missing, vec![0xfffd]);
+    let font = BitmapFont::new(&compiled.data).unwrap();
+    assert_eq!(font.header().replacement, NO_REPLACEMENT);
+}
+
+#[test]
+fn is_deterministic() {
+    let font = SourceFont::parse(bdf().as_bytes()).unwrap();
+    let a = compile(&font, Some(&chars("温Ag")), Some('?'), 1).unwrap();
+    let b = compile(&font, Some(&chars("gA温gA")), Some('?'), 1).unwrap();
+    assert_eq!(a, b);
+
+    // every glyph when no charset is given
+    let all = compile(&font, None, Some('?'), 1).unwrap();
+    assert_eq!(all.count, 4);
+    assert!(all.missing.is_empty());
+}
+
+#[test]
+fn pcf_compiles_like_bdf() {
+    let bdf = SourceFont::parse(bdf().as_bytes()).unwrap();
+    let expected = compile(&bdf, None, Some('?'), 1).unwrap();
+    let layouts = [
+        // what bdftopcf writes by default
+        PcfLayout {
+            big_endian: true,
+            msb_bit: true,
+            pad: 2,
+            unit: 0,
+            compressed: false,
+        },
+        PcfLayout {
+            big_endian: false,
+            msb_bit: false,
+            pad: 0,
+            unit: 0,
+            compressed: true,
+        },
+        PcfLayout {
+            big_endian: true,
+            msb_bit: false,
+            pad: 2,
+            unit: 2,
+            compressed: false,
+        },
+        PcfLayout {
+            big_endian: false,
+            msb_bit: true,
+            pad: 1,
+            unit: 1,
+            compressed: true,
+        },
+    ];
+    for &layout in &layouts {
+        let font = SourceFont::parse(&pcf(layout)).unwrap();
+        assert_eq!(font, bdf);
+        assert_eq!(compile(&font, None, Some('?'), 1).unwrap(), expected);
+    }
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(SourceFont::parse(b"\x89PNG").is_err());
+    assert!(SourceFont::parse(b"STARTFONT 2.1\nSTARTCHAR A\nENCODING 65\n").is_err());
+    let mut truncated = pcf(PcfLayout {
+        big_endian: true,
+        msb_bit: true,
+        pad: 2,
+        unit: 0,
+        compressed: false,
+    });
+    truncated.truncate(200);
+    assert!(SourceFont::parse(&truncated).is_err());
+}
+
+#[test]
+fn parses_ranges() {
+    assert_eq!(
+        charset::parse_range("U+4E00-U+9FFF").unwrap(),
+        (0x4e00, 0x9fff)
+    );
+    assert_eq!(charset::parse_range("0x20-0x7e").unwrap(), (0x20, 0x7e));
+    assert_eq!(charset::parse_range("u+b0").unwrap(), (0xb0, 0xb0));
+    assert!(charset::parse_range("U+7E-U+20").is_err());
+    assert!(charset::parse_range("U+110000").is_err());
+    assert!(charset::parse_range("ascii").is_err());
+
+    let mut charset = Charset::new();
+    charset.add_range_str("U+0-U+7F").unwrap();
+    // without the control characters
+    assert_eq!(charset.len(), 0x7f - 0x20);
+    assert!(charset.contains(' ' as u32) && !charset.contains('\n' as u32));
+}
+
+#[test]
+fn writes_rust() {
+    let font = SourceFont::parse(bdf().as_bytes()).unwrap();
+    let compiled = compile(&font, Some(&chars("A")), None, 1).unwrap();
+    let rs = to_rust(&compiled, "FONT16", "test.bdf");
+    assert!(rs.starts_with("// Generated by tools/font-compiler from test.bdf"));
+    assert!(rs.contains("/// 1 glyphs, 16 pixels high\n"));
+    assert!(rs.contains("pub const FONT16: &[u8] = &[\n    0x4c, 0x4e, 0x46, 0x01,"));
+    assert_eq!(rs.matches("0x").count(), compiled.data.len());
+    assert!(rs.ends_with("];\n"));
+}
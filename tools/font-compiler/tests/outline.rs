@@ -0,0 +1,245 @@
+use font_compiler::format::BitmapFont;
+use font_compiler::{compile, outline, Charset};
+
+/// Units per pixel at `SIZE`: ascender 800 and descender -200 span 10 pixels
+const SIZE: f32 = 10.0;
+
+/// A TrueType font of rectangles: (codepoint, advance, x0, y0, x1, y1) in
+/// font units, y up from the baseline
+fn ttf(glyphs: &[(char, u16, i16, i16, i16, i16)]) -> Vec<u8> {
+    let be16 = |out: &mut Vec<u8>, v: i32| out.extend_from_slice(&(v as u16).to_be_bytes());
+    let be32 = |out: &mut Vec<u8>, v: u32| out.extend_from_slice(&v.to_be_bytes());
+    let count = glyphs.len() as i32 + 1;
+
+    let mut head = Vec::new();
+    be32(&mut head, 0x0001_0000);
+    be32(&mut head, 0x0001_0000);
+    be32(&mut head, 0);
+    be32(&mut head, 0x5f0f_3cf5);
+    be16(&mut head, 0);
+    // units per em
+    be16(&mut head, 1000);
+    head.extend_from_slice(&[0; 16]);
+    for &v in &[0, -200, 1000, 800, 0, 8, 2] {
+        be16(&mut head, v);
+    }
+    // short loca, glyph data format
+    be16(&mut head, 0);
+    be16(&mut head, 0);
+
+    let mut hhea = Vec::new();
+    be32(&mut hhea, 0x0001_0000);
+    for &v in &[800, -200, 0, 1000, 0, 0, 1000, 1, 0, 0, 0, 0, 0, 0, 0] {
+        be16(&mut hhea, v);
+    }
+    be16(&mut hhea, count);
+
+    let mut maxp = Vec::new();
+    be32(&mut maxp, 0x0000_5000);
+    be16(&mut maxp, count);
+
+    let mut hmtx = Vec::new();
+    be16(&mut hmtx, 500);
+    be16(&mut hmtx, 0);
+    for g in glyphs {
+        be16(&mut hmtx, g.1 as i32);
+        be16(&mut hmtx, g.2 as i32);
+    }
+
+    // .notdef is empty, the others one clockwise contour of 4 points
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    be16(&mut loca, 0);
+    be16(&mut loca, 0);
+    for &(_, _, x0, y0, x1, y1) in glyphs {
+        for &v in &[1, x0, y0, x1, y1] {
+            be16(&mut glyf, v as i32);
+        }
+        // last point, no instructions, on-curve flags
+        be16(&mut glyf, 3);
+        be16(&mut glyf, 0);
+        glyf.extend_from_slice(&[1; 4]);
+        let points = [(x0, y0), (x0, y1), (x1, y1), (x1, y0)];
+        let (mut px, mut py) = (0, 0);
+        for &(x, _) in &points {
+            be16(&mut glyf, (x - px) as i32);
+            px = x;
+        }
+        for &(_, y) in &points {
+            be16(&mut glyf, (y - py) as i32);
+            py = y;
+        }
+        be16(&mut loca, glyf.len() as i32 / 2);
+    }
+
+    // format 4, one sorted segment per glyph and the final 0xffff
+    let mut segments: Vec<(u16, u16, i16)> = glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g.0 as u16, g.0 as u16, (i as i32 + 1 - g.0 as i32) as i16))
+        .collect();
+    segments.sort();
+    segments.push((0xffff, 0xffff, 1));
+    let seg_count = segments.len() as i32;
+    let mut sub = Vec::new();
+    for &v in &[4, 16 + 8 * seg_count, 0, seg_count * 2, 0, 0, 0] {
+        be16(&mut sub, v);
+    }
+    for s in &segments {
+        be16(&mut sub, s.1 as i32);
+    }
+    be16(&mut sub, 0);
+    for s in &segments {
+        be16(&mut sub, s.0 as i32);
+    }
+    for s in &segments {
+        be16(&mut sub, s.2 as i32);
+    }
+    for _ in &segments {
+        be16(&mut sub, 0);
+    }
+    let mut cmap = Vec::new();
+    for &v in &[0, 1, 3, 1] {
+        be16(&mut cmap, v);
+    }
+    be32(&mut cmap, 12);
+    cmap.extend(sub);
+
+    let tables: [(&[u8; 4], Vec<u8>); 7] = [
+        (b"cmap", cmap),
+        (b"glyf", glyf),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"loca", loca),
+        (b"maxp", maxp),
+    ];
+    let mut out = Vec::new();
+    be32(&mut out, 0x0001_0000);
+    for &v in &[tables.len() as i32, 0, 0, 0] {
+        be16(&mut out, v);
+    }
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in &tables {
+        out.extend_from_slice(*tag);
+        be32(&mut out, 0);
+        be32(&mut out, offset as u32);
+        be32(&mut out, table.len() as u32);
+        offset += (table.len() + 3) & !3;
+    }
+    for (_, table) in &tables {
+        out.extend_from_slice(table);
+        out.resize((out.len() + 3) & !3, 0);
+    }
+    out
+}
+
+fn fixture() -> Vec<u8> {
+    ttf(&[
+        // 3 x 4.25 pixels on the baseline, a quarter of the top row covered
+        ('A', 400, 0, 0, 300, 425),
+        // half a pixel in from both sides, 2 pixels below the baseline
+        ('g', 400, 50, -200, 350, 300),
+        ('?', 400, 100, 0, 200, 700),
+    ])
+}
+
+fn chars(text: &str) -> Charset {
+    let mut charset = Charset::new();
+    charset.add_str(text);
+    charset
+}
+
+/// Coverage levels of a compiled glyph
+fn levels(font: &BitmapFont, c: char) -> Vec<Vec<u8>> {
+    let glyph = font.glyph(c).unwrap();
+    let max = (1u32 << glyph.bpp) - 1;
+    (0..glyph.height as u32)
+        .map(|y| {
+            (0..glyph.width as u32)
+                .map(|x| (glyph.coverage(x, y) as u32 * max / 255) as u8)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn rasterizes_rectangles() {
+    let data = fixture();
+    assert!(outline::is_outline(&data));
+    let font = outline::parse(&data, SIZE, None).unwrap();
+    assert_eq!((font.ascent, font.descent), (8, 2));
+    assert_eq!(font.glyphs.len(), 3);
+
+    let a = &font.glyphs[&('A' as u32)];
+    assert_eq!((a.advance, a.width, a.height), (4, 3, 5));
+    assert_eq!((a.x_offset, a.y_offset), (0, 0));
+    assert!((a.coverage(1, 0) as i32 - 64).abs() <= 1);
+    assert_eq!(a.coverage(1, 1), 255);
+}
+
+#[test]
+fn quantizes_coverage() {
+    let data = fixture();
+    let font = outline::parse(&data, SIZE, Some(&chars("Ag"))).unwrap();
+
+    let compiled = compile(&font, None, None, 4).unwrap();
+    let blob = BitmapFont::new(&compiled.data).unwrap();
+    assert_eq!(
+        (blob.height(), blob.header().bpp, blob.header().baseline),
+        (10, 4, 8)
+    );
+    let a = levels(&blob, 'A');
+    assert_eq!(a.len(), 10);
+    assert!(a[..3].iter().all(|row| row == &[0, 0, 0, 0]));
+    // a quarter is 4 of 15
+    assert_eq!(a[3], [4, 4, 4, 0]);
+    assert!(a[4..8].iter().all(|row| row == &[15, 15, 15, 0]));
+    assert!(a[8..].iter().all(|row| row == &[0, 0, 0, 0]));
+
+    // half covered edge columns, down to the bottom of the cell
+    let g = levels(&blob, 'g');
+    assert_eq!(g[4], [0, 0, 0, 0]);
+    assert!(g[5..].iter().all(|row| row == &[8, 15, 15, 8]));
+
+    let compiled = compile(&font, None, None, 2).unwrap();
+    let blob = BitmapFont::new(&compiled.data).unwrap();
+    assert_eq!(levels(&blob, 'A')[3], [1, 1, 1, 0]);
+    assert_eq!(levels(&blob, 'g')[9], [2, 3, 3, 2]);
+
+    // at 1bpp, a quarter is off and a half is on
+    let compiled = compile(&font, None, None, 1).unwrap();
+    let blob = BitmapFont::new(&compiled.data).unwrap();
+    assert_eq!(levels(&blob, 'A')[3], [0, 0, 0, 0]);
+    assert_eq!(levels(&blob, 'g')[9], [1, 1, 1, 1]);
+}
+
+#[test]
+fn leaves_out_missing_characters() {
+    let data = fixture();
+    let font = outline::parse(&data, SIZE, Some(&chars("A温"))).unwrap();
+    let compiled = compile(&font, Some(&chars("A温")), Some('?'), 4).unwrap();
+    assert_eq!(compiled.missing, vec!['?' as u32, '温' as u32]);
+    assert_eq!(compiled.count, 1);
+}
+
+#[test]
+fn is_deterministic() {
+    let data = fixture();
+    let a = outline::parse(&data, SIZE, None).unwrap();
+    let b = outline::parse(&data, SIZE, Some(&chars("g?A"))).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(
+        compile(&a, None, None, 4).unwrap(),
+        compile(&b, None, None, 4).unwrap()
+    );
+}
+
+#[test]
+fn rejects_bad_input() {
+    let data = fixture();
+    assert!(outline::parse(&data, 0.0, None).is_err());
+    assert!(outline::parse(&data[..40], SIZE, None).is_err());
+    let font = outline::parse(&data, SIZE, None).unwrap();
+    assert!(compile(&font, None, None, 3).is_err());
+}
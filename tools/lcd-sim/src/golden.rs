@@ -0,0 +1,114 @@
+//! Comparing the simulated screen with golden images
+//!
+//! Golden images are PPM files in `tests/golden`, see `path`. They are only
+//! written with `LCD_SIM_BLESS=1` set, after checking the differences by eye;
+//! otherwise a missing golden image is an error. On a mismatch the actual
+//! screen is saved as `<golden>.actual.png` and `<golden>.actual.ppm`.
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::image;
+use crate::SimLcd;
+
+/// Environment variable to write golden images
+pub const BLESS_VAR: &str = "LCD_SIM_BLESS";
+
+/// Golden image `name` of the lcd-sim tests, `tests/golden/<name>.ppm`
+pub fn path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.ppm", name))
+}
+
+/// Why a screen does not match its golden image
+#[derive(Debug)]
+pub enum Mismatch {
+    /// Golden image could not be read or written
+    Io(io::Error),
+    /// Golden image of another size, (width, height)
+    Size(u32, u32),
+    /// Number of differing pixels, and the first one
+    Pixels { count: usize, first: (u32, u32) },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mismatch::Io(e) => write!(f, "golden image: {}", e),
+            Mismatch::Size(w, h) => write!(f, "golden image is {}x{}", w, h),
+            Mismatch::Pixels { count, first } => write!(
+                f,
+                "{} pixels differ, first at ({}, {})",
+                count, first.0, first.1
+            ),
+        }
+    }
+}
+
+impl error::Error for Mismatch {}
+
+impl From<io::Error> for Mismatch {
+    fn from(e: io::Error) -> Self {
+        Mismatch::Io(e)
+    }
+}
+
+/// Compares the visible screen with the PPM at `path`, or writes it there
+/// when blessing
+pub fn check<P: AsRef<Path>>(lcd: &SimLcd, path: P) -> Result<(), Mismatch> {
+    let path = path.as_ref();
+    let (width, height) = (lcd.width() as u32, lcd.height() as u32);
+    let actual = image::to_rgb(&lcd.pixels());
+
+    if env::var(BLESS_VAR).as_deref() == Ok("1") {
+        lcd.save_ppm(path)?;
+        return Ok(());
+    }
+
+    let (w, h, golden) = image::read_ppm(&mut File::open(path)?)?;
+    let result = if (w, h) != (width, height) {
+        Err(Mismatch::Size(w, h))
+    } else {
+        let mut diffs = actual
+            .chunks(3)
+            .zip(golden.chunks(3))
+            .enumerate()
+            .filter(|(_, (a, g))| a != g)
+            .map(|(i, _)| (i as u32 % width, i as u32 / width));
+        match diffs.next() {
+            None => Ok(()),
+            Some(first) => Err(Mismatch::Pixels {
+                count: diffs.count() + 1,
+                first,
+            }),
+        }
+    };
+
+    if result.is_err() {
+        lcd.save_png(with_suffix(path, ".actual.png"))?;
+        lcd.save_ppm(with_suffix(path, ".actual.ppm"))?;
+    }
+    result
+}
+
+/// Panics with the mismatch, for tests
+pub fn assert_matches<P: AsRef<Path>>(lcd: &SimLcd, path: P) {
+    if let Err(e) = check(lcd, path.as_ref()) {
+        panic!("{}: {}", path.as_ref().display(), e);
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub(crate) fn create(path: &Path) -> io::Result<BufWriter<File>> {
+    File::create(path).map(BufWriter::new)
+}
@@ -0,0 +1,143 @@
+//! PPM and PNG files of RGB565 pixels
+//!
+//! Pixels are widened to 8 bits per channel by repeating the high bits, so
+//! white stays 0xffffff. PPM (binary P6) is what golden images are stored as,
+//! being trivial to read back; PNG is for looking at them. Both writers are
+//! deterministic, the PNG uses uncompressed deflate blocks.
+
+use std::io::{self, Read, Write};
+
+/// 8-bit R, G, B of a raw RGB565 value
+pub fn rgb888(raw: u16) -> [u8; 3] {
+    let r = (raw >> 11) as u8 & 0x1f;
+    let g = (raw >> 5) as u8 & 0x3f;
+    let b = raw as u8 & 0x1f;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// RGB rows of raw RGB565 pixels
+pub fn to_rgb(pixels: &[u16]) -> Vec<u8> {
+    pixels.iter().flat_map(|&p| rgb888(p).to_vec()).collect()
+}
+
+/// Writes a binary PPM
+pub fn write_ppm<W: Write>(w: &mut W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    write!(w, "P6\n{} {}\n255\n", width, height)?;
+    w.write_all(rgb)
+}
+
+/// Reads a binary PPM with 255 as maximum, returns (width, height, rgb)
+pub fn read_ppm<R: Read>(r: &mut R) -> io::Result<(u32, u32, Vec<u8>)> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    // magic, width, height, maxval, separated by whitespace and comments
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(invalid("truncated PPM header"));
+        }
+        fields.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+    }
+    // one whitespace byte before the pixels
+    pos += 1;
+
+    if fields[0] != "P6" || fields[3] != "255" {
+        return Err(invalid("not an 8-bit binary PPM"));
+    }
+    let width: u32 = fields[1].parse().map_err(|_| invalid("bad PPM width"))?;
+    let height: u32 = fields[2].parse().map_err(|_| invalid("bad PPM height"))?;
+    let len = width as usize * height as usize * 3;
+    if data.len() < pos + len {
+        return Err(invalid("truncated PPM pixels"));
+    }
+    Ok((width, height, data[pos..pos + len].to_vec()))
+}
+
+/// Writes an 8-bit RGB PNG
+pub fn write_png<W: Write>(w: &mut W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8 bits, truecolour, deflate, no filter, no interlace
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(w, b"IHDR", &ihdr)?;
+
+    // every row starts with filter type 0
+    let stride = width as usize * 3;
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgb.chunks(stride.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(w, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(w, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32(crc32(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    w.write_all(&crc.to_be_bytes())
+}
+
+/// zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// CRC-32 of PNG chunks, reflected 0xedb88320, without final xor
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
@@ -0,0 +1,230 @@
+//! Headless stand-in for the on-board LCD, for rendering tests on the host
+//!
+//! `SimLcd` keeps the ST7735 frame memory and the visible window at the same
+//! offsets as `lcd::Lcd` with the default `LcdConfig`: 160x80 at (1, 26) in
+//! landscape. Draw on it with embedded-graphics, then compare it with a golden
+//! image:
+//!
+//! ```ignore
+//! let mut lcd = SimLcd::new();
+//! let mut console = LcdConsole::new(lcd);
+//! writeln!(console, "\x1b[31mred\x1b[0m text").unwrap();
+//! lcd_sim::golden::assert_matches(&console.release(), lcd_sim::golden::path("console"));
+//! ```
+//!
+//! The console, widgets and text layout are compiled in from the firmware
+//! sources, as `lcd_sim::console`, `lcd_sim::ui` and `lcd_sim::font`.
+//!
+//! The repo's `.cargo/config` targets the board, so test for the host
+//! explicitly:
+//!
+//! ```text
+//! $ cargo test --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::convert::Infallible;
+use std::io::{self, Write};
+use std::path::Path;
+
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Size;
+use embedded_graphics::pixelcolor::raw::{RawData, RawU16};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::DrawTarget;
+
+pub mod golden;
+pub mod image;
+
+// shared with the firmware
+#[path = "../../../src/lcd/console.rs"]
+pub mod console;
+#[path = "../../../src/ui/mod.rs"]
+pub mod ui;
+
+/// The font blob format and text layout, without the drawing
+#[path = "../../../src/font"]
+pub mod font {
+    pub mod format;
+    pub mod layout;
+}
+
+/// ST7735 frame memory, in landscape
+pub const MEMORY_WIDTH: u16 = 162;
+pub const MEMORY_HEIGHT: u16 = 132;
+
+/// Simulated LCD
+pub struct SimLcd {
+    /// Frame memory, raw RGB565
+    memory: Vec<u16>,
+    width: u16,
+    height: u16,
+    dx: u16,
+    dy: u16,
+    /// Address window in memory coordinates, inclusive, and the next pixel
+    window: (u16, u16, u16, u16),
+    cursor: (u16, u16),
+    /// Half of a pixel streamed by `write_data`
+    pending: Option<u8>,
+    writes: usize,
+}
+
+impl SimLcd {
+    /// The Longan Nano panel: 160x80 at (1, 26)
+    pub fn new() -> Self {
+        SimLcd::with_geometry(160, 80, 1, 26)
+    }
+
+    /// Another visible size and offset, like `LcdConfig::size` and `offset`.
+    /// Panics if it does not fit in frame memory.
+    pub fn with_geometry(width: u16, height: u16, dx: u16, dy: u16) -> Self {
+        assert!(
+            width > 0 && height > 0 && dx + width <= MEMORY_WIDTH && dy + height <= MEMORY_HEIGHT,
+            "{}x{} at ({}, {}) does not fit in frame memory",
+            width,
+            height,
+            dx,
+            dy
+        );
+        SimLcd {
+            memory: vec![0; MEMORY_WIDTH as usize * MEMORY_HEIGHT as usize],
+            width,
+            height,
+            dx,
+            dy,
+            window: (0, 0, MEMORY_WIDTH - 1, MEMORY_HEIGHT - 1),
+            cursor: (0, 0),
+            pending: None,
+            writes: 0,
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Raw RGB565 of a visible pixel
+    pub fn raw_pixel(&self, x: u16, y: u16) -> u16 {
+        self.memory[self.index(x + self.dx, y + self.dy)]
+    }
+
+    pub fn pixel(&self, x: u16, y: u16) -> Rgb565 {
+        RawU16::new(self.raw_pixel(x, y)).into()
+    }
+
+    /// Raw RGB565 of the visible pixels, row by row
+    pub fn pixels(&self) -> Vec<u16> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.raw_pixel(x, y))
+            .collect()
+    }
+
+    /// Pixels written since the start or `reset_writes`, to check that
+    /// widgets only redraw what changed
+    pub fn writes(&self) -> usize {
+        self.writes
+    }
+
+    pub fn reset_writes(&mut self) {
+        self.writes = 0;
+    }
+
+    /// Sets the window of the next `write_data`, in screen coordinates
+    /// (inclusive), like `RawLcd::set_address_window`
+    pub fn set_address_window(&mut self, sx: u16, sy: u16, ex: u16, ey: u16) {
+        let (dx, dy) = (self.dx, self.dy);
+        self.window = (sx + dx, sy + dy, ex + dx, ey + dy);
+        self.cursor = (sx + dx, sy + dy);
+        self.pending = None;
+    }
+
+    /// Streams big endian RGB565 bytes into the window, wrapping at its right
+    /// edge and back to the top after the last line, like the ST7735
+    pub fn write_data(&mut self, data: &[u8]) {
+        for &b in data {
+            let hi = match self.pending.take() {
+                Some(hi) => hi,
+                None => {
+                    self.pending = Some(b);
+                    continue;
+                }
+            };
+            let (x, y) = self.cursor;
+            if x < MEMORY_WIDTH && y < MEMORY_HEIGHT {
+                let i = self.index(x, y);
+                self.memory[i] = u16::from_be_bytes([hi, b]);
+                self.writes += 1;
+            }
+            let (sx, sy, ex, ey) = self.window;
+            self.cursor = if x < ex {
+                (x + 1, y)
+            } else if y < ey {
+                (sx, y + 1)
+            } else {
+                (sx, sy)
+            };
+        }
+    }
+
+    /// Same as `lcd::blit`: big endian RGB565 `data` into an inclusive
+    /// rectangle of the screen
+    pub fn blit(&mut self, x0: u16, y0: u16, x1: u16, y1: u16, data: &[u8]) {
+        self.set_address_window(x0, y0, x1, y1);
+        self.write_data(data);
+    }
+
+    /// Saves the visible screen as binary PPM
+    pub fn save_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = golden::create(path.as_ref())?;
+        image::write_ppm(&mut w, self.width as u32, self.height as u32, &self.rgb())?;
+        w.flush()
+    }
+
+    /// Saves the visible screen as PNG
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = golden::create(path.as_ref())?;
+        image::write_png(&mut w, self.width as u32, self.height as u32, &self.rgb())?;
+        w.flush()
+    }
+
+    fn rgb(&self) -> Vec<u8> {
+        image::to_rgb(&self.pixels())
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * MEMORY_WIDTH as usize + x as usize
+    }
+}
+
+impl Default for SimLcd {
+    fn default() -> Self {
+        SimLcd::new()
+    }
+}
+
+impl DrawTarget<Rgb565> for SimLcd {
+    type Error = Infallible;
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+        let Pixel(point, color) = pixel;
+        if point.x < 0 || point.y < 0 {
+            return Ok(());
+        }
+        let (x, y) = (point.x as u16, point.y as u16);
+        if x >= self.width || y >= self.height {
+            return Ok(());
+        }
+        let i = self.index(x + self.dx, y + self.dy);
+        self.memory[i] = RawU16::from(color).into_inner();
+        self.writes += 1;
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt::Write;
+
+use lcd_sim::console::{LcdConsole, PALETTE};
+use lcd_sim::golden::{self, assert_matches};
+use lcd_sim::SimLcd;
+
+#[test]
+fn draws_colors() {
+    let mut console = LcdConsole::new(SimLcd::new());
+    assert_eq!(console.size(), (26, 10));
+    for fg in 30..38 {
+        write!(console, "\x1b[{}m{}", fg, fg).unwrap();
+    }
+    writeln!(console, "\x1b[0m").unwrap();
+    for fg in 90..98 {
+        write!(console, "\x1b[{}m{}", fg, fg).unwrap();
+    }
+    writeln!(console, "\x1b[0m").unwrap();
+    for bg in 40..48 {
+        write!(console, "\x1b[{}m{} ", bg, bg - 40).unwrap();
+    }
+    writeln!(console, "\x1b[0m").unwrap();
+    writeln!(console, "\x1b[1;31mbright\x1b[22m normal\x1b[0m").unwrap();
+    writeln!(console, "\x1b[44mcleared to the end\x1b[K").unwrap();
+    write!(console, "\x1b[0m\x1b[8;12Hmoved\tx\x08y").unwrap();
+    assert_matches(&console.release(), golden::path("console_colors"));
+}
+
+#[test]
+fn scrolls() {
+    let mut console = LcdConsole::new(SimLcd::new());
+    for i in 0..14 {
+        writeln!(console, "\x1b[3{}mline {}\x1b[0m", i % 7 + 1, i).unwrap();
+    }
+    // wraps at the right edge, scrolling again
+    write!(console, "{}", "0123456789".repeat(3)).unwrap();
+    assert_eq!(console.cursor(), (4, 9));
+    assert_matches(&console.release(), golden::path("console_scroll"));
+}
+
+#[test]
+fn redraws_only_changed_cells() {
+    let mut console = LcdConsole::new(SimLcd::new());
+    for i in 0..10 {
+        write!(console, "\nrow {}", i % 2).unwrap();
+    }
+    console.display().reset_writes();
+    // every other row stays the same after scrolling
+    writeln!(console).unwrap();
+    let writes = console.display().writes();
+    assert!(writes > 0 && writes < 160 * 80 / 2, "{} writes", writes);
+}
+
+#[test]
+fn fits_a_tiny_display() {
+    let mut console = LcdConsole::new(SimLcd::with_geometry(4, 5, 1, 26));
+    assert_eq!(console.size(), (1, 1));
+    writeln!(console, "\x1b[32mab\x1b[0m").unwrap();
+    write!(console, "\x1b[41mc").unwrap();
+    assert_eq!(console.cursor(), (0, 0));
+    let lcd = console.release();
+    assert_eq!(lcd.pixel(0, 0), PALETTE[1]);
+    assert_matches(&lcd, golden::path("console_tiny"));
+}
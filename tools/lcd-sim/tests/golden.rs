@@ -0,0 +1,92 @@
+use std::env;
+use std::fs;
+use std::io;
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::*;
+use lcd_sim::golden::{check, Mismatch, BLESS_VAR};
+use lcd_sim::image::read_ppm;
+use lcd_sim::SimLcd;
+
+fn screen(color: Rgb565) -> SimLcd {
+    let mut lcd = SimLcd::with_geometry(8, 4, 0, 0);
+    lcd.clear(color).unwrap();
+    lcd
+}
+
+// one test, as it sets the environment
+#[test]
+fn checks_and_blesses() {
+    let dir = env::temp_dir().join(format!("lcd-sim-golden-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let golden = dir.join("screen.ppm");
+    let actual_png = dir.join("screen.ppm.actual.png");
+    let actual_ppm = dir.join("screen.ppm.actual.ppm");
+    env::remove_var(BLESS_VAR);
+
+    // a missing golden image is only written when blessing
+    let blue = screen(Rgb565::BLUE);
+    match check(&blue, &golden) {
+        Err(Mismatch::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        other => panic!("{:?}", other),
+    }
+    assert!(!golden.exists());
+    env::set_var(BLESS_VAR, "1");
+    check(&blue, &golden).unwrap();
+    env::remove_var(BLESS_VAR);
+    let (w, h, rgb) = read_ppm(&mut fs::File::open(&golden).unwrap()).unwrap();
+    assert_eq!((w, h), (8, 4));
+    assert_eq!(&rgb[..3], [0, 0, 0xff]);
+    check(&blue, &golden).unwrap();
+    assert!(!actual_png.exists());
+
+    let mut changed = screen(Rgb565::BLUE);
+    Pixel(Point::new(5, 2), Rgb565::RED)
+        .draw(&mut changed)
+        .unwrap();
+    Pixel(Point::new(1, 3), Rgb565::RED)
+        .draw(&mut changed)
+        .unwrap();
+    match check(&changed, &golden) {
+        Err(Mismatch::Pixels { count, first }) => assert_eq!((count, first), (2, (5, 2))),
+        other => panic!("{:?}", other),
+    }
+    assert!(fs::read(&actual_png).unwrap().starts_with(b"\x89PNG"));
+    let (_, _, rgb) = read_ppm(&mut fs::File::open(&actual_ppm).unwrap()).unwrap();
+    assert_eq!(&rgb[3 * (8 * 2 + 5)..][..3], [0xff, 0, 0]);
+
+    let other = SimLcd::with_geometry(4, 8, 0, 0);
+    match check(&other, &golden) {
+        Err(Mismatch::Size(w, h)) => assert_eq!((w, h), (8, 4)),
+        other => panic!("{:?}", other),
+    }
+
+    // blessing takes the new screen
+    env::set_var(BLESS_VAR, "1");
+    check(&changed, &golden).unwrap();
+    env::remove_var(BLESS_VAR);
+    check(&changed, &golden).unwrap();
+    assert!(check(&blue, &golden).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn finds_golden_images() {
+    let path = lcd_sim::golden::path("labels");
+    assert!(path.ends_with("tests/golden/labels.ppm"));
+    assert!(path.exists());
+}
+
+#[test]
+fn mismatch_messages() {
+    let message = |m: Mismatch| m.to_string();
+    assert_eq!(message(Mismatch::Size(3, 2)), "golden image is 3x2");
+    assert_eq!(
+        message(Mismatch::Pixels {
+            count: 4,
+            first: (1, 2)
+        }),
+        "4 pixels differ, first at (1, 2)"
+    );
+}
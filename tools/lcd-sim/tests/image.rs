@@ -0,0 +1,124 @@
+use lcd_sim::image::{read_ppm, rgb888, to_rgb, write_png, write_ppm};
+
+/// 3x2 of distinct colours
+fn pixels() -> Vec<u8> {
+    to_rgb(&[0x0000, 0xf800, 0x07e0, 0x001f, 0xffff, 0x8410])
+}
+
+#[test]
+fn widens_rgb565() {
+    assert_eq!(rgb888(0x0000), [0, 0, 0]);
+    assert_eq!(rgb888(0xffff), [0xff, 0xff, 0xff]);
+    assert_eq!(rgb888(0xf800), [0xff, 0, 0]);
+    assert_eq!(rgb888(0x07e0), [0, 0xff, 0]);
+    assert_eq!(rgb888(0x8410), [0x84, 0x82, 0x84]);
+}
+
+#[test]
+fn round_trips_ppm() {
+    let rgb = pixels();
+    let mut data = Vec::new();
+    write_ppm(&mut data, 3, 2, &rgb).unwrap();
+    assert!(data.starts_with(b"P6\n3 2\n255\n"));
+    assert_eq!(read_ppm(&mut &data[..]).unwrap(), (3, 2, rgb.clone()));
+
+    // other whitespace and comments in the header
+    let mut data = b"P6 # from an editor\n3\t2\n# max\n255\n".to_vec();
+    data.extend_from_slice(&rgb);
+    assert_eq!(read_ppm(&mut &data[..]).unwrap(), (3, 2, rgb));
+}
+
+#[test]
+fn rejects_bad_ppm() {
+    let bad = |data: &[u8]| read_ppm(&mut &data[..]).unwrap_err().to_string();
+    assert_eq!(bad(b"P6\n3 2\n"), "truncated PPM header");
+    assert_eq!(bad(b"P3\n1 1\n255\n000"), "not an 8-bit binary PPM");
+    assert_eq!(bad(b"P6\n1 1\n65535\n000000"), "not an 8-bit binary PPM");
+    assert_eq!(bad(b"P6\nx 1\n255\n000"), "bad PPM width");
+    assert_eq!(bad(b"P6\n2 2\n255\n000000"), "truncated PPM pixels");
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// (kind, data) of the chunks, checking their CRCs
+fn chunks(mut png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    let mut chunks = Vec::new();
+    while !png.is_empty() {
+        let len = u32::from_be_bytes([png[0], png[1], png[2], png[3]]) as usize;
+        let body = &png[4..8 + len];
+        let crc = &png[8 + len..12 + len];
+        assert_eq!(crc, crc32(body).to_be_bytes());
+        chunks.push(([body[0], body[1], body[2], body[3]], body[4..].to_vec()));
+        png = &png[12 + len..];
+    }
+    chunks
+}
+
+/// Contents of a zlib stream of stored blocks
+fn inflate_stored(mut zlib: &[u8]) -> Vec<u8> {
+    assert_eq!(&zlib[..2], [0x78, 0x01]);
+    assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+    zlib = &zlib[2..];
+    let mut out = Vec::new();
+    loop {
+        let last = zlib[0] == 1;
+        let len = u16::from_le_bytes([zlib[1], zlib[2]]);
+        assert_eq!(!len, u16::from_le_bytes([zlib[3], zlib[4]]));
+        out.extend_from_slice(&zlib[5..5 + len as usize]);
+        zlib = &zlib[5 + len as usize..];
+        if last {
+            break;
+        }
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in &out {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    assert_eq!(zlib, (b << 16 | a).to_be_bytes());
+    out
+}
+
+#[test]
+fn writes_png() {
+    let rgb = pixels();
+    let mut png = Vec::new();
+    write_png(&mut png, 3, 2, &rgb).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    let chunks = chunks(&png[8..]);
+    let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| &kind[..]).collect();
+    assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+    let mut rows = vec![0];
+    rows.extend_from_slice(&rgb[..9]);
+    rows.push(0);
+    rows.extend_from_slice(&rgb[9..]);
+    assert_eq!(inflate_stored(&chunks[1].1), rows);
+    assert!(chunks[2].1.is_empty());
+}
+
+#[test]
+fn writes_large_png() {
+    // more than one stored block
+    let rgb: Vec<u8> = (0..200 * 120 * 3).map(|i| i as u8).collect();
+    let mut png = Vec::new();
+    write_png(&mut png, 200, 120, &rgb).unwrap();
+    let raw = inflate_stored(&chunks(&png[8..])[1].1);
+    assert_eq!(raw.len(), 120 * 601);
+    assert!(raw.chunks(601).all(|row| row[0] == 0));
+    assert_eq!(&raw[601 * 119 + 1..], &rgb[600 * 119..]);
+}
@@ -0,0 +1,135 @@
+use embedded_graphics::fonts::{Font6x8, Font8x16};
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use lcd_sim::golden::{self, assert_matches};
+use lcd_sim::ui::layout::{self, Grid, Stack};
+use lcd_sim::ui::{
+    self, rect, Align, Chart, Gauge, Icon, Label, ProgressBar, Readout, Scale, StatusBar, Style,
+    Widget,
+};
+use lcd_sim::SimLcd;
+
+/// Draws `widget` on a fresh screen
+fn render<W: Widget<SimLcd>>(widget: &mut W) -> SimLcd {
+    let mut lcd = SimLcd::new();
+    widget.invalidate();
+    widget.draw(&mut lcd).unwrap();
+    lcd
+}
+
+/// 8x8 heart
+const HEART: [u8; 8] = [
+    0b0110_0110,
+    0b1111_1111,
+    0b1111_1111,
+    0b1111_1111,
+    0b0111_1110,
+    0b0011_1100,
+    0b0001_1000,
+    0b0000_0000,
+];
+
+#[test]
+fn labels() {
+    let grid = Grid::new(layout::screen(160, 80), 1, 4).gap(2);
+    let mut left = Label::new(grid.cell(0, 0), Font6x8, "left");
+    let mut center = Label::new(grid.cell(0, 1), Font6x8, "center").align(Align::Center);
+    let mut right = Label::new(grid.cell(0, 2), Font6x8, "right")
+        .align(Align::Right)
+        .style(Style::new(Rgb565::BLACK, Rgb565::YELLOW, Rgb565::RED));
+    let mut readout = Readout::new(grid.cell(0, 3), Font8x16, "V").decimals(2);
+    readout.set(3300);
+
+    let mut lcd = SimLcd::new();
+    ui::update(
+        &mut lcd,
+        &mut [&mut left, &mut center, &mut right, &mut readout],
+    )
+    .unwrap();
+    assert_matches(&lcd, golden::path("labels"));
+
+    // shorter text leaves nothing of the longer one behind
+    readout.set(-5);
+    center.set_text("c");
+    ui::update(&mut lcd, &mut [&mut center, &mut readout]).unwrap();
+    center.set_text("center");
+    readout.set(3300);
+    ui::update(&mut lcd, &mut [&mut center, &mut readout]).unwrap();
+    assert_matches(&lcd, golden::path("labels"));
+}
+
+#[test]
+fn progress_bar() {
+    let mut bar = ProgressBar::new(rect(Point::new(10, 30), Size::new(140, 20)), 200);
+    bar.set(150);
+    let mut lcd = render(&mut bar);
+    assert_matches(&lcd, golden::path("progress_bar"));
+
+    // emptied then refilled in place
+    bar.set(20);
+    bar.draw(&mut lcd).unwrap();
+    assert_eq!(lcd.pixels(), render(&mut bar).pixels());
+    bar.set(150);
+    bar.draw(&mut lcd).unwrap();
+    assert_matches(&lcd, golden::path("progress_bar"));
+}
+
+#[test]
+fn gauge() {
+    let mut gauge = Gauge::new(rect(Point::new(50, 0), Size::new(60, 80)), 100).thickness(6);
+    gauge.set(60);
+    let mut lcd = render(&mut gauge);
+    assert_matches(&lcd, golden::path("gauge"));
+
+    gauge.set(95);
+    gauge.draw(&mut lcd).unwrap();
+    gauge.set(60);
+    gauge.draw(&mut lcd).unwrap();
+    assert_matches(&lcd, golden::path("gauge"));
+}
+
+#[test]
+fn icon() {
+    let mut icon = Icon::new(Point::new(76, 36), 8, 8, &HEART).style(Style::new(
+        Rgb565::RED,
+        Rgb565::BLACK,
+        Rgb565::RED,
+    ));
+    let mut lcd = render(&mut icon);
+    assert_matches(&lcd, golden::path("icon"));
+
+    icon.set_visible(false);
+    icon.draw(&mut lcd).unwrap();
+    assert!(lcd.pixels().iter().all(|&p| p == 0));
+}
+
+#[test]
+fn status_bar() {
+    let mut rows = Stack::vertical(layout::screen(160, 80));
+    let mut status = StatusBar::new(rows.take(10));
+    status.set_left("longan nano");
+    status.set_right_fmt(format_args!("{:02}:{:02}", 12, 34));
+    let lcd = render(&mut status);
+    assert_matches(&lcd, golden::path("status_bar"));
+}
+
+#[test]
+fn chart() {
+    let mut chart = Chart::new(rect(Point::new(0, 10), Size::new(160, 70)), 2)
+        .scale(Scale::Fixed(-100, 100))
+        .grid(20, 4);
+    let mut lcd = SimLcd::new();
+    // a sawtooth and a square wave, wrapping once
+    for i in 0..200 {
+        let sawtooth = (i % 80 - 40) * 5 / 2;
+        let square = if i / 25 % 2 == 0 { 60 } else { -60 };
+        chart.push(&[sawtooth, square]);
+        if i % 30 == 0 {
+            chart.draw(&mut lcd).unwrap();
+        }
+    }
+    chart.draw(&mut lcd).unwrap();
+    assert_matches(&lcd, golden::path("chart"));
+    // drawing as the samples came in matches drawing them all at once
+    assert_eq!(lcd.pixels(), render(&mut chart).pixels());
+}
@@ -0,0 +1,106 @@
+//! Decodes the framed packets of `longan_nano_playground::packet`
+//!
+//! Reads a serial device, or a file captured from one, and prints each
+//! packet with its sequence number and kind:
+//!
+//! ```text
+//! $ stty -F /dev/ttyUSB0 115200 raw -echo
+//! $ packet-dump /dev/ttyUSB0
+//! #0 kind=1 len=2: 3a 07
+//! #1 kind=1 len=2: 39 07
+//! error: Crc
+//! #3 kind=1 len=2: 3b 07 (lost 1)
+//! ```
+//!
+//! `-` reads stdin. `--raw` writes payloads to stdout instead, e.g. to pipe
+//! them into another tool.
+//!
+//! The repo's `.cargo/config` targets the board, so build for the host
+//! explicitly:
+//!
+//! ```text
+//! $ cargo run --target x86_64-unknown-linux-gnu -- capture.bin
+//! ```
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+// the decoder is shared with the firmware
+#[path = "../../../src/packet/mod.rs"]
+#[allow(dead_code)]
+mod packet;
+
+use packet::{Decoder, SoftCrc};
+
+fn usage() -> ! {
+    eprintln!("usage: packet-dump [--raw] <serial device | capture file | ->");
+    process::exit(2);
+}
+
+fn main() {
+    let mut raw = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--raw" => raw = true,
+            "-h" | "--help" => usage(),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let path = path.unwrap_or_else(|| usage());
+
+    let input: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin())
+    } else {
+        match File::open(&path) {
+            Ok(f) => Box::new(f),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    };
+
+    if let Err(e) = dump(input, raw) {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    }
+}
+
+fn dump(mut input: Box<dyn Read>, raw: bool) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut decoder = Decoder::new(SoftCrc);
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &b in &buf[..n] {
+            let lost = decoder.lost();
+            match decoder.feed(b) {
+                None => {}
+                Some(Ok(p)) if raw => out.write_all(p.payload)?,
+                Some(Ok(p)) => {
+                    write!(out, "#{} kind={} len={}:", p.seq, p.kind, p.payload.len())?;
+                    for b in p.payload {
+                        write!(out, " {:02x}", b)?;
+                    }
+                    let lost = decoder.lost() - lost;
+                    if lost > 0 {
+                        write!(out, " (lost {})", lost)?;
+                    }
+                    writeln!(out)?;
+                }
+                Some(Err(e)) => eprintln!("error: {:?}", e),
+            }
+        }
+        out.flush()?;
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Frames built by hand the way `packet::Encoder` sends them, COBS with a
+/// CRC-32/MPEG-2: the tail of an earlier frame, then #0 to #3 with a bit
+/// flipped in #1
+const STREAM: &[u8] = &[
+    // the tail of a frame whose start is missing
+    0x5d, 0x2e, 0x07, 0x00, //
+    // #0 kind=1: 3a 07
+    0x01, 0x08, 0x01, 0x3a, 0x07, 0x91, 0x51, 0xd3, 0xc2, 0x00, //
+    // #1 kind=1: 39 07, received as 29 07
+    0x09, 0x01, 0x01, 0x29, 0x07, 0xf5, 0x94, 0x55, 0x6c, 0x00, //
+    // #2 kind=1: 3b 07
+    0x09, 0x02, 0x01, 0x3b, 0x07, 0x94, 0xb8, 0xd0, 0xac, 0x00, //
+    // #3 kind=2: 00 ff 10
+    0x03, 0x03, 0x02, 0x07, 0xff, 0x10, 0x6b, 0xc4, 0xb3, 0x22, 0x00,
+];
+
+fn packet_dump(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_packet-dump"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    output
+}
+
+#[test]
+fn skips_corrupted_frames() {
+    let output = packet_dump(&["-"], STREAM);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#0 kind=1 len=2: 3a 07\n\
+         #2 kind=1 len=2: 3b 07 (lost 1)\n\
+         #3 kind=2 len=3: 00 ff 10\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: Malformed\nerror: Crc\n"
+    );
+}
+
+#[test]
+fn writes_raw_payloads() {
+    let output = packet_dump(&["--raw", "-"], STREAM);
+    assert_eq!(output.stdout, [0x3a, 0x07, 0x3b, 0x07, 0x00, 0xff, 0x10]);
+}
+
+#[test]
+fn resynchronizes_mid_frame() {
+    // started listening in the middle of #0
+    let output = packet_dump(&["-"], &STREAM[8..]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#2 kind=1 len=2: 3b 07\n#3 kind=2 len=3: 00 ff 10\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: Malformed\nerror: Crc\n"
+    );
+}